@@ -0,0 +1,131 @@
+use crate::buffer::Buffer;
+use crate::editor::Editor;
+use std::io::Read;
+
+pub const USAGE: &str = "usage: uu [options] [file ...]
+  +            start at the last line
+  +{number}    start at line {number}
+  +/{pattern}  start at the first line with {pattern}
+  +{command}   run {command} once the first file is read
+  -c {command} the same
+  -R           read-only, :w needs a !
+  -            read the text from stdin
+  -h, --help   show this and quit";
+
+// what uu was started with
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Arguments {
+    pub files: Vec<String>,
+    // in the order they were given, /pattern is a search
+    pub commands: Vec<String>,
+    pub read_only: bool,
+    pub stdin: bool,
+    pub help: bool,
+}
+
+// anything after -- is a file, even when it starts with - or +
+pub fn parse_arguments(arguments: impl IntoIterator<Item = String>) -> Result<Arguments, String> {
+    let mut parsed = Arguments::default();
+    let mut arguments = arguments.into_iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--" => parsed.files.extend(&mut arguments),
+            "-" => parsed.stdin = true,
+            "-R" => parsed.read_only = true,
+            "-h" | "--help" => parsed.help = true,
+            "-c" => {
+                let command = arguments.next().ok_or("argument missing after -c")?;
+                parsed.commands.push(command);
+            }
+            "+" => parsed.commands.push("$".into()),
+            _ if argument.starts_with('+') => parsed.commands.push(argument[1..].to_string()),
+            _ if argument.starts_with('-') => return Err(format!("unknown option: {}", argument)),
+            _ => parsed.files.push(argument),
+        }
+    }
+    Ok(parsed)
+}
+
+impl Editor {
+    // the first file, or stdin, is the one edited and the others wait in the buffer list. without
+    // any there's an empty buffer with no name. an error is shown once everything has run
+    pub fn start(&mut self, arguments: Arguments) {
+        if arguments.read_only {
+            let result = self.run_command("set readonly");
+            self.report(result);
+        }
+        let mut files = arguments.files.iter();
+        if arguments.stdin {
+            let mut text = String::new();
+            match std::io::stdin().read_to_string(&mut text) {
+                Ok(_) => self.buffer = Buffer::from(&text),
+                Err(error) => self.status_message = format!("Can't read stdin: {}", error),
+            }
+        } else if let Some(path) = files.next() {
+            let result = self.open_file(path);
+            self.report(result);
+        }
+        for path in files {
+            let result = self.add_buffer(path);
+            self.report(result);
+        }
+        for command in &arguments.commands {
+            let result = match command.strip_prefix('/') {
+                Some(pattern) => self.execute_search(pattern, true),
+                None => self.run_command(command),
+            };
+            self.report(result);
+        }
+        self.scroll_to_cursor();
+    }
+}
+
+mod test {
+    #[test]
+    fn should_start_with_the_files_and_commands_given() {
+        use super::parse_arguments;
+        let arguments = |line: &str| parse_arguments(line.split_whitespace().map(String::from));
+
+        let parsed = arguments("-R +42 one.txt -c nohlsearch two.txt + -- -three").unwrap();
+        assert_eq!(vec!["one.txt", "two.txt", "-three"], parsed.files);
+        assert_eq!(vec!["42", "nohlsearch", "$"], parsed.commands);
+        assert!(parsed.read_only && !parsed.stdin && !parsed.help);
+        assert!(arguments("- --help").unwrap().stdin);
+        assert_eq!(
+            Err("argument missing after -c".into()),
+            arguments("one.txt -c")
+        );
+        assert_eq!(Err("unknown option: -x".into()), arguments("-x"));
+
+        let directory = std::env::temp_dir().join(format!("uu-args-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let first = directory.join("first.txt");
+        let second = directory.join("second.txt");
+        std::fs::write(&first, "one\ntwo\nthree\nfour\n").unwrap();
+        let (first, second) = (first.to_str().unwrap(), second.to_str().unwrap());
+
+        let mut editor = crate::editor::Editor::new();
+        let line = format!("+/thr {} {} +2 -c d", first, second);
+        editor.start(arguments(&line).unwrap());
+        assert_eq!(first, editor.editing_file_path);
+        assert_eq!("one\nthree\nfour\n", editor.buffer.as_string());
+        assert_eq!((0, 1), editor.buffer.cursor());
+        assert_eq!(second, editor.other_buffers[0].path);
+        assert_eq!(2, editor.other_buffers[0].number);
+
+        let mut editor = crate::editor::Editor::new();
+        editor.start(arguments(&format!("-R -c 3 {}", first)).unwrap());
+        assert_eq!((0, 2), editor.buffer.cursor());
+        assert_eq!(
+            Err("E45: 'readonly' option is set (add ! to override)".into()),
+            editor.run_command("w")
+        );
+        editor.run_command("w!").unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let mut editor = crate::editor::Editor::new();
+        editor.start(Default::default());
+        assert_eq!("", editor.editing_file_path);
+        assert_eq!("", editor.buffer.as_string());
+    }
+}
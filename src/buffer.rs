@@ -1,4 +1,10 @@
 use crate::{min, UnicodeSegmentation, editor::Mode};
+use crate::options::OptionValue;
+use crate::undo::{self, Edit, UndoStep, UndoTree};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+use std::time::SystemTime;
 #[derive(Debug, Clone, Copy)]
 pub enum LineSeparatorFormat {
     UNIX,
@@ -21,15 +27,60 @@ impl std::fmt::Display for LineSeparatorFormat {
         write!(f, "{}", t)
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Source {
+    Original,
+    Add,
+}
+
+// a span of one of the two backing buffers. line_breaks caches how many '\n' it contains,
+// so finding a line only needs to walk the piece list and binary search inside one piece.
+#[derive(Debug, Clone, Copy)]
+struct Piece {
+    source: Source,
+    start: usize,
+    length: usize,
+    line_breaks: usize,
+}
+impl Piece {
+    fn end(&self) -> usize {
+        self.start + self.length
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Buffer {
-    lines: Vec<String>,
+    original: String,
+    original_line_breaks: Vec<usize>,
+    add: String,
+    add_line_breaks: Vec<usize>,
+    pieces: Vec<Piece>,
+    length: usize,
+    line_breaks: usize,
 
     cursor_x: usize,
     cursor_y: usize,
     reminder_cursor_x: usize,
 
+    history: UndoTree,
+    pending_undo_step: Option<UndoStep>,
+    undo_group_depth: usize,
+    // the undo state the text was in when it was last read or written
+    saved_undo_state: usize,
+    // starts of the lines marked by :g, kept pointing at the same lines while the text changes
+    line_marks: Vec<usize>,
+    // offsets of the marks set with m and the ones set on their own: ' where the last jump came
+    // from, . the last change, ^ where insert mode was left, [ and ] the start and end of the
+    // last changed or yanked text, < and > the last visual selection
+    marks: HashMap<char, usize>,
+    // Ctrl-O goes back through these, Ctrl-I forward again
+    jumps: Vec<usize>,
+    jump_index: usize,
+
     pub line_separator_format: LineSeparatorFormat,
+    // the buffer's own values of buffer-local options, the others come from the editor
+    pub(crate) local_options: HashMap<&'static str, OptionValue>,
 }
 impl Buffer {
     pub fn from(s: &str) -> Self {
@@ -41,33 +92,355 @@ impl Buffer {
                 LineSeparatorFormat::UNIX
             }
         };
-        let lines = s
-            .split(line_separator_format.separator())
-            .map(str::to_string)
-            .collect::<Vec<_>>();
+        let original = match line_separator_format {
+            LineSeparatorFormat::UNIX => s.to_string(),
+            LineSeparatorFormat::DOS => s.replace("\r\n", "\n"),
+        };
+        let original_line_breaks = line_breaks_of(&original, 0);
+        let length = original.len();
+        let line_breaks = original_line_breaks.len();
+        let pieces = if length > 0 {
+            vec![Piece {
+                source: Source::Original,
+                start: 0,
+                length,
+                line_breaks,
+            }]
+        } else {
+            vec![]
+        };
         let cursor_x = 0;
         let cursor_y = 0;
         let reminder_cursor_x = 0;
         Buffer {
-            lines,
+            original,
+            original_line_breaks,
+            add: String::new(),
+            add_line_breaks: vec![],
+            pieces,
+            length,
+            line_breaks,
             cursor_x,
             cursor_y,
             reminder_cursor_x,
+            history: UndoTree::new(),
+            pending_undo_step: None,
+            undo_group_depth: 0,
+            saved_undo_state: 0,
+            line_marks: vec![],
+            marks: HashMap::new(),
+            jumps: vec![],
+            jump_index: 0,
             line_separator_format,
+            local_options: HashMap::new(),
         }
     }
     pub fn as_string(&self) -> String {
-        self.lines.join("\n")
+        let mut result = String::with_capacity(self.length);
+        for chunk in self.chunks() {
+            result.push_str(chunk);
+        }
+        result
+    }
+    pub fn len(&self) -> usize {
+        self.length
+    }
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+    pub fn line_count(&self) -> usize {
+        self.line_breaks + 1
+    }
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.pieces.iter().map(move |piece| self.piece_text(piece))
+    }
+    pub fn slice(&self, range: Range<usize>) -> String {
+        debug_assert!(range.start <= range.end && range.end <= self.length);
+        let mut result = String::with_capacity(range.end - range.start);
+        let mut piece_offset = 0;
+        for piece in &self.pieces {
+            let piece_end = piece_offset + piece.length;
+            if piece_end > range.start && piece_offset < range.end {
+                let from = range.start.max(piece_offset) - piece_offset;
+                let to = min(range.end, piece_end) - piece_offset;
+                result.push_str(&self.piece_text(piece)[from..to]);
+            }
+            if piece_end >= range.end {
+                break;
+            }
+            piece_offset = piece_end;
+        }
+        result
+    }
+    pub fn line_start(&self, y: usize) -> usize {
+        debug_assert!(y < self.line_count());
+        if y == 0 {
+            return 0;
+        }
+        let mut piece_offset = 0;
+        let mut breaks_before = 0;
+        for piece in &self.pieces {
+            if breaks_before + piece.line_breaks >= y {
+                let breaks = self.source_line_breaks(piece.source);
+                let first = breaks.partition_point(|&b| b < piece.start);
+                let line_break = breaks[first + (y - breaks_before - 1)];
+                return piece_offset + (line_break - piece.start) + 1;
+            }
+            breaks_before += piece.line_breaks;
+            piece_offset += piece.length;
+        }
+        self.length
+    }
+    pub fn line_end(&self, y: usize) -> usize {
+        if y + 1 < self.line_count() {
+            self.line_start(y + 1) - 1
+        } else {
+            self.length
+        }
+    }
+    pub fn line(&self, y: usize) -> String {
+        self.slice(self.line_start(y)..self.line_end(y))
+    }
+    pub fn line_length(&self, y: usize) -> usize {
+        UnicodeSegmentation::graphemes(self.line(y).as_str(), true).count()
+    }
+    pub fn first_non_blank(&self, y: usize) -> usize {
+        let line = self.line(y);
+        UnicodeSegmentation::graphemes(line.as_str(), true)
+            .position(|g| !g.trim().is_empty())
+            .unwrap_or_else(|| self.line_length(y).saturating_sub(1))
+    }
+    pub fn line_of_offset(&self, offset: usize) -> usize {
+        debug_assert!(offset <= self.length);
+        let mut piece_offset = 0;
+        let mut breaks_before = 0;
+        for piece in &self.pieces {
+            if piece_offset + piece.length > offset {
+                let breaks = self.source_line_breaks(piece.source);
+                let position = piece.start + (offset - piece_offset);
+                return breaks_before + count_between(breaks, piece.start, position);
+            }
+            breaks_before += piece.line_breaks;
+            piece_offset += piece.length;
+        }
+        breaks_before
+    }
+    pub fn position_of(&self, offset: usize) -> (usize, usize) {
+        let y = self.line_of_offset(offset);
+        let start = self.line_start(y);
+        let x = UnicodeSegmentation::graphemes(self.slice(start..offset).as_str(), true).count();
+        (x, y)
+    }
+    pub fn offset_of(&self, x: usize, y: usize) -> usize {
+        let start = self.line_start(y);
+        let line = self.line(y);
+        let in_line = UnicodeSegmentation::grapheme_indices(line.as_str(), true)
+            .nth(x)
+            .map(|(i, _)| i)
+            .unwrap_or_else(|| line.len());
+        start + in_line
+    }
+    pub fn insert(&mut self, offset: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.record(Edit::INSERT {
+            offset,
+            text: text.to_string(),
+        });
+        self.apply_insert(offset, text);
+        let last = text.chars().last().map_or(0, char::len_utf8);
+        self.marks.insert('.', offset);
+        self.marks.insert('[', offset);
+        self.marks.insert(']', offset + text.len() - last);
+    }
+    pub fn delete(&mut self, range: Range<usize>) -> String {
+        let deleted = self.apply_delete(range.clone());
+        if !deleted.is_empty() {
+            self.record(Edit::DELETE {
+                offset: range.start,
+                text: deleted.clone(),
+            });
+            for name in ['.', '[', ']'].iter() {
+                self.marks.insert(*name, range.start);
+            }
+        }
+        deleted
+    }
+    fn apply_insert(&mut self, offset: usize, text: &str) {
+        debug_assert!(offset <= self.length);
+        if text.is_empty() {
+            return;
+        }
+        // a mark at the insertion point follows its line down when the text breaks lines
+        let after_break = text.rfind('\n').map(|i| i + 1);
+        for mark in &mut self.line_marks {
+            if *mark > offset {
+                *mark += text.len();
+            } else if *mark == offset {
+                *mark += after_break.unwrap_or(0);
+            }
+        }
+        // the others stay on the character they were on
+        for mark in self.marks.values_mut().chain(self.jumps.iter_mut()) {
+            if *mark >= offset {
+                *mark += text.len();
+            }
+        }
+        let start = self.add.len();
+        self.add.push_str(text);
+        self.add_line_breaks.extend(line_breaks_of(text, start));
+        let line_breaks = count_between(&self.add_line_breaks, start, self.add.len());
+        self.length += text.len();
+        self.line_breaks += line_breaks;
+
+        let (index, in_piece) = self.locate(offset);
+        // typing appends to the add buffer right after the previous insertion, so grow that piece
+        // instead of creating one per keystroke
+        if in_piece == 0 && index > 0 {
+            let previous = &mut self.pieces[index - 1];
+            if previous.source == Source::Add && previous.end() == start {
+                previous.length += text.len();
+                previous.line_breaks += line_breaks;
+                return;
+            }
+        }
+        let piece = Piece {
+            source: Source::Add,
+            start,
+            length: text.len(),
+            line_breaks,
+        };
+        if in_piece == 0 {
+            self.pieces.insert(index, piece);
+        } else {
+            self.split_piece(index, in_piece);
+            self.pieces.insert(index + 1, piece);
+        }
+    }
+    fn apply_delete(&mut self, range: Range<usize>) -> String {
+        debug_assert!(range.start <= range.end && range.end <= self.length);
+        if range.start == range.end {
+            return String::new();
+        }
+        let deleted = self.slice(range.clone());
+        // marks of lines that lost their line break go away with them
+        let length = range.end - range.start;
+        let line_kept = !deleted.contains('\n');
+        self.line_marks.retain(|&mark| {
+            mark < range.start || mark >= range.end || (mark == range.start && line_kept)
+        });
+        for mark in &mut self.line_marks {
+            if *mark >= range.end {
+                *mark -= length;
+            }
+        }
+        // marks set with m go away with their line, the others and the jumps go to where the
+        // text was
+        let mut marks = std::mem::take(&mut self.marks);
+        marks.retain(|name, mark| {
+            let inside = *mark >= range.start && *mark < range.end;
+            if !name.is_ascii_alphabetic() || !inside || line_kept {
+                return true;
+            }
+            let y = self.line_of_offset(*mark);
+            range.start > self.line_start(y) || range.end < self.line_end(y)
+        });
+        self.marks = marks;
+        for mark in self.marks.values_mut().chain(self.jumps.iter_mut()) {
+            if *mark >= range.end {
+                *mark -= length;
+            } else if *mark > range.start {
+                *mark = range.start;
+            }
+        }
+        let first = self.split_at(range.start);
+        let last = self.split_at(range.end);
+        let removed_breaks: usize = self.pieces[first..last].iter().map(|p| p.line_breaks).sum();
+        self.pieces.drain(first..last);
+        self.length -= range.end - range.start;
+        self.line_breaks -= removed_breaks;
+        deleted
+    }
+    pub fn mark_lines(&mut self, lines: &[usize]) {
+        self.line_marks = lines.iter().map(|&y| self.line_start(y)).collect();
+    }
+    // the first marked line still left, which stops being marked
+    pub fn take_marked_line(&mut self) -> Option<usize> {
+        if self.line_marks.is_empty() {
+            return None;
+        }
+        let mark = self.line_marks.remove(0);
+        Some(self.line_of_offset(mark))
+    }
+    pub fn clear_line_marks(&mut self) {
+        self.line_marks.clear();
+    }
+    pub fn mark(&self, name: char) -> Option<usize> {
+        self.marks.get(&name).copied()
+    }
+    pub fn set_mark(&mut self, name: char, offset: usize) {
+        self.marks.insert(name, min(offset, self.length));
+    }
+    // a new jump replaces older ones on the same line, and Ctrl-I starts over from the newest
+    pub fn push_jump(&mut self, offset: usize) {
+        let y = self.line_of_offset(offset);
+        let jumps = std::mem::take(&mut self.jumps);
+        self.jumps = jumps
+            .into_iter()
+            .filter(|&jump| self.line_of_offset(jump) != y)
+            .collect();
+        self.jumps.push(offset);
+        if self.jumps.len() > 100 {
+            self.jumps.remove(0);
+        }
+        self.jump_index = self.jumps.len();
+        self.marks.insert('\'', offset);
+    }
+    // Ctrl-O, the first one remembers where it started so Ctrl-I can come back there
+    pub fn older_jump(&mut self, from: usize) -> Option<usize> {
+        if self.jump_index == self.jumps.len() {
+            self.push_jump(from);
+            self.jump_index = self.jumps.len() - 1;
+        }
+        self.jump_index = self.jump_index.checked_sub(1)?;
+        Some(self.jumps[self.jump_index])
+    }
+    // Ctrl-I
+    pub fn newer_jump(&mut self) -> Option<usize> {
+        if self.jump_index + 1 >= self.jumps.len() {
+            return None;
+        }
+        self.jump_index += 1;
+        Some(self.jumps[self.jump_index])
     }
     pub fn cursor(&self) -> (usize, usize) {
         (self.cursor_x, self.cursor_y)
     }
+    // the column vertical movement tries to keep, usize::MAX sticks to the end of lines
+    pub fn preferred_column(&self) -> usize {
+        self.reminder_cursor_x
+    }
+    pub fn set_preferred_column(&mut self, x: usize) {
+        self.reminder_cursor_x = x;
+    }
+    pub fn set_cursor(&mut self, x: usize, y: usize, mode: Mode) {
+        self.cursor_y = min(y, self.line_count() - 1);
+        let length_of_line = self.line_length(self.cursor_y);
+        let last = if mode == Mode::INSERT || length_of_line == 0 {
+            length_of_line
+        } else {
+            length_of_line - 1
+        };
+        self.cursor_x = min(x, last);
+        self.reminder_cursor_x = self.cursor_x;
+    }
     pub fn move_cursor_horizontal(&mut self, x: i64, mode: Mode) {
-        let length_of_line = self
-            .lines
-            .get(self.cursor_y)
-            .map(|it| UnicodeSegmentation::graphemes(it.as_str(), true).count())
-            .unwrap_or(0);
+        let length_of_line = if self.cursor_y < self.line_count() {
+            self.line_length(self.cursor_y)
+        } else {
+            0
+        };
 
         let moving_beyond_first = x < 0 && self.cursor_x == 0;
         let last = if mode == Mode::INSERT { length_of_line + 1 } else { length_of_line };
@@ -79,74 +452,288 @@ impl Buffer {
         }
     }
     pub fn move_cursor_vertical(&mut self, y: i64) {
-        let moving_beyond_first = y < 0 && self.cursor_y == 0;
-        let moving_beyond_last = y > 0 && self.cursor_y + 1 >= self.lines.len();
+        let last_line = (self.line_count() - 1) as i64;
+        self.cursor_y = ((self.cursor_y as i64) + y).max(0).min(last_line) as usize;
 
-        if !moving_beyond_first && !moving_beyond_last {
-            self.cursor_y = ((self.cursor_y as i64) + y) as usize;
-        }
-
-        let length_of_line =
-            UnicodeSegmentation::graphemes(self.lines[self.cursor_y].as_str(), true).count();
+        let length_of_line = self.line_length(self.cursor_y);
         if length_of_line == 0 {
             self.cursor_x = 0;
         } else {
             self.cursor_x = min(self.reminder_cursor_x, length_of_line - 1);
         }
     }
-    pub fn get_under_cursor(&self) -> &str {
-        debug_assert!(self.cursor_y < self.lines.len());
-        UnicodeSegmentation::graphemes(self.lines[self.cursor_y].as_str(), true)
+    // None on an empty line, or past the end of the line in insert mode
+    pub fn get_under_cursor(&self) -> Option<String> {
+        UnicodeSegmentation::graphemes(self.line(self.cursor_y).as_str(), true)
             .nth(self.cursor_x)
-            .unwrap()
+            .map(String::from)
     }
     pub fn insert_before_cursor(&mut self, s: &str) {
-        debug_assert!(self.cursor_y < self.lines.len());
-        let line_graphemes =
-            UnicodeSegmentation::graphemes(self.lines[self.cursor_y].as_str(), true)
-                .collect::<Vec<_>>();
-        if s == "\n" {
-            let mut extend_lines: Vec<String> = vec![];
-            let extra = line_graphemes[self.cursor_x..].join("");
-            self.lines[self.cursor_y] = line_graphemes[..self.cursor_x].join("");
-            extend_lines.extend(self.lines[..self.cursor_y + 1].iter().cloned());
-            extend_lines.push(extra);
-            extend_lines.extend(self.lines[self.cursor_y + 1..].iter().cloned());
-            self.lines = extend_lines;
-            self.move_cursor_vertical(1);
-            self.cursor_x = 0;
-            self.reminder_cursor_x = 0;
-        } else {
-            self.lines[self.cursor_y] = line_graphemes[..self.cursor_x].join("")
-                + s
-                + &line_graphemes[self.cursor_x..].join("");
-            self.move_cursor_horizontal(1, Mode::INSERT);
-        }
+        debug_assert!(self.cursor_y < self.line_count());
+        self.begin_undo_group();
+        let offset = self.offset_of(self.cursor_x, self.cursor_y);
+        self.insert(offset, s);
+        let (x, y) = self.position_of(offset + s.len());
+        self.cursor_x = x;
+        self.cursor_y = y;
+        self.reminder_cursor_x = x;
+        self.end_undo_group();
     }
     pub fn delete_under_cursor(&mut self) {
-        debug_assert!(self.cursor_y < self.lines.len());
-        if self.cursor_x > 0 {
-            let line_graphemes =
-                UnicodeSegmentation::graphemes(self.lines[self.cursor_y].as_str(), true)
-                    .collect::<Vec<_>>();
-            let mut new_line = vec![];
-            new_line.extend(line_graphemes[..self.cursor_x - 1].iter().cloned());
-            new_line.extend(line_graphemes[self.cursor_x..].iter().cloned());
-            self.lines[self.cursor_y] = new_line.join("");
-            self.move_cursor_horizontal(-1, Mode::INSERT);
+        debug_assert!(self.cursor_y < self.line_count());
+        let end = self.offset_of(self.cursor_x, self.cursor_y);
+        let start = if self.cursor_x > 0 {
+            self.offset_of(self.cursor_x - 1, self.cursor_y)
         } else if self.cursor_y > 0 {
-            let previous_line_len = UnicodeSegmentation::grapheme_indices(
-                self.lines[self.cursor_y - 1].as_str(),
-                true,
-            )
-            .count();
-            self.lines[self.cursor_y] =
-                self.lines[self.cursor_y - 1].clone() + &self.lines[self.cursor_y];
-            self.lines.remove(self.cursor_y - 1);
-            self.cursor_y -= 1;
-            self.cursor_x = previous_line_len;
+            end - 1
+        } else {
+            return;
+        };
+        self.begin_undo_group();
+        self.delete(start..end);
+        let (x, y) = self.position_of(start);
+        self.cursor_x = x;
+        self.cursor_y = y;
+        self.reminder_cursor_x = x;
+        self.end_undo_group();
+    }
+    pub fn delete_after_cursor(&mut self) {
+        debug_assert!(self.cursor_y < self.line_count());
+        let start = self.offset_of(self.cursor_x, self.cursor_y);
+        let end = if self.cursor_x < self.line_length(self.cursor_y) {
+            self.offset_of(self.cursor_x + 1, self.cursor_y)
+        } else if self.cursor_y + 1 < self.line_count() {
+            self.line_start(self.cursor_y + 1)
+        } else {
+            return;
+        };
+        self.begin_undo_group();
+        self.delete(start..end);
+        self.end_undo_group();
+    }
+
+    // every edit made between the outermost begin and end becomes a single undo step
+    pub fn begin_undo_group(&mut self) {
+        if self.undo_group_depth == 0 {
+            self.pending_undo_step = Some(UndoStep {
+                edits: vec![],
+                cursor_before: self.cursor(),
+                cursor_after: self.cursor(),
+            });
+        }
+        self.undo_group_depth += 1;
+    }
+    pub fn end_undo_group(&mut self) {
+        debug_assert!(self.undo_group_depth > 0);
+        self.undo_group_depth = self.undo_group_depth.saturating_sub(1);
+        if self.undo_group_depth > 0 {
+            return;
+        }
+        if let Some(mut step) = self.pending_undo_step.take() {
+            if !step.edits.is_empty() {
+                step.cursor_after = self.cursor();
+                self.history.commit(step);
+            }
+        }
+    }
+    pub fn undo(&mut self) -> bool {
+        debug_assert!(self.undo_group_depth == 0);
+        match self.history.undo() {
+            Some(step) => {
+                self.apply_undo(&step);
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn redo(&mut self) -> bool {
+        debug_assert!(self.undo_group_depth == 0);
+        match self.history.redo() {
+            Some(step) => {
+                self.apply_redo(&step);
+                true
+            }
+            None => false,
+        }
+    }
+    // undo states are numbered in creation order, 0 being the text the buffer was loaded with
+    pub fn undo_state(&self) -> usize {
+        self.history.current()
+    }
+    pub fn undo_state_count(&self) -> usize {
+        self.history.len()
+    }
+    pub fn undo_state_time(&self, state: usize) -> SystemTime {
+        self.history.time_of(state)
+    }
+    pub fn undo_state_at(&self, time: SystemTime) -> usize {
+        self.history.state_at(time)
+    }
+    pub fn goto_undo_state(&mut self, state: usize) -> bool {
+        debug_assert!(self.undo_group_depth == 0);
+        if state >= self.history.len() || state == self.history.current() {
+            return false;
+        }
+        let (undos, redos) = self.history.travel(state);
+        for step in &undos {
+            self.apply_undo(step);
+        }
+        for step in &redos {
+            self.apply_redo(step);
         }
+        true
     }
+    pub fn is_modified(&self) -> bool {
+        self.history.current() != self.saved_undo_state
+    }
+    pub fn mark_saved(&mut self) {
+        self.saved_undo_state = self.history.current();
+    }
+    // the text with the line separators it was read with
+    pub fn file_contents(&self, range: Range<usize>) -> String {
+        self.slice(range)
+            .replace('\n', self.line_separator_format.separator())
+    }
+    pub fn write_undo_file(&self, path: &Path) -> std::io::Result<()> {
+        let hash = undo::content_hash(self.chunks());
+        std::fs::write(path, self.history.serialize(hash))
+    }
+    // the history is only restored if it was saved for exactly the current text
+    pub fn read_undo_file(&mut self, path: &Path) -> std::io::Result<bool> {
+        let data = std::fs::read_to_string(path)?;
+        let hash = undo::content_hash(self.chunks());
+        match UndoTree::deserialize(&data, hash, &self.as_string()) {
+            Some(history) => {
+                self.history = history;
+                self.saved_undo_state = self.history.current();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn record(&mut self, edit: Edit) {
+        self.begin_undo_group();
+        let step = self.pending_undo_step.as_mut().unwrap();
+        // typing and backspacing produce one edit per key, merge them while they stay contiguous
+        let merged = match (step.edits.last_mut(), &edit) {
+            (
+                Some(Edit::INSERT { offset, text }),
+                Edit::INSERT {
+                    offset: new_offset,
+                    text: new_text,
+                },
+            ) if *offset + text.len() == *new_offset => {
+                text.push_str(new_text);
+                true
+            }
+            (
+                Some(Edit::DELETE { offset, text }),
+                Edit::DELETE {
+                    offset: new_offset,
+                    text: new_text,
+                },
+            ) if *new_offset + new_text.len() == *offset => {
+                text.insert_str(0, new_text);
+                *offset = *new_offset;
+                true
+            }
+            _ => false,
+        };
+        if !merged {
+            step.edits.push(edit);
+        }
+        self.end_undo_group();
+    }
+    fn apply_undo(&mut self, step: &UndoStep) {
+        for edit in step.edits.iter().rev() {
+            self.apply(&edit.inverse());
+        }
+        self.set_cursor(step.cursor_before.0, step.cursor_before.1, Mode::NORMAL);
+    }
+    fn apply_redo(&mut self, step: &UndoStep) {
+        for edit in &step.edits {
+            self.apply(edit);
+        }
+        self.set_cursor(step.cursor_after.0, step.cursor_after.1, Mode::NORMAL);
+    }
+    fn apply(&mut self, edit: &Edit) {
+        match edit {
+            Edit::INSERT { offset, text } => self.apply_insert(*offset, text),
+            Edit::DELETE { offset, text } => {
+                self.apply_delete(*offset..*offset + text.len());
+            }
+        }
+    }
+
+    fn source_text(&self, source: Source) -> &str {
+        match source {
+            Source::Original => &self.original,
+            Source::Add => &self.add,
+        }
+    }
+    fn source_line_breaks(&self, source: Source) -> &[usize] {
+        match source {
+            Source::Original => &self.original_line_breaks,
+            Source::Add => &self.add_line_breaks,
+        }
+    }
+    fn piece_text(&self, piece: &Piece) -> &str {
+        &self.source_text(piece.source)[piece.start..piece.end()]
+    }
+    // (piece index, offset inside that piece). an offset on a piece boundary resolves to the
+    // start of the following piece, or to pieces.len() at the end of the buffer
+    fn locate(&self, offset: usize) -> (usize, usize) {
+        let mut piece_offset = 0;
+        for (index, piece) in self.pieces.iter().enumerate() {
+            if offset < piece_offset + piece.length {
+                return (index, offset - piece_offset);
+            }
+            piece_offset += piece.length;
+        }
+        (self.pieces.len(), 0)
+    }
+    fn split_piece(&mut self, index: usize, in_piece: usize) {
+        let piece = self.pieces[index];
+        let breaks = self.source_line_breaks(piece.source);
+        let left_breaks = count_between(breaks, piece.start, piece.start + in_piece);
+        self.pieces[index] = Piece {
+            length: in_piece,
+            line_breaks: left_breaks,
+            ..piece
+        };
+        self.pieces.insert(
+            index + 1,
+            Piece {
+                source: piece.source,
+                start: piece.start + in_piece,
+                length: piece.length - in_piece,
+                line_breaks: piece.line_breaks - left_breaks,
+            },
+        );
+    }
+    // makes sure a piece starts at offset and returns its index
+    fn split_at(&mut self, offset: usize) -> usize {
+        let (index, in_piece) = self.locate(offset);
+        if in_piece == 0 {
+            index
+        } else {
+            self.split_piece(index, in_piece);
+            index + 1
+        }
+    }
+}
+
+fn line_breaks_of(s: &str, base: usize) -> Vec<usize> {
+    s.bytes()
+        .enumerate()
+        .filter(|&(_, b)| b == b'\n')
+        .map(|(i, _)| base + i)
+        .collect()
+}
+
+fn count_between(sorted: &[usize], start: usize, end: usize) -> usize {
+    sorted.partition_point(|&b| b < end) - sorted.partition_point(|&b| b < start)
 }
 
 mod test {
@@ -166,4 +753,196 @@ mod test {
         buffer.insert_before_cursor("t");
         assert_eq!("hi\nbest\nfriend", buffer.as_string());
     }
+
+    #[test]
+    fn should_split_and_join_lines() {
+        let mut buffer = crate::buffer::Buffer::from("hello world\nbye");
+        buffer.move_cursor_horizontal(5, crate::editor::Mode::NORMAL);
+        buffer.insert_before_cursor("\n");
+        assert_eq!("hello\n world\nbye", buffer.as_string());
+        assert_eq!((0, 1), buffer.cursor());
+        assert_eq!(3, buffer.line_count());
+        assert_eq!(" world", buffer.line(1));
+
+        buffer.delete_under_cursor();
+        assert_eq!("hello world\nbye", buffer.as_string());
+        assert_eq!((5, 0), buffer.cursor());
+        assert_eq!(2, buffer.line_count());
+        assert_eq!("bye", buffer.line(1));
+    }
+
+    #[test]
+    fn should_keep_line_index_across_pieces() {
+        let mut buffer = crate::buffer::Buffer::from("one\ntwo\nthree\n");
+        buffer.insert(6, "\nX\nY");
+        buffer.insert(0, "zero\n");
+        assert_eq!("zero\none\ntw\nX\nYo\nthree\n", buffer.as_string());
+        assert_eq!(7, buffer.line_count());
+        let lines = (0..buffer.line_count())
+            .map(|y| buffer.line(y))
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["zero", "one", "tw", "X", "Yo", "three", ""], lines);
+        assert_eq!(4, buffer.line_of_offset(buffer.line_start(4) + 1));
+
+        assert_eq!("w\nX\nY", buffer.delete(10..15));
+        assert_eq!("zero\none\nto\nthree\n", buffer.as_string());
+        assert_eq!(5, buffer.line_count());
+        assert_eq!("to", buffer.line(2));
+        assert_eq!((1, 2), buffer.position_of(10));
+    }
+
+    #[test]
+    fn should_treat_graphemes_as_single_characters() {
+        let mut buffer = crate::buffer::Buffer::from("ñe\u{301}a");
+        buffer.move_cursor_horizontal(1, crate::editor::Mode::NORMAL);
+        assert_eq!(Some("e\u{301}".into()), buffer.get_under_cursor());
+        buffer.move_cursor_horizontal(1, crate::editor::Mode::NORMAL);
+        buffer.delete_under_cursor();
+        assert_eq!("ña", buffer.as_string());
+        assert_eq!((1, 0), buffer.cursor());
+
+        let mut buffer = crate::buffer::Buffer::from("a\n\nb");
+        buffer.set_cursor(0, 1, crate::editor::Mode::NORMAL);
+        assert_eq!(None, buffer.get_under_cursor());
+    }
+
+    #[test]
+    fn should_normalize_dos_line_separators() {
+        let buffer = crate::buffer::Buffer::from("a\r\nb\r\n");
+        assert_eq!("DOS", buffer.line_separator_format.to_string());
+        assert_eq!("a\nb\n", buffer.as_string());
+        assert_eq!(3, buffer.line_count());
+    }
+
+    #[test]
+    fn should_undo_and_redo_grouped_edits() {
+        let mut buffer = crate::buffer::Buffer::from("one\ntwo");
+        buffer.move_cursor_vertical(1);
+        buffer.begin_undo_group();
+        buffer.insert_before_cursor("x");
+        buffer.insert_before_cursor("y");
+        buffer.insert_before_cursor("\n");
+        buffer.delete_under_cursor();
+        buffer.delete_under_cursor();
+        buffer.end_undo_group();
+        buffer.delete_under_cursor();
+        assert_eq!("one\ntwo", buffer.as_string());
+        assert_eq!((0, 1), buffer.cursor());
+
+        assert!(buffer.undo());
+        assert_eq!("one\nxtwo", buffer.as_string());
+        assert_eq!((1, 1), buffer.cursor());
+        assert!(buffer.undo());
+        assert_eq!("one\ntwo", buffer.as_string());
+        assert_eq!((0, 1), buffer.cursor());
+        assert!(!buffer.undo());
+
+        assert!(buffer.redo());
+        assert_eq!("one\nxtwo", buffer.as_string());
+        assert_eq!((1, 1), buffer.cursor());
+        assert!(buffer.redo());
+        assert!(!buffer.redo());
+        assert_eq!("one\ntwo", buffer.as_string());
+    }
+
+    #[test]
+    fn should_start_new_branch_after_undo() {
+        let mut buffer = crate::buffer::Buffer::from("");
+        buffer.insert_before_cursor("a");
+        buffer.insert_before_cursor("b");
+        assert!(buffer.undo());
+        buffer.insert_before_cursor("c");
+        assert!(!buffer.redo());
+        assert_eq!("ca", buffer.as_string());
+        assert!(buffer.undo());
+        assert!(buffer.undo());
+        assert_eq!("", buffer.as_string());
+    }
+
+    #[test]
+    fn should_keep_abandoned_branches_reachable() {
+        let mut buffer = crate::buffer::Buffer::from("");
+        buffer.insert_before_cursor("a");
+        buffer.insert_before_cursor("b");
+        assert!(buffer.undo());
+        buffer.insert_before_cursor("c");
+        assert_eq!("ca", buffer.as_string());
+        assert_eq!((3, 4), (buffer.undo_state(), buffer.undo_state_count()));
+
+        assert!(buffer.goto_undo_state(2));
+        assert_eq!("ab", buffer.as_string());
+        assert!(buffer.goto_undo_state(0));
+        assert_eq!("", buffer.as_string());
+        assert!(buffer.redo());
+        assert!(buffer.redo());
+        assert_eq!("ab", buffer.as_string());
+        assert!(buffer.goto_undo_state(3));
+        assert_eq!("ca", buffer.as_string());
+    }
+
+    #[test]
+    fn should_restore_undo_tree_only_for_same_contents() {
+        let mut buffer = crate::buffer::Buffer::from("line\n");
+        buffer.insert_before_cursor("new\n");
+        buffer.insert_before_cursor("more");
+        assert!(buffer.undo());
+        buffer.insert_before_cursor("x");
+        let path = std::env::temp_dir().join(format!("uu-undo-test-{}", std::process::id()));
+        buffer.write_undo_file(&path).unwrap();
+
+        let mut reloaded = crate::buffer::Buffer::from(&buffer.as_string());
+        assert!(reloaded.read_undo_file(&path).unwrap());
+        assert_eq!(3, reloaded.undo_state());
+        assert!(reloaded.goto_undo_state(2));
+        assert_eq!("new\nmoreline\n", reloaded.as_string());
+        assert!(reloaded.goto_undo_state(0));
+        assert_eq!("line\n", reloaded.as_string());
+
+        let mut changed = crate::buffer::Buffer::from("something else");
+        assert!(!changed.read_undo_file(&path).unwrap());
+        assert!(!changed.undo());
+
+        // a damaged file is ignored, even when it was saved for the same text
+        let damaged = [
+            ("line\n", "current 0\nnodes 18446744073709551615\n"),
+            (
+                "line\n",
+                "current 1\nnodes 2\nnode 0 1 0 0 0 0 0 0\nnode 0 -1 0 0 0 0 0 1\ni 9 1\nx\n",
+            ),
+            (
+                "line\n",
+                "current 0\nnodes 2\nnode 0 1 0 0 0 0 0 0\nnode 0 -1 0 0 0 0 0 1\nd 2 9\nxxxxxxxxx\n",
+            ),
+            (
+                "line\n",
+                "current 0\nnodes 2\nnode 0 1 0 0 0 0 0 0\nnode 0 -1 0 0 0 0 0 1\nd 0 1\nx\n",
+            ),
+            (
+                "\u{e9}\n",
+                "current 0\nnodes 2\nnode 0 1 0 0 0 0 0 0\nnode 0 -1 0 0 0 0 0 1\ni 1 1\nx\n",
+            ),
+        ];
+        for (text, data) in &damaged {
+            let hash = crate::undo::content_hash(std::iter::once(*text));
+            let header = format!("uu-undo 1\nhash {:016x}\n", hash);
+            std::fs::write(&path, format!("{}{}", header, data)).unwrap();
+            let mut damaged = crate::buffer::Buffer::from(text);
+            assert!(!damaged.read_undo_file(&path).unwrap());
+            assert!(!damaged.redo());
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn should_keep_line_marks_on_their_lines() {
+        let mut buffer = crate::buffer::Buffer::from("a\nb\nc\nd\n");
+        buffer.mark_lines(&[1, 2, 3]);
+        buffer.delete(0..2);
+        assert_eq!(Some(0), buffer.take_marked_line());
+        buffer.insert(0, "x\ny\n");
+        buffer.delete(6..8);
+        assert_eq!("x\ny\nb\nd\n", buffer.as_string());
+        assert_eq!(Some(3), buffer.take_marked_line());
+        assert_eq!(None, buffer.take_marked_line());
+    }
 }
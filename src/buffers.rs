@@ -0,0 +1,369 @@
+use crate::buffer::Buffer;
+use crate::editor::Editor;
+
+// a buffer of the list other than the one being edited. buffers added with :badd or given on
+// the command line aren't read until they're gone to
+#[derive(Debug, Clone)]
+pub struct ListedBuffer {
+    pub number: usize,
+    pub path: String,
+    pub(crate) buffer: Option<Buffer>,
+    pub(crate) y_render_offset: usize,
+}
+
+impl Editor {
+    // :badd, a file already in the list isn't added again
+    pub(crate) fn add_buffer(&mut self, path: &str) -> Result<(), String> {
+        if path.is_empty() {
+            return Err("E471: Argument required".into());
+        }
+        let listed = self.other_buffers.iter().any(|listed| listed.path == path);
+        if listed || path == self.editing_file_path {
+            return Ok(());
+        }
+        self.last_buffer_number += 1;
+        self.other_buffers.push(ListedBuffer {
+            number: self.last_buffer_number,
+            path: path.to_string(),
+            buffer: None,
+            y_render_offset: 0,
+        });
+        Ok(())
+    }
+
+    // :e {file}. the buffer that is left stays in the list with its changes, unless it's the
+    // empty one without a name uu starts with
+    pub fn edit_file(&mut self, path: &str) -> Result<(), String> {
+        if let Some(listed) = self.other_buffers.iter().find(|listed| listed.path == path) {
+            return self.switch_to_buffer(listed.number);
+        }
+        let empty = self.buffer.is_empty() && !self.buffer.is_modified();
+        if self.editing_file_path.is_empty() && empty {
+            return self.open_file(path);
+        }
+        let (buffer, message) = self.read_file(path)?;
+        self.hide_current_buffer();
+        self.last_buffer_number += 1;
+        self.buffer_number = self.last_buffer_number;
+        self.show_buffer(path.to_string(), buffer, 0);
+        self.status_message = message;
+        Ok(())
+    }
+
+    pub(crate) fn switch_to_buffer(&mut self, number: usize) -> Result<(), String> {
+        if number == self.buffer_number {
+            return Ok(());
+        }
+        let index = self
+            .other_buffers
+            .iter()
+            .position(|listed| listed.number == number)
+            .ok_or_else(|| format!("E86: Buffer {} does not exist", number))?;
+        let message = match &self.other_buffers[index].buffer {
+            Some(buffer) => {
+                let (_, y) = buffer.cursor();
+                let name = display_name(&self.other_buffers[index].path);
+                format!("\"{}\" line {} of {}", name, y + 1, buffer.line_count())
+            }
+            None => {
+                let (buffer, message) = self.read_file(&self.other_buffers[index].path)?;
+                self.other_buffers[index].buffer = Some(buffer);
+                message
+            }
+        };
+        let listed = self.other_buffers.remove(index);
+        self.hide_current_buffer();
+        self.buffer_number = listed.number;
+        self.show_buffer(listed.path, listed.buffer.unwrap(), listed.y_render_offset);
+        self.status_message = message;
+        Ok(())
+    }
+
+    // the current buffer goes back into the list, keeping its changes and where it was
+    fn hide_current_buffer(&mut self) {
+        self.save_file_marks();
+        let hidden = ListedBuffer {
+            number: self.buffer_number,
+            path: std::mem::take(&mut self.editing_file_path),
+            buffer: Some(std::mem::replace(&mut self.buffer, Buffer::from(""))),
+            y_render_offset: std::mem::take(&mut self.y_render_offset),
+        };
+        self.alternate_buffer = Some(hidden.number);
+        let index = self
+            .other_buffers
+            .partition_point(|listed| listed.number < hidden.number);
+        self.other_buffers.insert(index, hidden);
+    }
+
+    pub(crate) fn show_buffer(&mut self, path: String, buffer: Buffer, y_render_offset: usize) {
+        self.editing_file_path = path;
+        self.buffer = buffer;
+        self.y_render_offset = y_render_offset;
+        self.restore_file_marks();
+    }
+
+    // an empty buffer without a name takes the place of the current one
+    pub(crate) fn new_buffer(&mut self) {
+        self.hide_current_buffer();
+        self.last_buffer_number += 1;
+        self.buffer_number = self.last_buffer_number;
+        self.show_buffer(String::new(), Buffer::from(""), 0);
+    }
+
+    pub(crate) fn buffer_name(&self, number: usize) -> &str {
+        if number == self.buffer_number {
+            return display_name(&self.editing_file_path);
+        }
+        let listed = self
+            .other_buffers
+            .iter()
+            .find(|listed| listed.number == number);
+        display_name(listed.map_or("", |listed| listed.path.as_str()))
+    }
+
+    // Ctrl-^
+    pub fn edit_alternate_buffer(&mut self) -> Result<(), String> {
+        let number = self.alternate_buffer.ok_or("E23: No alternate file")?;
+        self.switch_to_buffer(number)
+    }
+
+    // :b takes a number, # for the alternate buffer or a part of a name only one buffer has
+    pub(crate) fn find_buffer(&self, argument: &str) -> Result<usize, String> {
+        match argument {
+            "" | "%" => return Ok(self.buffer_number),
+            "#" => return Ok(self.alternate_buffer.ok_or("E23: No alternate file")?),
+            _ => {}
+        }
+        if let Ok(number) = argument.parse() {
+            return Ok(number);
+        }
+        let mut paths = vec![(self.buffer_number, self.editing_file_path.as_str())];
+        paths.extend(
+            self.other_buffers
+                .iter()
+                .map(|listed| (listed.number, listed.path.as_str())),
+        );
+        // a whole name wins over the names it's part of
+        if let Some((number, _)) = paths.iter().find(|(_, path)| *path == argument) {
+            return Ok(*number);
+        }
+        paths.retain(|(_, path)| path.contains(argument));
+        match paths.as_slice() {
+            [(number, _)] => Ok(*number),
+            [] => Err(format!("E94: No matching buffer for {}", argument)),
+            _ => Err(format!("E93: More than one match for {}", argument)),
+        }
+    }
+
+    // :bn and :bp, going around from the last buffer to the first
+    pub(crate) fn next_buffer(&mut self, count: usize, forward: bool) -> Result<(), String> {
+        let mut numbers: Vec<_> = self
+            .other_buffers
+            .iter()
+            .map(|listed| listed.number)
+            .collect();
+        let index = numbers.partition_point(|&number| number < self.buffer_number);
+        numbers.insert(index, self.buffer_number);
+        let length = numbers.len();
+        let step = count % length;
+        let target = if forward {
+            (index + step) % length
+        } else {
+            (index + length - step) % length
+        };
+        self.switch_to_buffer(numbers[target])
+    }
+
+    // :bd. the other windows showing the buffer are closed, and in the current one it's replaced
+    // by the alternate buffer, or the next one in the list, or an empty one when it was the last
+    pub(crate) fn delete_buffer(&mut self, argument: &str, force: bool) -> Result<(), String> {
+        let number = self.find_buffer(argument)?;
+        let modified = if number == self.buffer_number {
+            self.buffer.is_modified()
+        } else {
+            let listed = self
+                .other_buffers
+                .iter()
+                .find(|listed| listed.number == number);
+            let listed =
+                listed.ok_or_else(|| format!("E516: No buffers were deleted: {}", number))?;
+            listed.buffer.as_ref().is_some_and(Buffer::is_modified)
+        };
+        if modified && !force {
+            return Err(format!(
+                "E89: No write since last change for buffer {} (add ! to override)",
+                number
+            ));
+        }
+        self.close_windows_of_buffer(number);
+        if number == self.buffer_number {
+            let alternate = self.alternate_buffer.filter(|&alternate| {
+                self.other_buffers
+                    .iter()
+                    .any(|listed| listed.number == alternate)
+            });
+            let next = self
+                .other_buffers
+                .iter()
+                .find(|listed| listed.number > number)
+                .or_else(|| self.other_buffers.last())
+                .map(|listed| listed.number);
+            match alternate.or(next) {
+                Some(replacement) => self.switch_to_buffer(replacement)?,
+                None => {
+                    self.save_file_marks();
+                    self.last_buffer_number += 1;
+                    self.buffer_number = self.last_buffer_number;
+                    self.show_buffer(String::new(), Buffer::from(""), 0);
+                }
+            }
+        }
+        self.other_buffers.retain(|listed| listed.number != number);
+        if self.alternate_buffer == Some(number) {
+            self.alternate_buffer = None;
+        }
+        Ok(())
+    }
+
+    // :ls. % is the current buffer, # the alternate one, h the ones read but not shown and + the
+    // ones with changes
+    pub(crate) fn list_buffers(&mut self) {
+        let current = ListedBuffer {
+            number: self.buffer_number,
+            path: self.editing_file_path.clone(),
+            buffer: None,
+            y_render_offset: 0,
+        };
+        let mut listed: Vec<_> = self.other_buffers.iter().chain(Some(&current)).collect();
+        listed.sort_by_key(|listed| listed.number);
+        let lines: Vec<_> = listed
+            .into_iter()
+            .map(|listed| {
+                let is_current = listed.number == self.buffer_number;
+                let buffer = match &listed.buffer {
+                    _ if is_current => Some(&self.buffer),
+                    buffer => buffer.as_ref(),
+                };
+                let mut flags = String::new();
+                if is_current {
+                    flags.push_str("%a");
+                } else if self.alternate_buffer == Some(listed.number) {
+                    flags.push('#');
+                }
+                if buffer.is_some() && !is_current {
+                    flags.push('h');
+                }
+                if buffer.is_some_and(Buffer::is_modified) {
+                    flags.push('+');
+                }
+                let line = buffer.map_or(0, |buffer| buffer.cursor().1 + 1);
+                let name = display_name(&listed.path);
+                let flags = if flags.is_empty() {
+                    flags
+                } else {
+                    format!("{} ", flags)
+                };
+                format!("{} {}\"{}\" line {}", listed.number, flags, name, line)
+            })
+            .collect();
+        self.status_message = lines.join("  |  ");
+    }
+
+    // :q and the rest of the ways out don't leave changes behind in other buffers either
+    pub(crate) fn check_other_buffers(&self, force: bool) -> Result<(), String> {
+        let modified = self
+            .other_buffers
+            .iter()
+            .find(|listed| listed.buffer.as_ref().is_some_and(Buffer::is_modified));
+        match modified {
+            Some(listed) if !force => Err(format!(
+                "E162: No write since last change for buffer \"{}\"",
+                display_name(&listed.path)
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn display_name(path: &str) -> &str {
+    if path.is_empty() {
+        "[No Name]"
+    } else {
+        path
+    }
+}
+
+mod test {
+    #[test]
+    fn should_keep_every_file_in_its_own_buffer() {
+        let directory =
+            std::env::temp_dir().join(format!("uu-buffers-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = |name: &str| directory.join(name).to_str().unwrap().to_string();
+        std::fs::write(path("one.txt"), "1\n").unwrap();
+        std::fs::write(path("two.txt"), "2\n2\n").unwrap();
+        std::fs::write(path("three.txt"), "3\n").unwrap();
+
+        let mut editor = crate::editor::Editor::new();
+        let arguments = vec![path("one.txt"), path("two.txt"), path("three.txt")];
+        editor.start(crate::args::Arguments {
+            files: arguments,
+            ..Default::default()
+        });
+        editor.type_keys("dl:bn<CR>j");
+        assert_eq!("2\n2\n", editor.buffer.as_string());
+        assert_eq!(2, editor.buffer_number);
+        editor.run_command("ls").unwrap();
+        assert_eq!(
+            format!(
+                "1 #h+ \"{}\" line 1  |  2 %a \"{}\" line 2  |  3 \"{}\" line 0",
+                path("one.txt"),
+                path("two.txt"),
+                path("three.txt")
+            ),
+            editor.status_message
+        );
+
+        // changes and cursors stay with their buffers
+        editor.type_keys("<C-^>");
+        assert_eq!("\n", editor.buffer.as_string());
+        editor.type_keys("<C-^>");
+        assert_eq!((0, 1), editor.buffer.cursor());
+        editor.run_command("b thr").unwrap();
+        assert_eq!("3\n", editor.buffer.as_string());
+        assert_eq!(
+            Err("E93: More than one match for .txt".into()),
+            editor.run_command("b .txt")
+        );
+        editor.run_command("bp 2").unwrap();
+        assert_eq!(1, editor.buffer_number);
+
+        // changes have to be written or given up on before their buffer goes
+        assert_eq!(
+            Err("E89: No write since last change for buffer 1 (add ! to override)".into()),
+            editor.run_command("bd")
+        );
+        editor.run_command("b3").unwrap();
+        assert_eq!(
+            Err(format!(
+                "E162: No write since last change for buffer \"{}\"",
+                path("one.txt")
+            )),
+            editor.run_command("q")
+        );
+        editor.run_command("bd! 1").unwrap();
+        editor.run_command("bd").unwrap();
+        assert_eq!(2, editor.buffer_number);
+        editor
+            .run_command(&format!("e {}", path("one.txt")))
+            .unwrap();
+        assert_eq!(4, editor.buffer_number);
+        assert_eq!("1\n", editor.buffer.as_string());
+        editor.run_command("bd 2").unwrap();
+        editor.run_command("bd").unwrap();
+        assert_eq!(5, editor.buffer_number);
+        assert_eq!("", editor.editing_file_path);
+        editor.run_command("q").unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
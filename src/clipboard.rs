@@ -0,0 +1,175 @@
+use crate::editor::Editor;
+use crate::register::{Register, RegisterKind};
+use copypasta::ClipboardProvider;
+use std::cell::RefCell;
+
+// "+ is the clipboard and "* the primary selection. the editor only talks to this, so it runs
+// the same with the desktop's clipboard or without a display at all
+pub trait Clipboard {
+    fn get(&self, primary: bool) -> Option<String>;
+    fn set(&mut self, primary: bool, text: &str);
+}
+
+// what the editor starts with, and what tests run with
+#[derive(Debug, Default)]
+pub struct MemoryClipboard {
+    clipboard: Option<String>,
+    primary: Option<String>,
+}
+
+impl Clipboard for MemoryClipboard {
+    fn get(&self, primary: bool) -> Option<String> {
+        if primary {
+            self.primary.clone()
+        } else {
+            self.clipboard.clone()
+        }
+    }
+
+    fn set(&mut self, primary: bool, text: &str) {
+        if primary {
+            self.primary = Some(text.to_string());
+        } else {
+            self.clipboard = Some(text.to_string());
+        }
+    }
+}
+
+pub struct SystemClipboard {
+    clipboard: RefCell<Box<dyn ClipboardProvider>>,
+    primary: RefCell<Box<dyn ClipboardProvider>>,
+}
+
+impl SystemClipboard {
+    // on wayland the clipboard has to go through the display the window is on, X11 connects on
+    // its own. None when neither works
+    #[cfg(target_os = "linux")]
+    pub fn new(wayland_display: Option<*mut std::ffi::c_void>) -> Option<Self> {
+        use copypasta::x11_clipboard::{self, X11ClipboardContext};
+        let (clipboard, primary): (Box<dyn ClipboardProvider>, Box<dyn ClipboardProvider>) =
+            match wayland_display {
+                Some(display) => {
+                    let (primary, clipboard) = unsafe {
+                        copypasta::wayland_clipboard::create_clipboards_from_external(display)
+                    };
+                    (Box::new(clipboard), Box::new(primary))
+                }
+                None => (
+                    Box::new(X11ClipboardContext::<x11_clipboard::Clipboard>::new().ok()?),
+                    Box::new(X11ClipboardContext::<x11_clipboard::Primary>::new().ok()?),
+                ),
+            };
+        Some(SystemClipboard {
+            clipboard: RefCell::new(clipboard),
+            primary: RefCell::new(primary),
+        })
+    }
+
+    // elsewhere there's no primary selection, so "* is the clipboard too
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(_wayland_display: Option<*mut std::ffi::c_void>) -> Option<Self> {
+        Some(SystemClipboard {
+            clipboard: RefCell::new(Box::new(copypasta::ClipboardContext::new().ok()?)),
+            primary: RefCell::new(Box::new(copypasta::ClipboardContext::new().ok()?)),
+        })
+    }
+
+    fn provider(&self, primary: bool) -> &RefCell<Box<dyn ClipboardProvider>> {
+        if primary {
+            &self.primary
+        } else {
+            &self.clipboard
+        }
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn get(&self, primary: bool) -> Option<String> {
+        self.provider(primary).borrow_mut().get_contents().ok()
+    }
+
+    fn set(&mut self, primary: bool, text: &str) {
+        // nothing to do about a clipboard that went away, the register keeps the text anyway
+        let _ = self
+            .provider(primary)
+            .borrow_mut()
+            .set_contents(text.to_string());
+    }
+}
+
+// None for registers that aren't the clipboard. unnamedplus makes commands without a register
+// use "+
+pub(crate) fn clipboard_selection(name: Option<char>, unnamedplus: bool) -> Option<bool> {
+    match name {
+        Some('+') => Some(false),
+        Some('*') => Some(true),
+        None if unnamedplus => Some(false),
+        _ => None,
+    }
+}
+
+impl Editor {
+    pub(crate) fn set_clipboard(&mut self, primary: bool, register: &Register) {
+        self.clipboard.set(primary, &register.text);
+        // kept to know what kind of register the text was if it comes back unchanged
+        let name = if primary { '*' } else { '+' };
+        self.registers.set(name, register.clone());
+    }
+
+    // text copied in other programs is lines when it ends with a line break
+    pub(crate) fn clipboard_register(&self, primary: bool) -> Option<Register> {
+        let text = self.clipboard.get(primary)?;
+        let name = if primary { '*' } else { '+' };
+        match self.registers.get(name) {
+            Some(ours) if ours.text == text => Some(ours.clone()),
+            _ => Some(Register {
+                kind: if text.ends_with('\n') {
+                    RegisterKind::Lines
+                } else {
+                    RegisterKind::Chars
+                },
+                text,
+            }),
+        }
+    }
+
+    // clipboard=unnamedplus
+    pub(crate) fn unnamedplus(&self) -> bool {
+        let clipboard = self.option("clipboard").list();
+        clipboard.iter().any(|item| item == "unnamedplus")
+    }
+}
+
+mod test {
+    #[test]
+    fn should_yank_and_put_through_the_clipboard() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("one two\nthree\n");
+
+        editor.type_keys("\"+yy\"*yw");
+        assert_eq!(Some("one two\n".into()), editor.clipboard.get(false));
+        assert_eq!(Some("one ".into()), editor.clipboard.get(true));
+        editor.type_keys("j\"+p");
+        assert_eq!("one two\nthree\none two\n", editor.buffer.as_string());
+
+        editor.clipboard.set(false, "copied");
+        editor.type_keys("gg\"+P");
+        assert_eq!("copiedone two\nthree\none two\n", editor.buffer.as_string());
+        editor.clipboard.set(true, "line\n");
+        editor.type_keys("\"*p");
+        assert_eq!(
+            "copiedone two\nline\nthree\none two\n",
+            editor.buffer.as_string()
+        );
+
+        editor.run_command("set clipboard=unnamedplus").unwrap();
+        editor.type_keys("dd");
+        assert_eq!(Some("line\n".into()), editor.clipboard.get(false));
+        editor.clipboard.set(false, "!");
+        editor.type_keys("p");
+        assert_eq!(
+            "copiedone two\nt!hree\none two\n",
+            editor.buffer.as_string()
+        );
+    }
+}
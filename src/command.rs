@@ -0,0 +1,724 @@
+use crate::editor::EditorCommand;
+use crate::key::{parse_notation, KeyCode};
+use crate::keymap::Keymap;
+use crate::motion::{CharSearch, Motion};
+use crate::register::is_register_name;
+use crate::textobject::{parse_text_object, TextObject};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Unindent,
+    Lowercase,
+    Uppercase,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Motion(Motion),
+    // the operator typed twice: dd, yy, >>, gUU, gUgU...
+    Line,
+    TextObject(TextObject),
+    // the visual mode selection
+    Selection,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    Binding(EditorCommand),
+    Motion(Motion),
+    Operator(Operator, Target),
+    // extends the visual mode selection over a text object
+    TextObject(TextObject),
+    // p and P
+    Put { before: bool },
+    // .
+    Repeat,
+    // q{register}
+    Record(char),
+    // @{register}, @@ and @:
+    Play(char),
+    // m{mark}
+    SetMark(char),
+    // gt and gT
+    Tab { forward: bool },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Command {
+    pub count: Option<usize>,
+    // "x
+    pub register: Option<char>,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Parse<T> {
+    // a prefix of something valid, wait for more keys
+    Pending,
+    // can never become valid, throw it away
+    Invalid,
+    Complete(T),
+    // complete, but also the start of a longer binding. taken when the timeout runs out or the
+    // next key doesn't continue the longer one
+    Ambiguous(T),
+}
+
+static OPERATORS: &[(&str, Operator)] = &[
+    ("d", Operator::Delete),
+    ("c", Operator::Change),
+    ("y", Operator::Yank),
+    (">", Operator::Indent),
+    ("<", Operator::Unindent),
+    ("gu", Operator::Lowercase),
+    ("gU", Operator::Uppercase),
+];
+
+// visual mode has some shorter names for operators that don't need a motion there
+static VISUAL_OPERATORS: &[(&str, Operator)] = &[
+    ("x", Operator::Delete),
+    ("s", Operator::Change),
+    ("u", Operator::Lowercase),
+    ("U", Operator::Uppercase),
+];
+
+static PUTS: &[(&str, bool)] = &[("p", false), ("P", true)];
+
+static TABS: &[(&str, bool)] = &[("gt", true), ("gT", false)];
+
+static MOTIONS: &[(&str, Motion)] = &[
+    ("h", Motion::Left),
+    ("l", Motion::Right),
+    ("k", Motion::Up),
+    ("j", Motion::Down),
+    ("<Left>", Motion::Left),
+    ("<Right>", Motion::Right),
+    ("<Up>", Motion::Up),
+    ("<Down>", Motion::Down),
+    ("<Home>", Motion::LineStart),
+    ("<End>", Motion::LineEnd),
+    ("w", Motion::WordStart),
+    ("b", Motion::WordBackward),
+    ("e", Motion::WordEnd),
+    ("ge", Motion::WordEndBackward),
+    ("W", Motion::BigWordStart),
+    ("B", Motion::BigWordBackward),
+    ("E", Motion::BigWordEnd),
+    ("gE", Motion::BigWordEndBackward),
+    ("0", Motion::LineStart),
+    ("^", Motion::FirstNonBlank),
+    ("$", Motion::LineEnd),
+    ("gg", Motion::FirstLine),
+    ("G", Motion::LastLine),
+    ("}", Motion::ParagraphForward),
+    ("{", Motion::ParagraphBackward),
+    (")", Motion::SentenceForward),
+    ("(", Motion::SentenceBackward),
+    ("H", Motion::WindowTop),
+    ("M", Motion::WindowMiddle),
+    ("L", Motion::WindowBottom),
+    ("%", Motion::MatchingBracket),
+    (";", Motion::RepeatFind),
+    (",", Motion::RepeatFindReverse),
+];
+
+// [count] ["x] [count] (binding | motion | . | macro | mark | put | operator [count] (motion |
+// text object | operator))
+pub fn parse_normal(input: &str, bindings: &Keymap<EditorCommand>) -> Parse<Command> {
+    let (count, register, rest) = match split_register(input) {
+        Some(split) => split,
+        None => return Parse::Invalid,
+    };
+    if rest.is_empty() {
+        return Parse::Pending;
+    }
+    let binding = bindings.lookup(&parse_notation(rest));
+    with_binding(
+        binding,
+        parse_normal_builtin(count, register, rest),
+        count,
+        register,
+    )
+}
+
+fn parse_normal_builtin(
+    count: Option<usize>,
+    register: Option<char>,
+    rest: &str,
+) -> Parse<Command> {
+    let mut pending = false;
+
+    match parse_motion(rest) {
+        Parse::Complete(motion) => {
+            return Parse::Complete(Command {
+                count,
+                register,
+                action: Action::Motion(counted_motion(motion, count)),
+            })
+        }
+        Parse::Pending | Parse::Ambiguous(_) => pending = true,
+        Parse::Invalid => {}
+    }
+
+    if rest == "." {
+        return Parse::Complete(Command {
+            count,
+            register,
+            action: Action::Repeat,
+        });
+    }
+
+    for parse in [parse_macro(rest), parse_set_mark(rest)].iter() {
+        match *parse {
+            Parse::Complete(action) => {
+                return Parse::Complete(Command {
+                    count,
+                    register,
+                    action,
+                })
+            }
+            Parse::Pending | Parse::Ambiguous(_) => pending = true,
+            Parse::Invalid => {}
+        }
+    }
+
+    match lookup(PUTS, rest) {
+        Parse::Complete(before) => {
+            return Parse::Complete(Command {
+                count,
+                register,
+                action: Action::Put { before },
+            })
+        }
+        Parse::Pending | Parse::Ambiguous(_) => pending = true,
+        Parse::Invalid => {}
+    }
+
+    match lookup(TABS, rest) {
+        Parse::Complete(forward) => {
+            return Parse::Complete(Command {
+                count,
+                register,
+                action: Action::Tab { forward },
+            })
+        }
+        Parse::Pending | Parse::Ambiguous(_) => pending = true,
+        Parse::Invalid => {}
+    }
+
+    for &(keys, operator) in OPERATORS {
+        if let Some(after) = rest.strip_prefix(keys) {
+            match parse_operator_target(keys, after) {
+                Parse::Complete((target_count, target)) => {
+                    let count = multiply_counts(count, target_count);
+                    let target = match target {
+                        Target::Motion(motion) => Target::Motion(counted_motion(motion, count)),
+                        other => other,
+                    };
+                    return Parse::Complete(Command {
+                        count,
+                        register,
+                        action: Action::Operator(operator, target),
+                    });
+                }
+                Parse::Pending | Parse::Ambiguous(_) => pending = true,
+                Parse::Invalid => {}
+            }
+        } else if keys.starts_with(rest) {
+            pending = true;
+        }
+    }
+
+    if pending {
+        Parse::Pending
+    } else {
+        Parse::Invalid
+    }
+}
+
+// [count] ["x] [count] (binding | motion | text object | put | operator), operators and puts apply
+// to the selection
+pub fn parse_visual(input: &str, bindings: &Keymap<EditorCommand>) -> Parse<Command> {
+    let (count, register, rest) = match split_register(input) {
+        Some(split) => split,
+        None => return Parse::Invalid,
+    };
+    if rest.is_empty() {
+        return Parse::Pending;
+    }
+    let binding = bindings.lookup(&parse_notation(rest));
+    with_binding(
+        binding,
+        parse_visual_builtin(count, register, rest),
+        count,
+        register,
+    )
+}
+
+fn parse_visual_builtin(
+    count: Option<usize>,
+    register: Option<char>,
+    rest: &str,
+) -> Parse<Command> {
+    let mut pending = false;
+
+    let parses = [
+        map_complete(parse_motion(rest), |motion| {
+            Action::Motion(counted_motion(motion, count))
+        }),
+        map_complete(lookup(OPERATORS, rest), |operator| {
+            Action::Operator(operator, Target::Selection)
+        }),
+        map_complete(lookup(VISUAL_OPERATORS, rest), |operator| {
+            Action::Operator(operator, Target::Selection)
+        }),
+        map_complete(lookup(PUTS, rest), |before| Action::Put { before }),
+        match parse_text_object(rest) {
+            Some(object) => Parse::Complete(Action::TextObject(object)),
+            None if rest == "i" || rest == "a" => Parse::Pending,
+            None => Parse::Invalid,
+        },
+    ];
+    for parse in parses.iter() {
+        match *parse {
+            Parse::Complete(action) => {
+                return Parse::Complete(Command {
+                    count,
+                    register,
+                    action,
+                })
+            }
+            Parse::Pending | Parse::Ambiguous(_) => pending = true,
+            Parse::Invalid => {}
+        }
+    }
+
+    if pending {
+        Parse::Pending
+    } else {
+        Parse::Invalid
+    }
+}
+
+fn parse_operator_target(keys: &str, input: &str) -> Parse<(Option<usize>, Target)> {
+    let (count, rest) = split_count(input);
+    if rest.is_empty() {
+        return Parse::Pending;
+    }
+    let last_key = &keys[keys.len() - 1..];
+    if rest == keys || rest == last_key {
+        return Parse::Complete((count, Target::Line));
+    }
+    match parse_motion(rest) {
+        Parse::Complete(motion) => Parse::Complete((count, Target::Motion(motion))),
+        Parse::Invalid if keys.starts_with(rest) => Parse::Pending,
+        Parse::Invalid if rest == "i" || rest == "a" => Parse::Pending,
+        Parse::Invalid => match parse_text_object(rest) {
+            Some(object) => Parse::Complete((count, Target::TextObject(object))),
+            None => Parse::Invalid,
+        },
+        other => map_incomplete(other),
+    }
+}
+
+fn parse_motion(input: &str) -> Parse<Motion> {
+    let mut chars = input.chars();
+    let (forward, till) = match chars.next() {
+        Some('f') => (true, false),
+        Some('F') => (false, false),
+        Some('t') => (true, true),
+        Some('T') => (false, true),
+        Some('\'') | Some('`') => return parse_mark_motion(input),
+        _ => return lookup(MOTIONS, input),
+    };
+    // the target is a single key, <Tab> finds a tab
+    match parse_notation(chars.as_str()).as_slice() {
+        [] => Parse::Pending,
+        [key] if key.code != KeyCode::Esc => match key.to_char() {
+            Some(target) => Parse::Complete(Motion::FindChar(CharSearch {
+                target,
+                forward,
+                till,
+                repeat: false,
+            })),
+            None => Parse::Invalid,
+        },
+        _ => Parse::Invalid,
+    }
+}
+
+// q records into a named or numbered register or "", @ plays any register, the last one played
+// again for @@
+fn parse_macro(input: &str) -> Parse<Action> {
+    let mut chars = input.chars();
+    let record = match chars.next() {
+        Some('q') => true,
+        Some('@') => false,
+        _ => return Parse::Invalid,
+    };
+    match (chars.next(), chars.next()) {
+        (None, _) => Parse::Pending,
+        (Some(name), None) if record && (name.is_ascii_alphanumeric() || name == '"') => {
+            Parse::Complete(Action::Record(name))
+        }
+        (Some(name), None) if !record && (is_register_name(name) || name == '@') => {
+            Parse::Complete(Action::Play(name))
+        }
+        _ => Parse::Invalid,
+    }
+}
+
+// m sets the marks that can be set by hand
+fn parse_set_mark(input: &str) -> Parse<Action> {
+    let mut chars = input.chars();
+    if chars.next() != Some('m') {
+        return Parse::Invalid;
+    }
+    match (chars.next(), chars.next()) {
+        (None, _) => Parse::Pending,
+        (Some(name), None) if name.is_ascii_alphabetic() || "'`[]<>".contains(name) => {
+            Parse::Complete(Action::SetMark(name))
+        }
+        _ => Parse::Invalid,
+    }
+}
+
+// ' and ` followed by a mark, `` is the same mark as ''
+fn parse_mark_motion(input: &str) -> Parse<Motion> {
+    let mut chars = input.chars();
+    let exact = match chars.next() {
+        Some('\'') => false,
+        Some('`') => true,
+        _ => return Parse::Invalid,
+    };
+    match (chars.next(), chars.next()) {
+        (None, _) => Parse::Pending,
+        (Some(name), None) if name.is_ascii_alphabetic() || "'`.^[]<>".contains(name) => {
+            let name = if name == '`' { '\'' } else { name };
+            Parse::Complete(Motion::Mark { name, exact })
+        }
+        _ => Parse::Invalid,
+    }
+}
+
+// % jumps to the matching bracket, but with a count it goes that far into the buffer
+fn counted_motion(motion: Motion, count: Option<usize>) -> Motion {
+    match (motion, count) {
+        (Motion::MatchingBracket, Some(_)) => Motion::Percent,
+        _ => motion,
+    }
+}
+
+fn lookup<T: Copy>(table: &[(&str, T)], input: &str) -> Parse<T> {
+    let mut pending = false;
+    for &(keys, value) in table {
+        if keys == input {
+            return Parse::Complete(value);
+        }
+        if keys.starts_with(input) {
+            pending = true;
+        }
+    }
+    if pending {
+        Parse::Pending
+    } else {
+        Parse::Invalid
+    }
+}
+
+pub(crate) fn map_complete<T, U>(parse: Parse<T>, f: impl FnOnce(T) -> U) -> Parse<U> {
+    match parse {
+        Parse::Complete(value) => Parse::Complete(f(value)),
+        Parse::Ambiguous(value) => Parse::Ambiguous(f(value)),
+        Parse::Pending => Parse::Pending,
+        Parse::Invalid => Parse::Invalid,
+    }
+}
+
+fn map_incomplete<T, U>(parse: Parse<T>) -> Parse<U> {
+    match parse {
+        Parse::Pending => Parse::Pending,
+        _ => Parse::Invalid,
+    }
+}
+
+// bindings come before what's built in
+fn with_binding(
+    binding: Parse<EditorCommand>,
+    builtin: Parse<Command>,
+    count: Option<usize>,
+    register: Option<char>,
+) -> Parse<Command> {
+    let binding = map_complete(binding, |func| Command {
+        count,
+        register,
+        action: Action::Binding(func),
+    });
+    prefer(binding, builtin)
+}
+
+// the first parse wins, and if it could still get longer what the second one completed waits to
+// see if it does
+pub(crate) fn prefer<T>(first: Parse<T>, second: Parse<T>) -> Parse<T> {
+    match (first, second) {
+        (Parse::Invalid, second) => second,
+        (Parse::Pending, Parse::Complete(value)) => Parse::Ambiguous(value),
+        (Parse::Pending, Parse::Ambiguous(value)) => Parse::Ambiguous(value),
+        (Parse::Pending, _) => Parse::Pending,
+        (first, _) => first,
+    }
+}
+
+// None when " is followed by something that isn't a register
+pub(crate) fn split_register(input: &str) -> Option<(Option<usize>, Option<char>, &str)> {
+    let (count, rest) = split_count(input);
+    let rest = match rest.strip_prefix('"') {
+        Some(rest) => rest,
+        None => return Some((count, None, rest)),
+    };
+    let mut chars = rest.chars();
+    match chars.next() {
+        // still waiting for the name
+        None => Some((count, None, "")),
+        Some(name) if is_register_name(name) => {
+            let (second_count, rest) = split_count(chars.as_str());
+            Some((multiply_counts(count, second_count), Some(name), rest))
+        }
+        Some(_) => None,
+    }
+}
+
+// a count can't start with 0, that's the motion to the start of the line
+fn split_count(input: &str) -> (Option<usize>, &str) {
+    if input.starts_with('0') {
+        return (None, input);
+    }
+    let digits = input.len() - input.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return (None, input);
+    }
+    let count = input[..digits].parse().unwrap_or(usize::MAX);
+    (Some(count), &input[digits..])
+}
+
+fn multiply_counts(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.saturating_mul(b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+mod test {
+    #[test]
+    fn should_parse_counts_operators_and_motions() {
+        use crate::command::*;
+        use crate::textobject::TextObjectKind;
+        let bindings: Vec<(&'static str, crate::editor::EditorCommand)> = vec![
+            ("i", |_| Ok(())),
+            ("g-", |_| Ok(())),
+            ("w<C-w>", |_| Ok(())),
+        ];
+        let bindings: Keymap<_> = bindings.into_iter().collect();
+        let parse = |input| parse_normal(input, &bindings);
+
+        assert!(matches!(
+            parse("3dw"),
+            Parse::Complete(Command {
+                count: Some(3),
+                register: None,
+                action: Action::Operator(Operator::Delete, Target::Motion(Motion::WordStart)),
+            })
+        ));
+        assert!(matches!(
+            parse("2c3j"),
+            Parse::Complete(Command {
+                count: Some(6),
+                register: None,
+                action: Action::Operator(Operator::Change, Target::Motion(Motion::Down)),
+            })
+        ));
+        assert!(matches!(
+            parse("5dd"),
+            Parse::Complete(Command {
+                count: Some(5),
+                register: None,
+                action: Action::Operator(Operator::Delete, Target::Line),
+            })
+        ));
+        assert!(matches!(
+            parse("gUgU"),
+            Parse::Complete(Command {
+                action: Action::Operator(Operator::Uppercase, Target::Line),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("d0"),
+            Parse::Complete(Command {
+                count: None,
+                register: None,
+                action: Action::Operator(Operator::Delete, Target::Motion(Motion::LineStart)),
+            })
+        ));
+        assert!(matches!(
+            parse("y50%"),
+            Parse::Complete(Command {
+                count: Some(50),
+                register: None,
+                action: Action::Operator(Operator::Yank, Target::Motion(Motion::Percent)),
+            })
+        ));
+        assert!(matches!(
+            parse("%"),
+            Parse::Complete(Command {
+                action: Action::Motion(Motion::MatchingBracket),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("dt)"),
+            Parse::Complete(Command {
+                action: Action::Operator(
+                    Operator::Delete,
+                    Target::Motion(Motion::FindChar(CharSearch {
+                        target: ')',
+                        forward: true,
+                        till: true,
+                        ..
+                    }))
+                ),
+                ..
+            })
+        ));
+        assert!(matches!(parse("2F"), Parse::Pending));
+        assert!(matches!(parse("f<Esc>"), Parse::Invalid));
+        assert!(matches!(
+            parse("t<Tab>"),
+            Parse::Complete(Command {
+                action: Action::Motion(Motion::FindChar(CharSearch { target: '\t', .. })),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("d<End>"),
+            Parse::Complete(Command {
+                action: Action::Operator(Operator::Delete, Target::Motion(Motion::LineEnd)),
+                ..
+            })
+        ));
+        assert!(matches!(parse("10"), Parse::Pending));
+        assert!(matches!(parse("d"), Parse::Pending));
+        assert!(matches!(parse("g"), Parse::Pending));
+        assert!(matches!(parse("gUg"), Parse::Pending));
+        assert!(matches!(parse("d3"), Parse::Pending));
+        assert!(matches!(parse("2i"), Parse::Complete(_)));
+        assert!(matches!(parse("dx"), Parse::Invalid));
+        assert!(matches!(parse("di"), Parse::Pending));
+        assert!(matches!(
+            parse("c2a("),
+            Parse::Complete(Command {
+                count: Some(2),
+                register: None,
+                action: Action::Operator(
+                    Operator::Change,
+                    Target::TextObject(TextObject {
+                        kind: TextObjectKind::Bracket('(', ')'),
+                        around: true,
+                    })
+                ),
+            })
+        ));
+        assert!(matches!(parse("yiq"), Parse::Invalid));
+        assert!(matches!(parse("q"), Parse::Pending));
+        assert!(matches!(
+            parse("qA"),
+            Parse::Complete(Command {
+                action: Action::Record('A'),
+                ..
+            })
+        ));
+        assert!(matches!(parse("q:"), Parse::Invalid));
+        assert!(matches!(
+            parse("3@@"),
+            Parse::Complete(Command {
+                count: Some(3),
+                action: Action::Play('@'),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("2\"a3yy"),
+            Parse::Complete(Command {
+                count: Some(6),
+                register: Some('a'),
+                action: Action::Operator(Operator::Yank, Target::Line),
+            })
+        ));
+        assert!(matches!(
+            parse("\"_P"),
+            Parse::Complete(Command {
+                register: Some('_'),
+                action: Action::Put { before: true },
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("3gT"),
+            Parse::Complete(Command {
+                count: Some(3),
+                action: Action::Tab { forward: false },
+                ..
+            })
+        ));
+        assert!(matches!(parse("\""), Parse::Pending));
+        assert!(matches!(parse("\"a"), Parse::Pending));
+        assert!(matches!(parse("\"!"), Parse::Invalid));
+        assert!(matches!(
+            parse("d`a"),
+            Parse::Complete(Command {
+                action: Action::Operator(
+                    Operator::Delete,
+                    Target::Motion(Motion::Mark {
+                        name: 'a',
+                        exact: true
+                    })
+                ),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("``"),
+            Parse::Complete(Command {
+                action: Action::Motion(Motion::Mark {
+                    name: '\'',
+                    exact: true
+                }),
+                ..
+            })
+        ));
+        assert!(matches!(parse("m"), Parse::Pending));
+        // w could still become the binding
+        assert!(matches!(
+            parse("2w"),
+            Parse::Ambiguous(Command {
+                count: Some(2),
+                action: Action::Motion(Motion::WordStart),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("2w<C-w>"),
+            Parse::Complete(Command {
+                count: Some(2),
+                action: Action::Binding(_),
+                ..
+            })
+        ));
+        assert!(matches!(parse("dw"), Parse::Complete(_)));
+        assert!(matches!(parse("m."), Parse::Invalid));
+    }
+}
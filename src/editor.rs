@@ -1,12 +1,36 @@
 use crate::*;
 use once_cell::sync::Lazy;
 
-type EditorCommand = fn(&mut Editor);
+use buffers::ListedBuffer;
+use clipboard::{Clipboard, MemoryClipboard};
+use command::{Action, Command, Parse};
+use key::Key;
+use keymap::Keymap;
+use mapping::{Mapping, Mappings};
+use motion::{CharSearch, Motion};
+use options::OptionValue;
+use register::{Register, Registers};
+use repeat::Change;
+use search::{Pattern, Search};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use substitute::Substitution;
+use tabs::TabPage;
+use visual::{BlockInsert, Selection, VisualKind};
+use window::{Layout, Window};
+
+// an empty error is a failure with nothing to say, like a motion that can't go any further. it
+// still stops the macro it was in
+pub type EditorCommand = fn(&mut Editor) -> Result<(), String>;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Mode {
     NORMAL,
     INSERT,
+    VISUAL(VisualKind),
+    COMMAND,
+    // answering y/n/a/q/l for :s with the c flag
+    CONFIRM,
 }
 
 pub struct Editor {
@@ -16,18 +40,98 @@ pub struct Editor {
     pub y_render_offset: usize,
 
     pub editing_file_path: String,
+    // the current buffer's number in the buffer list, the others are kept here
+    pub buffer_number: usize,
+    pub(crate) other_buffers: Vec<ListedBuffer>,
+    pub(crate) last_buffer_number: usize,
+    // the buffer that was edited before this one, for Ctrl-^
+    pub(crate) alternate_buffer: Option<usize>,
+    // how the screen is split between the windows, the current one is what the fields above
+    // are shown in
+    pub(crate) layout: Layout,
+    pub(crate) windows: Vec<Window>,
+    pub current_window: usize,
+    pub(crate) last_window_id: usize,
+    // the tab pages other than the current one, which goes between them at current_tab
+    pub(crate) other_tabs: Vec<TabPage>,
+    pub current_tab: usize,
+    // the global values of the options set with :set, the buffer has its own local ones
+    pub options: HashMap<&'static str, OptionValue>,
 
     pub cursor_animation_instant: Instant,
 
-    current_display_info: DisplayInformation,
+    // the end of the selection that stays put while the cursor moves
+    pub visual_anchor: (usize, usize),
+    // for gv
+    pub last_visual: Option<Selection>,
+    pub(crate) block_insert: Option<BlockInsert>,
+
+    pub(crate) current_display_info: DisplayInformation,
+
+    pub registers: Registers,
+    // the "x typed before the command being run
+    pub(crate) register_name: Option<char>,
+    pub clipboard: Box<dyn Clipboard>,
+    // what the current insert session typed, for ".
+    pub(crate) inserted_text: String,
+    // for .
+    pub(crate) last_change: Option<Change>,
+    // a change waiting for its insert session to end
+    pub(crate) pending_change: Option<Change>,
+    pub last_char_search: Option<CharSearch>,
+    // uppercase marks with their file, and where they were when it was last left
+    pub(crate) file_marks: HashMap<char, (String, (usize, usize))>,
+
+    // keys typed so far of a command or binding that isn't complete yet
+    pub(crate) pending_keys: Vec<Key>,
+    // counting down while the pending keys are a binding that could still get longer
+    pub pending_timeout: Option<Duration>,
+    pub(crate) mappings: Mappings,
+    // while the keys of a noremap mapping are handled
+    pub(crate) noremap: bool,
+    // mappings expanded since the last key that was typed
+    pub(crate) mapping_depth: usize,
+    // what <leader> stands for in mappings
+    pub mapleader: String,
+    // keys played from a macro that haven't been handled yet
+    pub(crate) typeahead: VecDeque<Key>,
+    // the register q is recording into and what was typed so far
+    pub recording: Option<(char, String)>,
+    // for @@
+    pub(crate) last_played: Option<char>,
+
+    // : for commands, / and ? for searches
+    pub command_prompt: char,
+    pub command_line: String,
+    pub command_history: Vec<String>,
+    pub search_history: Vec<String>,
+    pub(crate) command_history_index: Option<usize>,
+    // what the last command had to say, shown below the status line
+    pub status_message: String,
+    pub quit: bool,
+
+    pub last_search: Option<Search>,
+    pub highlight_search: bool,
+    // the last pattern highlighted and what it compiled to
+    pub(crate) highlighted: RefCell<Option<(String, Option<Pattern>)>>,
+    // cursor and scroll to go back to when a search is cancelled
+    pub(crate) search_start: ((usize, usize), usize),
+    pub(crate) substitution: Option<Substitution>,
+    // while :g runs its command on the lines it marked
+    pub(crate) in_global: bool,
+}
 
-    pub matching_input: String,
-    pub matching_input_timeout: Duration,
+// what the pending keys turned out to be
+enum Meaning {
+    Command(Command),
+    // a mapping, after the count and register typed before it
+    Mapping(usize, Mapping),
 }
 
 #[derive(Debug, Clone)]
 pub struct DisplayInformation {
     pub window_height_in_characters: usize,
+    pub window_width_in_characters: usize,
 }
 
 impl Default for Editor {
@@ -45,15 +149,67 @@ impl Editor {
             y_render_offset: 0,
 
             editing_file_path: String::from(""),
+            buffer_number: 1,
+            other_buffers: vec![],
+            last_buffer_number: 1,
+            alternate_buffer: None,
+            layout: Layout::Window(1),
+            windows: vec![Window {
+                id: 1,
+                buffer_number: 1,
+                cursor: (0, 0),
+                y_render_offset: 0,
+            }],
+            current_window: 1,
+            last_window_id: 1,
+            other_tabs: vec![],
+            current_tab: 0,
+            options: options::default_options(),
 
             cursor_animation_instant: Instant::now(),
 
+            visual_anchor: (0, 0),
+            last_visual: None,
+            block_insert: None,
+
             current_display_info: DisplayInformation {
                 window_height_in_characters: 0,
+                window_width_in_characters: 0,
             },
 
-            matching_input: "".into(),
-            matching_input_timeout: Duration::from_secs(1),
+            registers: Default::default(),
+            register_name: None,
+            clipboard: Box::new(MemoryClipboard::default()),
+            inserted_text: String::new(),
+            last_change: None,
+            pending_change: None,
+            last_char_search: None,
+            file_marks: HashMap::new(),
+
+            pending_keys: vec![],
+            pending_timeout: None,
+            mappings: Mappings::default(),
+            noremap: false,
+            mapping_depth: 0,
+            mapleader: "\\".into(),
+            typeahead: VecDeque::new(),
+            recording: None,
+            last_played: None,
+
+            command_prompt: ':',
+            command_line: "".into(),
+            command_history: vec![],
+            search_history: vec![],
+            command_history_index: None,
+            status_message: "".into(),
+            quit: false,
+
+            last_search: None,
+            highlight_search: false,
+            highlighted: RefCell::new(None),
+            search_start: ((0, 0), 0),
+            substitution: None,
+            in_global: false,
         }
     }
 
@@ -64,123 +220,630 @@ impl Editor {
 
     pub fn move_cursor_vertical(&mut self, y: i64) {
         self.buffer.move_cursor_vertical(y);
-        let (_, cursor_y) = self.buffer.cursor();
+        self.scroll_to_cursor();
+        self.cursor_animation_instant = Instant::now();
+    }
+
+    // <PageDown> and <PageUp>, a window at a time
+    pub fn scroll_page(&mut self, pages: i64) {
+        let height = self.window_height() as i64;
+        self.move_cursor_vertical(pages * height.max(1));
+    }
+
+    pub fn enter_insert_mode(&mut self) {
+        self.mode = Mode::INSERT;
+        self.inserted_text.clear();
+        self.buffer.begin_undo_group();
+    }
 
-        if y > 0
-            && cursor_y
-                > self.y_render_offset + self.current_display_info.window_height_in_characters - 7
-        {
-            self.y_render_offset += y as usize;
+    pub fn leave_insert_mode(&mut self) {
+        self.finish_block_insert();
+        let inserted = Register::chars(&self.inserted_text);
+        self.registers.set('.', inserted);
+        self.finish_pending_change();
+        let (x, y) = self.buffer.cursor();
+        self.buffer.set_mark('^', self.buffer.offset_of(x, y));
+        self.mode = Mode::NORMAL;
+        self.buffer.end_undo_group();
+        self.move_cursor_horizontal(0);
+    }
+
+    pub fn undo(&mut self) {
+        if self.buffer.undo() {
+            self.scroll_to_cursor();
         }
-        if y < 0 && cursor_y < self.y_render_offset + 5 && (self.y_render_offset as i64) + y >= 0 {
-            self.y_render_offset -= (-y) as usize;
+        self.cursor_animation_instant = Instant::now();
+    }
+
+    pub fn redo(&mut self) {
+        if self.buffer.redo() {
+            self.scroll_to_cursor();
         }
+        self.cursor_animation_instant = Instant::now();
+    }
 
+    pub fn time_travel(&mut self, states: i64) {
+        let target = (self.buffer.undo_state() as i64 + states)
+            .max(0)
+            .min(self.buffer.undo_state_count() as i64 - 1);
+        if self.buffer.goto_undo_state(target as usize) {
+            self.scroll_to_cursor();
+        }
         self.cursor_animation_instant = Instant::now();
     }
 
-    pub fn handle_input(&mut self, text: &str, is_text_input: bool, info: &DisplayInformation) {
+    // :earlier and :later take a number of states or a time span like 10s, 5m, 2h or 1d
+    pub fn earlier(&mut self, argument: &str) -> Result<(), String> {
+        match parse_time_travel(argument)? {
+            TimeTravel::States(states) => self.time_travel(-(states as i64)),
+            TimeTravel::Time(span) => {
+                let now = self.buffer.undo_state_time(self.buffer.undo_state());
+                let target = now
+                    .checked_sub(span)
+                    .map(|time| self.buffer.undo_state_at(time))
+                    .unwrap_or(0);
+                self.time_travel(target as i64 - self.buffer.undo_state() as i64);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn later(&mut self, argument: &str) -> Result<(), String> {
+        match parse_time_travel(argument)? {
+            TimeTravel::States(states) => self.time_travel(states as i64),
+            TimeTravel::Time(span) => {
+                let now = self.buffer.undo_state_time(self.buffer.undo_state());
+                let target = self.buffer.undo_state_at(now + span);
+                self.time_travel(target as i64 - self.buffer.undo_state() as i64);
+            }
+        }
+        Ok(())
+    }
+
+    // next to the file as .name.uu-undo, or inside undodir named after the whole path
+    pub fn undo_file_path(&self, path: &str) -> Option<std::path::PathBuf> {
+        if path.is_empty() {
+            return None;
+        }
+        let path = std::path::Path::new(path);
+        match self.option("undodir").string() {
+            "" => {
+                let name = format!(".{}.uu-undo", path.file_name()?.to_string_lossy());
+                Some(path.with_file_name(name))
+            }
+            dir => {
+                let absolute = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+                let name = absolute
+                    .to_string_lossy()
+                    .replace(std::path::MAIN_SEPARATOR, "%");
+                Some(std::path::Path::new(dir).join(name))
+            }
+        }
+    }
+
+    // fails when the motion can't go anywhere, like j on the last line or f without a match
+    pub fn apply_motion(&mut self, motion: Motion, count: Option<usize>) -> Result<(), String> {
+        let motion = self.resolve_motion(motion).ok_or_else(String::new)?;
+        if let Motion::Mark { name, .. } = motion {
+            self.find_mark(name)?;
+        }
+        let visible = self.visible_lines();
+        let target = self.buffer.motion_target(motion, count, visible);
+        let (x, y) = target.ok_or_else(String::new)?;
+        if motion.is_jump() {
+            self.push_jump();
+        }
+        let lines = count.unwrap_or(1) as i64;
+        match motion {
+            Motion::Up => self.move_cursor_vertical(-lines),
+            Motion::Down => self.move_cursor_vertical(lines),
+            _ => {
+                self.buffer.set_cursor(x, y, self.mode);
+                if motion == Motion::LineEnd {
+                    self.buffer.set_preferred_column(usize::MAX);
+                }
+                self.scroll_to_cursor();
+                self.cursor_animation_instant = Instant::now();
+            }
+        }
+        Ok(())
+    }
+
+    // remembers f F t T searches and turns ; and , into the search they repeat
+    pub(crate) fn resolve_motion(&mut self, motion: Motion) -> Option<Motion> {
+        match motion {
+            Motion::FindChar(search) => {
+                self.last_char_search = Some(search);
+                Some(motion)
+            }
+            Motion::RepeatFind | Motion::RepeatFindReverse => {
+                let mut search = self.last_char_search?;
+                search.forward ^= motion == Motion::RepeatFindReverse;
+                search.repeat = true;
+                Some(Motion::FindChar(search))
+            }
+            _ => Some(motion),
+        }
+    }
+
+    // the lines of the buffer drawn above the current window's status line
+    pub fn visible_lines(&self) -> std::ops::Range<usize> {
+        self.y_render_offset..self.y_render_offset + self.window_height()
+    }
+
+    pub(crate) fn execute(&mut self, command: Command) -> Result<(), String> {
+        match command.action {
+            Action::Repeat => return self.repeat_change(command.count, command.register),
+            Action::Record(name) => {
+                self.recording = Some((name, String::new()));
+                return Ok(());
+            }
+            Action::Play(name) => return self.play_macro(name, command.count),
+            Action::Tab { forward } => return self.tab_command(command.count, forward),
+            Action::SetMark(name) => {
+                self.set_mark(name);
+                return Ok(());
+            }
+            _ => {}
+        }
+        let selection = self.selection_size();
+        let states_before = self.buffer.undo_state_count();
+        self.register_name = command.register;
+        let result = match command.action {
+            Action::Binding(func) => func(self),
+            Action::Motion(motion) => self.apply_motion(motion, command.count),
+            Action::Operator(operator, target) => {
+                self.apply_operator(operator, target, command.count)
+            }
+            Action::TextObject(object) => {
+                self.select_text_object(object, command.count);
+                Ok(())
+            }
+            Action::Put { before } => self.put(before, command.count),
+            Action::Repeat
+            | Action::Record(_)
+            | Action::Play(_)
+            | Action::SetMark(_)
+            | Action::Tab { .. } => unreachable!(),
+        };
+        self.register_name = None;
+        self.record_change(command, selection, states_before);
+        result
+    }
+
+    pub(crate) fn scroll_to_cursor(&mut self) {
+        let (_, cursor_y) = self.buffer.cursor();
+        let height = self.window_height();
+        let scrolloff = self.option("scrolloff").number() as usize;
+        if cursor_y < self.y_render_offset.saturating_add(scrolloff) {
+            self.y_render_offset = cursor_y.saturating_sub(scrolloff);
+        } else if cursor_y.saturating_add(scrolloff) > self.y_render_offset + height {
+            self.y_render_offset = cursor_y.saturating_add(scrolloff).saturating_sub(height);
+        }
+    }
+
+    // keys written in notation, typed into a window of 40 lines of 80 characters
+    #[cfg(test)]
+    pub(crate) fn type_keys(&mut self, keys: &str) {
+        let info = DisplayInformation {
+            window_height_in_characters: 40,
+            window_width_in_characters: 80,
+        };
+        for key in key::parse_notation(keys) {
+            self.handle_input(key, &info);
+        }
+    }
+
+    // the key that was pressed. a recording macro gets it before anything else, in the notation
+    // it's played back with: keys without a character of their own are recorded like <Up>, and <
+    // as <lt> so typed text isn't taken for a key
+    pub fn handle_input(&mut self, key: Key, info: &DisplayInformation) {
         self.current_display_info = info.clone();
+        if let Some((_, keys)) = &mut self.recording {
+            match key.to_char() {
+                Some('<') => keys.push_str("<lt>"),
+                Some(c) => keys.push(c),
+                None => keys.push_str(&key.to_string()),
+            }
+        }
+        self.mapping_depth = 0;
+        let result = self.handle_key(key).and_then(|_| self.run_typeahead());
+        self.report(result);
+    }
 
-        self.matching_input.push_str(text);
-        self.matching_input_timeout = Duration::from_secs(1);
+    // keys from :normal, . or a noremap mapping, handled without being recorded. keys that were still waiting
+    // wait until these are done
+    pub(crate) fn run_keys(&mut self, keys: impl IntoIterator<Item = Key>) -> Result<(), String> {
+        let waiting = std::mem::replace(&mut self.typeahead, keys.into_iter().collect());
+        let result = self.run_typeahead();
+        self.typeahead = waiting;
+        result
+    }
 
-        match self.mode {
-            Mode::NORMAL => self.handle_input_in_normal_mode(),
-            Mode::INSERT => self.handle_input_in_insert_mode(text, is_text_input),
+    // macros put their keys in front of the typeahead instead of running them, so a macro
+    // that plays itself goes on until something fails instead of running out of stack
+    fn run_typeahead(&mut self) -> Result<(), String> {
+        while let Some(key) = self.typeahead.pop_front() {
+            if let Err(error) = self.handle_key(key) {
+                self.typeahead.clear();
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn report(&mut self, result: Result<(), String>) {
+        match result {
+            Err(error) if !error.is_empty() => self.status_message = error,
+            _ => {}
         }
     }
 
-    fn handle_input_in_normal_mode(&mut self) {
-        let mut reset_matching_input = false;
+    fn handle_key(&mut self, key: Key) -> Result<(), String> {
+        // the keys before this one were waiting to see if a longer binding was coming
+        let waiting = self.pending_timeout.take().is_some();
+        self.pending_keys.push(key);
+
+        let mode = self.mode;
+        match mode {
+            Mode::NORMAL | Mode::VISUAL(_) if self.stops_recording() => Ok(()),
+            Mode::NORMAL | Mode::VISUAL(_) | Mode::INSERT => self.handle_pending_keys(waiting),
+            Mode::COMMAND => self.handle_input_in_command_mode(key),
+            Mode::CONFIRM => {
+                self.handle_input_in_confirm_mode(key);
+                Ok(())
+            }
+        }
+    }
 
-        for (keys, func) in Lazy::force(&NORMAL_BINDINGS) {
-            if keys == &self.matching_input {
-                func(self);
-                reset_matching_input = true;
-                break;
+    fn handle_pending_keys(&mut self, waiting: bool) -> Result<(), String> {
+        match self.parse_pending_keys() {
+            Parse::Pending if self.mode != Mode::INSERT => Ok(()),
+            // the start of an insert mode binding times out into the keys it was typed with
+            Parse::Pending | Parse::Ambiguous(_) => {
+                let timeoutlen = self.option("timeoutlen").number() as u64;
+                self.pending_timeout = Some(Duration::from_millis(timeoutlen));
+                Ok(())
+            }
+            Parse::Complete(meaning) => self.run_pending_keys(meaning),
+            // the keys that were waiting are taken as they are, and the one that didn't fit
+            // starts over
+            Parse::Invalid if waiting => {
+                let key = self.pending_keys.pop().unwrap();
+                self.typeahead.push_front(key);
+                self.resolve_pending_keys()
+            }
+            Parse::Invalid if self.mode == Mode::INSERT => self.type_pending_keys(),
+            Parse::Invalid => {
+                self.pending_keys.clear();
+                Ok(())
             }
         }
+    }
+
+    // mappings come before what's built in, the way bindings come before the rest of the
+    // commands
+    fn parse_pending_keys(&self) -> Parse<Meaning> {
+        let (prefix, mapping) = self.find_mapping();
+        command::prefer(
+            command::map_complete(mapping, |mapping| Meaning::Mapping(prefix, mapping)),
+            command::map_complete(self.parse_builtin_keys(), Meaning::Command),
+        )
+    }
 
-        if reset_matching_input {
-            self.matching_input = "".into();
+    fn parse_builtin_keys(&self) -> Parse<Command> {
+        match self.mode {
+            Mode::NORMAL => command::parse_normal(&self.pending_input(), &NORMAL_BINDINGS),
+            Mode::VISUAL(_) => command::parse_visual(&self.pending_input(), &VISUAL_BINDINGS),
+            _ => {
+                command::map_complete(INSERT_BINDINGS.lookup(&self.pending_keys), |func| Command {
+                    count: None,
+                    register: None,
+                    action: Action::Binding(func),
+                })
+            }
         }
     }
-    fn handle_input_in_insert_mode(&mut self, input: &str, is_text_input: bool) {
-        let mut reset_matching_input = false;
 
-        for (keys, func) in Lazy::force(&INSERT_BINDINGS) {
-            if keys == &self.matching_input {
-                func(self);
-                reset_matching_input = true;
-                break;
+    // when the timeout runs out, or the next key doesn't continue the longer binding
+    fn resolve_pending_keys(&mut self) -> Result<(), String> {
+        match self.parse_pending_keys() {
+            Parse::Ambiguous(meaning) => self.run_pending_keys(meaning),
+            _ if self.mode == Mode::INSERT => self.type_pending_keys(),
+            _ => {
+                self.pending_keys.clear();
+                Ok(())
             }
         }
+    }
 
-        if is_text_input && !reset_matching_input {
-            self.buffer.insert_before_cursor(input);
-            reset_matching_input = true;
+    // insert mode bindings are part of the insert session, not changes of their own
+    fn run_pending_keys(&mut self, meaning: Meaning) -> Result<(), String> {
+        let command = match meaning {
+            Meaning::Mapping(prefix, mapping) => return self.expand_mapping(prefix, mapping),
+            Meaning::Command(command) => command,
+        };
+        self.pending_keys.clear();
+        match (self.mode, command.action) {
+            (Mode::INSERT, Action::Binding(func)) => func(self),
+            _ => self.execute(command),
         }
+    }
 
-        if reset_matching_input {
-            self.matching_input = "".into();
+    // keys that aren't an insert mode binding: the first one is typed, the ones after it are
+    // handled again on their own
+    fn type_pending_keys(&mut self) -> Result<(), String> {
+        let mut keys = std::mem::take(&mut self.pending_keys);
+        let first = keys.remove(0);
+        for key in keys.into_iter().rev() {
+            self.typeahead.push_front(key);
         }
+        if let Some(c) = first.text() {
+            self.buffer.insert_before_cursor(&c.to_string());
+            self.inserted_text.push(c);
+        }
+        Ok(())
+    }
+
+    // the pending keys as they are shown on the status line
+    pub fn pending_input(&self) -> String {
+        self.pending_keys.iter().map(Key::to_string).collect()
     }
 
-    pub fn fade_matching_input(&mut self, delta: Duration) {
-        if delta > self.matching_input_timeout {
-            self.matching_input = "".into();
-        } else {
-            self.matching_input_timeout -= delta;
+    pub(crate) fn clear_pending_keys(&mut self) {
+        self.pending_keys.clear();
+        self.pending_timeout = None;
+    }
+
+    // arrows and the like in insert mode. what . types again starts after the move
+    fn move_in_insert_mode(&mut self, motion: Motion) -> Result<(), String> {
+        match motion {
+            Motion::Left => self.move_cursor_horizontal(-1),
+            Motion::Right => self.move_cursor_horizontal(1),
+            Motion::LineStart | Motion::LineEnd => {
+                let (_, y) = self.buffer.cursor();
+                let x = if motion == Motion::LineStart {
+                    0
+                } else {
+                    usize::MAX
+                };
+                self.buffer.set_cursor(x, y, self.mode);
+                self.cursor_animation_instant = Instant::now();
+            }
+            motion => self.apply_motion(motion, None)?,
+        }
+        self.inserted_text.clear();
+        Ok(())
+    }
+
+    pub fn count_down_timeout(&mut self, delta: Duration) {
+        match self.pending_timeout {
+            Some(timeout) if delta >= timeout => {
+                self.pending_timeout = None;
+                self.mapping_depth = 0;
+                let result = self
+                    .resolve_pending_keys()
+                    .and_then(|_| self.run_typeahead());
+                self.report(result);
+            }
+            Some(timeout) => self.pending_timeout = Some(timeout - delta),
+            None => {}
         }
     }
 }
 
+enum TimeTravel {
+    States(usize),
+    Time(Duration),
+}
+
+fn parse_time_travel(argument: &str) -> Result<TimeTravel, String> {
+    let argument = argument.trim();
+    if argument.is_empty() {
+        return Ok(TimeTravel::States(1));
+    }
+    let (number, unit) = argument.split_at(argument.trim_end_matches(char::is_alphabetic).len());
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid argument: {}", argument))?;
+    let seconds = match unit {
+        "" => return Ok(TimeTravel::States(number as usize)),
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("Invalid argument: {}", argument)),
+    };
+    Ok(TimeTravel::Time(Duration::from_secs(number * seconds)))
+}
+
 // TODO: remove once_cell and inline this stuff into like a match statement lol
-static NORMAL_BINDINGS: Lazy<Vec<(&'static str, EditorCommand)>> = Lazy::new(|| {
-    vec![
-        ("i", |editor| editor.mode = Mode::INSERT),
+static NORMAL_BINDINGS: Lazy<Keymap<EditorCommand>> = Lazy::new(|| {
+    let bindings: Vec<(&str, EditorCommand)> = vec![
+        ("i", |editor| {
+            editor.enter_insert_mode();
+            Ok(())
+        }),
         ("a", |editor| {
             editor.move_cursor_horizontal(1);
-            editor.mode = Mode::INSERT;
-        }),
-        ("h", |editor| editor.move_cursor_horizontal(-1)),
-        ("l", |editor| editor.move_cursor_horizontal(1)),
-        ("k", |editor| editor.move_cursor_vertical(-1)),
-        ("j", |editor| editor.move_cursor_vertical(1)),
-        ("e", |editor| loop {
-            let c = editor.buffer.get_under_cursor();
-            if c == " " || c == "\n" {
-                editor.move_cursor_horizontal(-1);
-                break;
-            }
-            editor.move_cursor_horizontal(1);
+            editor.enter_insert_mode();
+            Ok(())
         }),
-        ("dd", |_editor| println!("dd is nice!")),
-        (" s", |editor| {
-            if !editor.editing_file_path.is_empty() {
-                std::fs::write(&editor.editing_file_path, editor.buffer.as_string()).unwrap_or(());
-            }
+        (":", |editor| {
+            editor.enter_command_mode();
+            Ok(())
+        }),
+        ("/", |editor| {
+            editor.enter_search_mode(true);
+            Ok(())
         }),
+        ("?", |editor| {
+            editor.enter_search_mode(false);
+            Ok(())
+        }),
+        ("n", |editor| editor.search_next(false)),
+        ("N", |editor| editor.search_next(true)),
+        ("*", |editor| editor.search_word_under_cursor(true)),
+        ("#", |editor| editor.search_word_under_cursor(false)),
+        ("u", |editor| {
+            editor.undo();
+            Ok(())
+        }),
+        ("<C-r>", |editor| {
+            editor.redo();
+            Ok(())
+        }),
+        ("v", |editor| {
+            editor.toggle_visual_mode(VisualKind::Chars);
+            Ok(())
+        }),
+        ("V", |editor| {
+            editor.toggle_visual_mode(VisualKind::Lines);
+            Ok(())
+        }),
+        ("<C-v>", |editor| {
+            editor.toggle_visual_mode(VisualKind::Block);
+            Ok(())
+        }),
+        ("gv", |editor| {
+            editor.reselect_visual();
+            Ok(())
+        }),
+        ("<C-o>", |editor| editor.jump_older()),
+        ("<C-^>", |editor| editor.edit_alternate_buffer()),
+        ("<C-w>s", |editor| editor.split_window(false)),
+        ("<C-w>v", |editor| editor.split_window(true)),
+        ("<C-w>w", |editor| editor.next_window()),
+        ("<C-w>h", |editor| editor.go_to_window_beside(-1, 0)),
+        ("<C-w>j", |editor| editor.go_to_window_beside(0, 1)),
+        ("<C-w>k", |editor| editor.go_to_window_beside(0, -1)),
+        ("<C-w>l", |editor| editor.go_to_window_beside(1, 0)),
+        ("<C-w>c", |editor| editor.close_window()),
+        ("<C-w>q", |editor| editor.quit_window(false)),
+        ("<C-w>o", |editor| {
+            editor.only_window();
+            Ok(())
+        }),
+        ("<C-w>+", |editor| {
+            editor.resize_window(1, false);
+            Ok(())
+        }),
+        ("<C-w>-", |editor| {
+            editor.resize_window(-1, false);
+            Ok(())
+        }),
+        ("<C-w>>", |editor| {
+            editor.resize_window(1, true);
+            Ok(())
+        }),
+        ("<C-w><lt>", |editor| {
+            editor.resize_window(-1, true);
+            Ok(())
+        }),
+        ("<C-w>=", |editor| {
+            editor.equalize_windows();
+            Ok(())
+        }),
+        ("<Tab>", |editor| editor.jump_newer()),
+        ("<PageDown>", |editor| {
+            editor.scroll_page(1);
+            Ok(())
+        }),
+        ("<PageUp>", |editor| {
+            editor.scroll_page(-1);
+            Ok(())
+        }),
+        ("g-", |editor| {
+            editor.time_travel(-1);
+            Ok(())
+        }),
+        ("g+", |editor| {
+            editor.time_travel(1);
+            Ok(())
+        }),
+        (" s", |editor| editor.execute_command_line("write")),
         ("  ", |editor| {
             let result = nfd::open_file_dialog(None, None).unwrap();
 
-            if let nfd::Response::Okay(file_path) = result {
-                editor.editing_file_path = file_path.clone();
-                let t = std::fs::read_to_string(file_path).unwrap_or_else(|_| "".to_string());
-                editor.buffer = buffer::Buffer::from(&t);
+            match result {
+                nfd::Response::Okay(file_path) => editor.edit_file(&file_path),
+                _ => Ok(()),
             }
         }),
-    ]
+    ];
+    bindings.into_iter().collect()
 });
 
-static INSERT_BINDINGS: Lazy<Vec<(&'static str, EditorCommand)>> = Lazy::new(|| {
-    vec![
-        ("\x08", |editor| editor.buffer.delete_under_cursor()),
-        ("\x1b", |editor| {
-            editor.mode = Mode::NORMAL;
-            editor.move_cursor_horizontal(0);
+static VISUAL_BINDINGS: Lazy<Keymap<EditorCommand>> = Lazy::new(|| {
+    let bindings: Vec<(&str, EditorCommand)> = vec![
+        ("<Esc>", |editor| {
+            editor.leave_visual_mode();
+            Ok(())
+        }),
+        ("v", |editor| {
+            editor.toggle_visual_mode(VisualKind::Chars);
+            Ok(())
+        }),
+        ("V", |editor| {
+            editor.toggle_visual_mode(VisualKind::Lines);
+            Ok(())
+        }),
+        ("<C-v>", |editor| {
+            editor.toggle_visual_mode(VisualKind::Block);
+            Ok(())
+        }),
+        ("o", |editor| {
+            editor.swap_visual_ends();
+            Ok(())
+        }),
+        (":", |editor| {
+            editor.enter_command_mode();
+            Ok(())
+        }),
+        ("I", |editor| {
+            editor.block_insert(false);
+            Ok(())
+        }),
+        ("A", |editor| {
+            editor.block_insert(true);
+            Ok(())
+        }),
+    ];
+    bindings.into_iter().collect()
+});
+
+static INSERT_BINDINGS: Lazy<Keymap<EditorCommand>> = Lazy::new(|| {
+    let bindings: Vec<(&str, EditorCommand)> = vec![
+        ("<BS>", |editor| {
+            editor.buffer.delete_under_cursor();
+            editor.inserted_text.pop();
+            Ok(())
+        }),
+        ("<Del>", |editor| {
+            editor.buffer.delete_after_cursor();
+            Ok(())
+        }),
+        ("<Esc>", |editor| {
+            editor.leave_insert_mode();
+            Ok(())
+        }),
+        ("<CR>", |editor| {
+            editor.buffer.insert_before_cursor("\n");
+            editor.inserted_text.push('\n');
+            Ok(())
+        }),
+        ("<Left>", |editor| editor.move_in_insert_mode(Motion::Left)),
+        ("<Right>", |editor| {
+            editor.move_in_insert_mode(Motion::Right)
+        }),
+        ("<Up>", |editor| editor.move_in_insert_mode(Motion::Up)),
+        ("<Down>", |editor| editor.move_in_insert_mode(Motion::Down)),
+        ("<Home>", |editor| {
+            editor.move_in_insert_mode(Motion::LineStart)
+        }),
+        ("<End>", |editor| {
+            editor.move_in_insert_mode(Motion::LineEnd)
         }),
-        ("\n", |editor| editor.buffer.insert_before_cursor("\n")),
-    ]
+    ];
+    bindings.into_iter().collect()
 });
@@ -0,0 +1,825 @@
+use crate::buffer::Buffer;
+use crate::command::Operator;
+use crate::editor::{Editor, Mode};
+use crate::key::Key;
+use crate::operator::Region;
+use crate::options::Scope;
+use crate::search::Pattern;
+use crate::substitute::split_delimited;
+use crate::Instant;
+
+// shortest abbreviation accepted for each command, like :w for :write or :sav for :saveas
+static COMMANDS: &[(&str, usize)] = &[
+    ("write", 1),
+    ("wq", 2),
+    ("quit", 1),
+    ("xit", 1),
+    ("exit", 3),
+    ("edit", 1),
+    ("saveas", 3),
+    ("read", 1),
+    ("earlier", 2),
+    ("later", 3),
+    ("nohlsearch", 3),
+    ("substitute", 1),
+    ("global", 1),
+    ("vglobal", 1),
+    ("delete", 1),
+    ("move", 1),
+    ("copy", 2),
+    ("t", 1),
+    ("normal", 4),
+    ("map", 3),
+    ("nmap", 2),
+    ("vmap", 2),
+    ("imap", 2),
+    ("noremap", 2),
+    ("nnoremap", 2),
+    ("vnoremap", 2),
+    ("inoremap", 3),
+    ("unmap", 3),
+    ("nunmap", 3),
+    ("vunmap", 2),
+    ("iunmap", 2),
+    ("source", 2),
+    ("let", 3),
+    ("set", 2),
+    ("setlocal", 4),
+    ("setglobal", 4),
+    ("buffer", 1),
+    ("buffers", 7),
+    ("files", 5),
+    ("ls", 2),
+    ("bnext", 2),
+    ("bNext", 2),
+    ("bprevious", 2),
+    ("bdelete", 2),
+    ("badd", 3),
+    ("split", 2),
+    ("vsplit", 2),
+    ("close", 3),
+    ("only", 2),
+    ("tabnew", 6),
+    ("tabedit", 4),
+    ("tabclose", 4),
+    ("tabmove", 4),
+    ("tabnext", 4),
+    ("tabNext", 4),
+    ("tabprevious", 4),
+];
+
+// first and last line, 1-based and inclusive
+type LineRange = (usize, usize);
+
+impl Editor {
+    pub fn enter_command_mode(&mut self) {
+        // a command typed from visual mode works on the selected lines
+        let range = match self.mode {
+            Mode::VISUAL(_) => {
+                self.leave_visual_mode();
+                "'<,'>"
+            }
+            _ => "",
+        };
+        self.mode = Mode::COMMAND;
+        self.command_prompt = ':';
+        self.command_line = range.into();
+        self.command_history_index = None;
+        self.status_message.clear();
+    }
+
+    pub fn leave_command_mode(&mut self) {
+        self.mode = Mode::NORMAL;
+        self.command_line.clear();
+        self.cursor_animation_instant = Instant::now();
+    }
+
+    // also used for typing search patterns after / and ?
+    pub(crate) fn handle_input_in_command_mode(&mut self, key: Key) -> Result<(), String> {
+        self.pending_keys.clear();
+        let searching = self.command_prompt != ':';
+        match key.to_string().as_str() {
+            "<Esc>" => {
+                if searching {
+                    self.cancel_search();
+                }
+                self.leave_command_mode();
+            }
+            "<CR>" => {
+                let line = std::mem::take(&mut self.command_line);
+                self.leave_command_mode();
+                return if searching {
+                    self.execute_search(&line, self.command_prompt == '/')
+                } else {
+                    self.execute_command_line(&line)
+                };
+            }
+            // backspacing over the empty command line cancels it
+            "<BS>" => {
+                if self.command_line.is_empty() {
+                    if searching {
+                        self.cancel_search();
+                    }
+                    self.leave_command_mode();
+                } else {
+                    self.command_line.pop();
+                }
+            }
+            "<C-p>" | "<Up>" => self.recall_command_history(false),
+            "<C-n>" | "<Down>" => self.recall_command_history(true),
+            _ => {
+                if let Some(c) = key.text() {
+                    self.command_line.push(c);
+                }
+            }
+        }
+        if searching && self.mode == Mode::COMMAND {
+            self.preview_search();
+        }
+        Ok(())
+    }
+
+    fn recall_command_history(&mut self, newer: bool) {
+        let history = if self.command_prompt == ':' {
+            &self.command_history
+        } else {
+            &self.search_history
+        };
+        let length = history.len();
+        let index = match (self.command_history_index, newer) {
+            (None, false) if length > 0 => Some(length - 1),
+            (None, _) => return,
+            (Some(index), false) => Some(index.saturating_sub(1)),
+            (Some(index), true) if index + 1 < length => Some(index + 1),
+            // past the newest entry is the empty line again
+            (Some(_), true) => None,
+        };
+        self.command_line = index
+            .map(|index| history[index].clone())
+            .unwrap_or_default();
+        self.command_history_index = index;
+    }
+
+    // runs a command line typed after :, remembering it in the history
+    pub fn execute_command_line(&mut self, line: &str) -> Result<(), String> {
+        if !line.trim().is_empty() {
+            self.command_history.retain(|old| old != line);
+            self.command_history.push(line.to_string());
+        }
+        let result = self.run_command(line);
+        self.scroll_to_cursor();
+        self.cursor_animation_instant = Instant::now();
+        result
+    }
+
+    // [range] name[!] [argument]
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim_start_matches(|c: char| c == ':' || c.is_whitespace());
+        let (range, rest) = parse_range(self, line)?;
+        let rest = rest.trim_start();
+        let name_length = rest.len() - rest.trim_start_matches(char::is_alphabetic).len();
+        let (name, rest) = rest.split_at(name_length);
+        let raw_argument = rest;
+        let (force, rest) = match rest.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        let argument = rest.trim();
+        let command = if name.is_empty() {
+            ""
+        } else {
+            COMMANDS
+                .iter()
+                .find(|(full, shortest)| name.len() >= *shortest && full.starts_with(name))
+                .map(|(full, _)| *full)
+                .ok_or_else(|| format!("E492: Not an editor command: {}", line))?
+        };
+
+        match command {
+            "" => {
+                // only a range jumps to its last line
+                if let Some((_, last)) = range {
+                    let y = self.line_index(last.max(1))?;
+                    let x = self.buffer.first_non_blank(y);
+                    self.push_jump();
+                    self.buffer.set_cursor(x, y, Mode::NORMAL);
+                }
+                Ok(())
+            }
+            "write" => self.write_command(range, argument, force),
+            // these close the window, it's only once there's one left that uu quits
+            "wq" => {
+                if self.windows.len() == 1 {
+                    self.check_other_buffers(force)?;
+                }
+                self.write_command(range, argument, force)?;
+                self.quit_window(true)
+            }
+            "xit" | "exit" => {
+                if self.windows.len() == 1 {
+                    self.check_other_buffers(force)?;
+                }
+                if self.buffer.is_modified() || !argument.is_empty() {
+                    self.write_command(range, argument, force)?;
+                }
+                self.quit_window(true)
+            }
+            "quit" => self.quit_window(force),
+            // another file is edited in a buffer of its own, the current one is read again
+            "edit" if !argument.is_empty() && argument != self.editing_file_path => {
+                self.edit_file(argument)
+            }
+            "edit" => {
+                self.check_modified(force)?;
+                let path = self.current_file_path()?;
+                self.open_file(&path)
+            }
+            "saveas" => {
+                if argument.is_empty() {
+                    return Err("E471: Argument required".into());
+                }
+                self.check_file_exists(argument, force)?;
+                self.write_lines(None, argument)?;
+                self.editing_file_path = argument.to_string();
+                self.buffer.mark_saved();
+                self.write_undo_file()
+            }
+            "read" => {
+                if argument.is_empty() {
+                    return Err("E32: No file name".into());
+                }
+                let after = match range {
+                    Some((_, last)) => last,
+                    None => self.buffer.cursor().1 + 1,
+                };
+                self.read_file_below(after, argument)
+            }
+            "earlier" => self.earlier(argument),
+            "later" => self.later(argument),
+            "nohlsearch" => {
+                self.highlight_search = false;
+                Ok(())
+            }
+            "substitute" => {
+                let (first, last) = self.range_lines(range)?;
+                self.substitute(first, last, raw_argument.trim_start())
+            }
+            "global" | "vglobal" => {
+                let (first, last) = match range {
+                    Some(_) => self.range_lines(range)?,
+                    None => (0, self.buffer.line_count() - 1),
+                };
+                // :g! is another way to write :v
+                let matching = (command == "global") != force;
+                self.global(first, last, argument, matching)
+            }
+            "delete" => {
+                let (first, last) = self.range_lines(range)?;
+                self.operate(Operator::Delete, Region::Lines(first, last));
+                Ok(())
+            }
+            "copy" | "t" | "move" => {
+                let (first, last) = self.range_lines(range)?;
+                let below = match parse_address(self, argument)? {
+                    (Some(below), "") => below,
+                    _ => return Err("E14: Invalid address".into()),
+                };
+                if command == "move" {
+                    self.move_lines(first, last, below)
+                } else {
+                    self.copy_lines(first, last, below);
+                    Ok(())
+                }
+            }
+            "normal" => {
+                // only the space after the name is taken off, the keys can start with more
+                let keys = rest.strip_prefix(' ').unwrap_or(rest);
+                if keys.is_empty() {
+                    return Err("E471: Argument required".into());
+                }
+                match range {
+                    Some(_) => {
+                        let (first, last) = self.range_lines(range)?;
+                        for y in first..=last {
+                            self.buffer.set_cursor(0, y, Mode::NORMAL);
+                            self.normal(keys, force);
+                        }
+                    }
+                    None => self.normal(keys, force),
+                }
+                Ok(())
+            }
+            "map" | "nmap" | "vmap" | "imap" | "noremap" | "nnoremap" | "vnoremap" | "inoremap" => {
+                self.map_command(command, argument, force)
+            }
+            "unmap" | "nunmap" | "vunmap" | "iunmap" => {
+                self.unmap_command(command, argument, force)
+            }
+            "source" => self.source(argument),
+            "let" => self.let_command(argument),
+            "set" => self.set_command(argument, None),
+            "setlocal" => self.set_command(argument, Some(Scope::Buffer)),
+            "setglobal" => self.set_command(argument, Some(Scope::Global)),
+            "buffer" => {
+                let number = self.find_buffer(argument)?;
+                self.switch_to_buffer(number)
+            }
+            "buffers" | "files" | "ls" => {
+                self.list_buffers();
+                Ok(())
+            }
+            "bnext" | "bNext" | "bprevious" => {
+                let count = match argument {
+                    "" => 1,
+                    count => count.parse().map_err(|_| "E488: Trailing characters")?,
+                };
+                self.next_buffer(count, command == "bnext")
+            }
+            "bdelete" => self.delete_buffer(argument, force),
+            "badd" => self.add_buffer(argument),
+            "split" | "vsplit" => {
+                self.split_window(command == "vsplit")?;
+                match argument {
+                    "" => Ok(()),
+                    path => self.edit_file(path),
+                }
+            }
+            "close" => self.close_window(),
+            "tabnew" | "tabedit" => self.new_tab(argument),
+            "tabclose" => self.close_tab(argument),
+            "tabmove" => self.move_tab(argument),
+            // a tab page's number for :tabnext, how many to go back for the others
+            "tabnext" if !argument.is_empty() => {
+                let index = self.tab_index(argument)?;
+                self.go_to_tab(index)
+            }
+            "tabnext" | "tabNext" | "tabprevious" => {
+                let count = match argument {
+                    "" => 1,
+                    count => count.parse().map_err(|_| "E488: Trailing characters")?,
+                };
+                self.next_tab(count, command == "tabnext")
+            }
+            "only" => {
+                self.only_window();
+                Ok(())
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // reads the file into the current buffer
+    pub fn open_file(&mut self, path: &str) -> Result<(), String> {
+        let (buffer, message) = self.read_file(path)?;
+        self.save_file_marks();
+        self.show_buffer(path.to_string(), buffer, 0);
+        self.status_message = message;
+        Ok(())
+    }
+
+    // a file that doesn't exist yet is an empty buffer. the message says what was read
+    pub(crate) fn read_file(&self, path: &str) -> Result<(Buffer, String), String> {
+        let (text, message) = match std::fs::read_to_string(path) {
+            Ok(text) => {
+                let message = format!("\"{}\" {}L, {}B", path, text.lines().count(), text.len());
+                (text, message)
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                (String::new(), format!("\"{}\" [New]", path))
+            }
+            Err(error) => return Err(format!("\"{}\" {}", path, error)),
+        };
+        let mut buffer = Buffer::from(&text);
+        let undofile = self.option("undofile").boolean();
+        if let (true, Some(undo_file)) = (undofile, self.undo_file_path(path)) {
+            buffer.read_undo_file(&undo_file).unwrap_or(false);
+        }
+        Ok((buffer, message))
+    }
+
+    fn write_command(
+        &mut self,
+        range: Option<LineRange>,
+        path: &str,
+        force: bool,
+    ) -> Result<(), String> {
+        let path = if path.is_empty() {
+            self.current_file_path()?
+        } else {
+            path.to_string()
+        };
+        // a buffer without a name takes the one it's written to, but that file isn't overwritten
+        // any more than another one is
+        let current = path == self.editing_file_path;
+        if !current {
+            self.check_file_exists(&path, force)?;
+        } else if !force && range.is_some() {
+            return Err("E140: Use ! to write partial buffer".into());
+        } else if !force && self.option("readonly").boolean() {
+            return Err("E45: 'readonly' option is set (add ! to override)".into());
+        }
+        self.write_lines(range, &path)?;
+        let becomes_current = current || self.editing_file_path.is_empty();
+        if becomes_current && range.is_none() {
+            self.editing_file_path = path;
+            self.buffer.mark_saved();
+            self.write_undo_file()?;
+        }
+        Ok(())
+    }
+
+    // lines are 1-based and inclusive, None writes the whole buffer
+    fn write_lines(&mut self, range: Option<LineRange>, path: &str) -> Result<(), String> {
+        let bytes = match range {
+            None => 0..self.buffer.len(),
+            Some((first, last)) => {
+                let (first, last) = (self.line_index(first)?, self.line_index(last)?);
+                let end = if last + 1 < self.buffer.line_count() {
+                    self.buffer.line_start(last + 1)
+                } else {
+                    self.buffer.len()
+                };
+                self.buffer.line_start(first)..end
+            }
+        };
+        let contents = self.buffer.file_contents(bytes);
+        std::fs::write(path, &contents).map_err(|error| {
+            format!("\"{}\" E212: Can't open file for writing: {}", path, error)
+        })?;
+        self.status_message = format!(
+            "\"{}\" {}L, {}B written",
+            path,
+            contents.lines().count(),
+            contents.len()
+        );
+        Ok(())
+    }
+
+    fn write_undo_file(&mut self) -> Result<(), String> {
+        let undofile = self.option("undofile").boolean();
+        let undo_file = self.undo_file_path(&self.editing_file_path);
+        if let (true, Some(undo_file)) = (undofile, undo_file) {
+            self.buffer.write_undo_file(&undo_file).map_err(|error| {
+                format!(
+                    "E828: Cannot open undo file for writing: {}: {}",
+                    undo_file.display(),
+                    error
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    // after is a 1-based line number, 0 reads above the first line
+    fn read_file_below(&mut self, after: usize, path: &str) -> Result<(), String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("E484: Can't open file {}: {}", path, error))?;
+        let text = text.replace("\r\n", "\n");
+        if text.is_empty() {
+            return Ok(());
+        }
+        if after > self.buffer.line_count() {
+            return Err("E16: Invalid range".into());
+        }
+        self.buffer.begin_undo_group();
+        self.put_lines_below(after, text.strip_suffix('\n').unwrap_or(&text));
+        let x = self.buffer.first_non_blank(after);
+        self.buffer.set_cursor(x, after, Mode::NORMAL);
+        self.buffer.end_undo_group();
+        Ok(())
+    }
+
+    // text is whole lines without the last line break, after is 1-based and 0 puts them above
+    // the first line
+    pub(crate) fn put_lines_below(&mut self, after: usize, text: &str) {
+        if after < self.buffer.line_count() {
+            let offset = self.buffer.line_start(after);
+            self.buffer.insert(offset, &(text.to_string() + "\n"));
+        } else {
+            // below the last line, which has no line break to insert after
+            self.buffer
+                .insert(self.buffer.len(), &("\n".to_string() + text));
+        }
+    }
+
+    fn lines_text(&self, first: usize, last: usize) -> String {
+        self.buffer
+            .slice(self.buffer.line_start(first)..self.buffer.line_end(last))
+    }
+
+    // :t and :copy, the cursor ends up on the last copy
+    fn copy_lines(&mut self, first: usize, last: usize, below: usize) {
+        let text = self.lines_text(first, last);
+        self.buffer.begin_undo_group();
+        self.put_lines_below(below, &text);
+        let y = below + last - first;
+        let x = self.buffer.first_non_blank(y);
+        self.buffer.set_cursor(x, y, Mode::NORMAL);
+        self.buffer.end_undo_group();
+    }
+
+    fn move_lines(&mut self, first: usize, last: usize, below: usize) -> Result<(), String> {
+        if below > first && below <= last {
+            return Err("E134: Cannot move a range of lines into itself".into());
+        }
+        let text = self.lines_text(first, last);
+        let count = last - first + 1;
+        self.buffer.begin_undo_group();
+        self.put_lines_below(below, &text);
+        let y = if below <= first {
+            self.delete_region(&Region::Lines(first + count, last + count));
+            below + count - 1
+        } else {
+            self.delete_region(&Region::Lines(first, last));
+            below - 1
+        };
+        let x = self.buffer.first_non_blank(y);
+        self.buffer.set_cursor(x, y, Mode::NORMAL);
+        self.buffer.end_undo_group();
+        Ok(())
+    }
+
+    // types keys as if in normal mode, then leaves whatever mode they ended in. keys after one
+    // that fails aren't typed. :normal! doesn't use mappings
+    fn normal(&mut self, keys: &str, force: bool) {
+        self.clear_pending_keys();
+        let keys = keys.chars().map(Key::from_char);
+        let result = if force {
+            self.without_mappings(|editor| editor.run_keys(keys))
+        } else {
+            self.run_keys(keys)
+        };
+        self.report(result);
+        self.clear_pending_keys();
+        if self.mode != Mode::NORMAL {
+            let result = self.run_keys(vec![Key::from_char('\x1b')]);
+            self.report(result);
+            self.clear_pending_keys();
+        }
+    }
+
+    // marks the lines matching the pattern, or not matching it, and runs the command on each
+    // of them. marks stay with their lines as the command deletes or adds others
+    fn global(
+        &mut self,
+        first: usize,
+        last: usize,
+        argument: &str,
+        matching: bool,
+    ) -> Result<(), String> {
+        if self.in_global {
+            return Err("E147: Cannot do :global recursive".into());
+        }
+        let mut chars = argument.chars();
+        let delimiter = match chars.next() {
+            Some(c) if !c.is_alphanumeric() && c != '\\' && c != '"' && c != '|' => c,
+            _ => return Err("E146: Regular expressions can't be delimited by letters".into()),
+        };
+        let (pattern, command) = split_delimited(chars.as_str(), delimiter);
+        let command = command.unwrap_or("");
+        let pattern = self.remember_search_pattern(pattern)?;
+        let regex = Pattern::new(&pattern)?.regex().clone();
+        let lines: Vec<usize> = (first..=last)
+            .filter(|&y| regex.is_match(&self.buffer.line(y)) == matching)
+            .collect();
+        if lines.is_empty() {
+            return Err(if matching {
+                format!("E486: Pattern not found: {}", pattern)
+            } else {
+                format!("E538: Pattern found in every line: {}", pattern)
+            });
+        }
+
+        self.buffer.mark_lines(&lines);
+        self.buffer.begin_undo_group();
+        self.in_global = true;
+        let mut result = Ok(());
+        while let Some(y) = self.buffer.take_marked_line() {
+            let x = self.buffer.first_non_blank(y);
+            self.buffer.set_cursor(x, y, Mode::NORMAL);
+            result = self.run_command(command);
+            if result.is_err() {
+                break;
+            }
+        }
+        self.in_global = false;
+        self.buffer.clear_line_marks();
+        self.buffer.end_undo_group();
+        result
+    }
+
+    fn current_file_path(&self) -> Result<String, String> {
+        if self.editing_file_path.is_empty() {
+            Err("E32: No file name".into())
+        } else {
+            Ok(self.editing_file_path.clone())
+        }
+    }
+
+    pub(crate) fn check_modified(&self, force: bool) -> Result<(), String> {
+        if !force && self.buffer.is_modified() {
+            Err("E37: No write since last change (add ! to override)".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_file_exists(&self, path: &str, force: bool) -> Result<(), String> {
+        if !force && std::path::Path::new(path).exists() {
+            Err("E13: File exists (add ! to override)".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    // 0-based lines of a range, the current line without one
+    fn range_lines(&self, range: Option<LineRange>) -> Result<(usize, usize), String> {
+        match range {
+            Some((first, last)) => Ok((self.line_index(first)?, self.line_index(last)?)),
+            None => Ok((self.buffer.cursor().1, self.buffer.cursor().1)),
+        }
+    }
+
+    // 1-based line number to a line of the buffer
+    fn line_index(&self, line: usize) -> Result<usize, String> {
+        if line == 0 || line > self.buffer.line_count() {
+            Err("E16: Invalid range".into())
+        } else {
+            Ok(line - 1)
+        }
+    }
+}
+
+// % or address[,address], addresses stay 1-based so :0r can read above the first line
+fn parse_range<'a>(
+    editor: &Editor,
+    input: &'a str,
+) -> Result<(Option<LineRange>, &'a str), String> {
+    if let Some(rest) = input.strip_prefix('%') {
+        return Ok((Some((1, editor.buffer.line_count())), rest));
+    }
+    let (first, rest) = parse_address(editor, input)?;
+    let rest = match rest.strip_prefix(',') {
+        Some(rest) => rest,
+        None => return Ok((first.map(|line| (line, line)), rest)),
+    };
+    let current = editor.buffer.cursor().1 + 1;
+    let first = first.unwrap_or(current);
+    let (last, rest) = parse_address(editor, rest)?;
+    let last = last.unwrap_or(current);
+    // backwards ranges are turned around instead of asking
+    Ok((Some((first.min(last), first.max(last))), rest))
+}
+
+// (number | . | $ | 'mark) followed by any number of +n and -n
+fn parse_address<'a>(editor: &Editor, input: &'a str) -> Result<(Option<usize>, &'a str), String> {
+    let current = editor.buffer.cursor().1 as i64 + 1;
+    let digits = input.len() - input.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let (mut line, mut rest) = if digits > 0 {
+        let number = input[..digits]
+            .parse::<i64>()
+            .map_err(|_| "E16: Invalid range".to_string())?;
+        (Some(number), &input[digits..])
+    } else if let Some(rest) = input.strip_prefix('.') {
+        (Some(current), rest)
+    } else if let Some(rest) = input.strip_prefix('$') {
+        (Some(editor.buffer.line_count() as i64), rest)
+    } else if let Some(rest) = input.strip_prefix('\'') {
+        let name = rest.chars().next().ok_or("E20: Mark not set")?;
+        let offset = editor.buffer.mark(name).ok_or("E20: Mark not set")?;
+        let line = editor.buffer.line_of_offset(offset);
+        (Some(line as i64 + 1), &rest[name.len_utf8()..])
+    } else {
+        (None, input)
+    };
+
+    while let Some(sign) = rest.chars().next().filter(|&c| c == '+' || c == '-') {
+        rest = &rest[1..];
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let offset = if digits > 0 {
+            rest[..digits]
+                .parse::<i64>()
+                .map_err(|_| "E16: Invalid range".to_string())?
+        } else {
+            1
+        };
+        rest = &rest[digits..];
+        let base = line.unwrap_or(current);
+        line = Some(if sign == '+' {
+            base.saturating_add(offset)
+        } else {
+            base - offset
+        });
+    }
+
+    match line {
+        Some(line) if line < 0 || line > editor.buffer.line_count() as i64 => {
+            Err("E16: Invalid range".into())
+        }
+        line => Ok((line.map(|line| line as usize), rest)),
+    }
+}
+
+mod test {
+    #[test]
+    fn should_write_read_and_quit_from_the_command_line() {
+        let directory = std::env::temp_dir().join(format!("uu-ex-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("file.txt");
+        let path = path.to_str().unwrap();
+        let part = directory.join("part.txt");
+        let part = part.to_str().unwrap();
+
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("one\ntwo\nthree\n");
+        editor.type_keys(":w\n");
+        assert_eq!("E32: No file name", editor.status_message);
+        editor.type_keys(&format!(":w {}\n", path));
+        assert_eq!(path, editor.editing_file_path);
+        assert_eq!("one\ntwo\nthree\n", std::fs::read_to_string(path).unwrap());
+        assert!(editor.status_message.ends_with("3L, 14B written"));
+
+        editor.type_keys(&format!(":2,$-1w {}\n", part));
+        assert_eq!("two\nthree\n", std::fs::read_to_string(part).unwrap());
+        editor.type_keys(&format!(":w {}\n", part));
+        assert_eq!(
+            "E13: File exists (add ! to override)",
+            editor.status_message
+        );
+        // only part of the file would be left
+        editor.type_keys(":2,3w\n");
+        assert_eq!("E140: Use ! to write partial buffer", editor.status_message);
+        assert_eq!("one\ntwo\nthree\n", std::fs::read_to_string(path).unwrap());
+
+        let mut unnamed = crate::editor::Editor::new();
+        unnamed.type_keys(&format!(":w {}\n", path));
+        assert_eq!(
+            "E13: File exists (add ! to override)",
+            unnamed.status_message
+        );
+        assert_eq!("", unnamed.editing_file_path);
+        assert_eq!("one\ntwo\nthree\n", std::fs::read_to_string(path).unwrap());
+
+        editor.type_keys(&format!(":1r {}\n", part));
+        assert_eq!("one\ntwo\nthree\ntwo\nthree\n", editor.buffer.as_string());
+        assert_eq!((0, 1), editor.buffer.cursor());
+        editor.type_keys(":q\n");
+        assert_eq!(
+            "E37: No write since last change (add ! to override)",
+            editor.status_message
+        );
+        assert!(!editor.quit);
+
+        editor.type_keys(":\x10\x10\x0e");
+        assert_eq!("q", editor.command_line);
+        editor.type_keys("\x08x\n");
+        assert_eq!(
+            "one\ntwo\nthree\ntwo\nthree\n",
+            std::fs::read_to_string(path).unwrap()
+        );
+        assert!(editor.quit);
+        assert_eq!(crate::editor::Mode::NORMAL, editor.mode);
+
+        editor.quit = false;
+        editor.type_keys(":e!\n:$-1\n");
+        assert_eq!("one\ntwo\nthree\ntwo\nthree\n", editor.buffer.as_string());
+        assert_eq!(4, editor.buffer.cursor().1);
+        editor.type_keys("Vk:d\n");
+        assert_eq!("one\ntwo\nthree\n", editor.buffer.as_string());
+        editor.type_keys(":'<\n");
+        assert_eq!(3, editor.buffer.cursor().1);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn should_run_commands_on_matching_lines() {
+        let mut editor = crate::editor::Editor::new();
+        let text = "foo 1\nbar 2\nfoo 3\nbaz 4";
+        editor.buffer = crate::buffer::Buffer::from(text);
+
+        let mut check = |command: &str, expected: &str| {
+            editor.execute_command_line(command).unwrap();
+            assert_eq!(expected, editor.buffer.as_string(), "{}", command);
+            editor.undo();
+            assert_eq!(text, editor.buffer.as_string(), "{}", command);
+        };
+        check("g/foo/d", "bar 2\nbaz 4");
+        check("v/foo/d", "foo 1\nfoo 3");
+        check("g!/foo/s/\\d/#/", "foo 1\nbar #\nfoo 3\nbaz #");
+        check("g/^/m0", "baz 4\nfoo 3\nbar 2\nfoo 1");
+        check("g/foo/t$", "foo 1\nbar 2\nfoo 3\nbaz 4\nfoo 1\nfoo 3");
+        check("2,$g/o/normal $ix", "foo 1\nbar 2\nfoo x3\nbaz 4");
+        check("g/ba/s//BA/", "foo 1\nBAr 2\nfoo 3\nBAz 4");
+
+        assert_eq!(
+            Err("E147: Cannot do :global recursive".into()),
+            editor.execute_command_line("g/foo/g/1/d")
+        );
+        assert_eq!(
+            Err("E538: Pattern found in every line:  ".into()),
+            editor.execute_command_line("v/ /d")
+        );
+        assert_eq!(text, editor.buffer.as_string());
+    }
+}
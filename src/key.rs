@@ -0,0 +1,259 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCode {
+    Char(char),
+    Esc,
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Insert,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    F(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    // only kept for keys that aren't characters, a shifted character is already its own
+    // character
+    pub shift: bool,
+}
+
+// a key as it was pressed, written <C-r>, <Esc>, <S-Tab>, <PageDown>... in bindings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: Modifiers,
+}
+
+static NAMES: &[(&str, KeyCode)] = &[
+    ("Esc", KeyCode::Esc),
+    ("CR", KeyCode::Enter),
+    ("Enter", KeyCode::Enter),
+    ("Return", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("BS", KeyCode::Backspace),
+    ("Del", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Space", KeyCode::Char(' ')),
+    ("lt", KeyCode::Char('<')),
+];
+
+impl Key {
+    pub fn new(code: KeyCode, modifiers: Modifiers) -> Self {
+        let modifiers = match code {
+            KeyCode::Char(_) => Modifiers {
+                shift: false,
+                ..modifiers
+            },
+            _ => modifiers,
+        };
+        Self { code, modifiers }
+    }
+
+    // control characters are the keys that type them: \x1b is <Esc>, \x12 is <C-r>...
+    pub fn from_char(c: char) -> Self {
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Default::default()
+        };
+        let (code, modifiers) = match c {
+            '\x1b' => (KeyCode::Esc, Default::default()),
+            '\n' | '\r' => (KeyCode::Enter, Default::default()),
+            '\t' => (KeyCode::Tab, Default::default()),
+            '\x08' => (KeyCode::Backspace, Default::default()),
+            '\x7f' => (KeyCode::Delete, Default::default()),
+            '\x01'..='\x1a' => (KeyCode::Char((c as u8 - 1 + b'a') as char), ctrl),
+            '\0'..='\x1f' => (KeyCode::Char((c as u8 + 0x40) as char), ctrl),
+            c => (KeyCode::Char(c), Default::default()),
+        };
+        Self { code, modifiers }
+    }
+
+    // the character that stands for the key in a register, if it has one
+    pub fn to_char(&self) -> Option<char> {
+        let Modifiers { ctrl, alt, shift } = self.modifiers;
+        if alt || shift {
+            return None;
+        }
+        match (self.code, ctrl) {
+            (KeyCode::Esc, false) => Some('\x1b'),
+            (KeyCode::Enter, false) => Some('\n'),
+            (KeyCode::Tab, false) => Some('\t'),
+            (KeyCode::Backspace, false) => Some('\x08'),
+            (KeyCode::Delete, false) => Some('\x7f'),
+            (KeyCode::Char(c), false) => Some(c),
+            (KeyCode::Char(c), true) if c.is_ascii_lowercase() => {
+                Some((c as u8 - b'a' + 1) as char)
+            }
+            (KeyCode::Char(c @ '@'..='_'), true) => Some((c as u8 - 0x40) as char),
+            _ => None,
+        }
+    }
+
+    // what the key types into the buffer or the command line
+    pub fn text(&self) -> Option<char> {
+        match (self.code, self.modifiers.ctrl || self.modifiers.alt) {
+            (KeyCode::Char(c), false) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    // plain characters are themselves, the rest go between < and >
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Modifiers { ctrl, alt, shift } = self.modifiers;
+        if let (KeyCode::Char(c), false, false) = (self.code, ctrl, alt) {
+            return write!(f, "{}", c);
+        }
+        write!(f, "<")?;
+        if ctrl {
+            write!(f, "C-")?;
+        }
+        if alt {
+            write!(f, "M-")?;
+        }
+        if shift {
+            write!(f, "S-")?;
+        }
+        match self.code {
+            KeyCode::Char(c) => write!(f, "{}", c)?,
+            KeyCode::F(n) => write!(f, "F{}", n)?,
+            code => {
+                let (name, _) = NAMES.iter().find(|(_, named)| *named == code).unwrap();
+                write!(f, "{}", name)?;
+            }
+        }
+        write!(f, ">")
+    }
+}
+
+// keys written the way bindings are. a < that doesn't start a key name is just <
+pub fn parse_notation(keys: &str) -> Vec<Key> {
+    let mut parsed = vec![];
+    let mut rest = keys;
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                if let Some(key) = parse_key_name(&rest[1..end]) {
+                    parsed.push(key);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+        }
+        parsed.push(Key::from_char(c));
+        rest = &rest[c.len_utf8()..];
+    }
+    parsed
+}
+
+// C-x, M-x or A-x, S-x and the names of keys, ignoring case like vim does
+fn parse_key_name(mut name: &str) -> Option<Key> {
+    let mut modifiers = Modifiers::default();
+    while name.len() > 2 && name.as_bytes()[1] == b'-' {
+        match name.as_bytes()[0].to_ascii_uppercase() {
+            b'C' => modifiers.ctrl = true,
+            b'M' | b'A' => modifiers.alt = true,
+            b'S' => modifiers.shift = true,
+            _ => return None,
+        }
+        name = &name[2..];
+    }
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        // <C-R> is <C-r>, there's no telling them apart
+        (Some(c), None) if modifiers.ctrl => KeyCode::Char(c.to_ascii_lowercase()),
+        (Some(c), None) if modifiers != Modifiers::default() => KeyCode::Char(c),
+        _ => match NAMES
+            .iter()
+            .find(|(named, _)| named.eq_ignore_ascii_case(name))
+        {
+            Some(&(_, code)) => code,
+            None => {
+                let n: u8 = name.strip_prefix(|c| c == 'F' || c == 'f')?.parse().ok()?;
+                if !(1..=12).contains(&n) {
+                    return None;
+                }
+                KeyCode::F(n)
+            }
+        },
+    };
+    Some(Key::new(code, modifiers))
+}
+
+mod test {
+    #[test]
+    fn should_write_and_read_keys_in_notation() {
+        use super::{parse_notation, Key, KeyCode, Modifiers};
+        let keys = |notation: &str| {
+            parse_notation(notation)
+                .iter()
+                .map(|key| key.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec!["<C-r>", "<Esc>", "<S-Tab>", "<PageDown>"],
+            keys("<c-R><esc><S-Tab><pagedown>")
+        );
+        assert_eq!(
+            vec!["<CR>", "<BS>", "<F12>", "<M-x>", " ", "<"],
+            keys("<Enter><bs><F12><A-x><Space><lt>")
+        );
+        assert_eq!(
+            vec!["<", "b", ">", "<", "F", "1", "3", ">", "<"],
+            keys("<b><F13><")
+        );
+        assert_eq!(
+            vec!["<C-r>", "<Esc>", "<CR>", "<Tab>", "<BS>", "d"],
+            keys("\x12\x1b\n\t\x08d")
+        );
+
+        // keys with a character of their own go back to it
+        for c in "\x12\x16\x1b\n\t\x08a<".chars() {
+            assert_eq!(Some(c), Key::from_char(c).to_char());
+        }
+        let shift = Modifiers {
+            shift: true,
+            ..Default::default()
+        };
+        assert_eq!(None, Key::new(KeyCode::Tab, shift).to_char());
+        assert_eq!("A", Key::new(KeyCode::Char('A'), shift).to_string());
+        assert_eq!(Some('x'), Key::from_char('x').text());
+        assert_eq!(None, Key::from_char('\x12').text());
+    }
+
+    #[test]
+    fn should_handle_keys_without_characters() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("one two\nthree");
+
+        editor.type_keys("<End>ix<Left><Left><Del><Home>y<Esc>");
+        assert_eq!("yone txo\nthree", editor.buffer.as_string());
+        editor.type_keys("<Down>0d<End>");
+        assert_eq!("yone txo\n", editor.buffer.as_string());
+        editor.type_keys("k:s/o/0/<CR>:<Up><CR>");
+        assert_eq!("y0ne tx0\n", editor.buffer.as_string());
+        editor.type_keys("<C-v><Esc>u<C-r>");
+        assert_eq!("y0ne tx0\n", editor.buffer.as_string());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::command::Parse;
+use crate::key::{parse_notation, Key};
+use std::collections::HashMap;
+use std::iter::FromIterator;
+
+// the bindings of a mode as a trie of keys, so a sequence that can't become one is known as
+// soon as its key is typed
+#[derive(Debug, Clone)]
+pub struct Keymap<T> {
+    value: Option<T>,
+    children: HashMap<Key, Keymap<T>>,
+}
+
+impl<T> Default for Keymap<T> {
+    fn default() -> Self {
+        Self {
+            value: None,
+            children: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone> Keymap<T> {
+    pub fn insert(&mut self, keys: &[Key], value: T) {
+        let node = keys.iter().fold(self, |node, key| {
+            node.children.entry(*key).or_insert_with(Default::default)
+        });
+        node.value = Some(value);
+    }
+
+    // branches left without bindings go too, so they aren't taken for prefixes
+    pub fn remove(&mut self, keys: &[Key]) -> Option<T> {
+        let (key, rest) = match keys.split_first() {
+            Some(split) => split,
+            None => return self.value.take(),
+        };
+        let child = self.children.get_mut(key)?;
+        let value = child.remove(rest);
+        if child.value.is_none() && child.children.is_empty() {
+            self.children.remove(key);
+        }
+        value
+    }
+
+    // every binding with the keys it's under
+    pub fn bindings(&self) -> Vec<(Vec<Key>, T)> {
+        let mut bindings = vec![];
+        if let Some(value) = &self.value {
+            bindings.push((vec![], value.clone()));
+        }
+        for (key, child) in &self.children {
+            for (mut keys, value) in child.bindings() {
+                keys.insert(0, *key);
+                bindings.push((keys, value));
+            }
+        }
+        bindings
+    }
+
+    // a binding that is also the start of a longer one is ambiguous until the next key
+    pub fn lookup(&self, keys: &[Key]) -> Parse<T> {
+        let mut node = self;
+        for key in keys {
+            node = match node.children.get(key) {
+                Some(child) => child,
+                None => return Parse::Invalid,
+            };
+        }
+        match (node.value.clone(), node.children.is_empty()) {
+            (Some(value), true) => Parse::Complete(value),
+            (Some(value), false) => Parse::Ambiguous(value),
+            (None, false) => Parse::Pending,
+            (None, true) => Parse::Invalid,
+        }
+    }
+}
+
+// bindings written in key notation
+impl<'a, T: Clone> FromIterator<(&'a str, T)> for Keymap<T> {
+    fn from_iter<I: IntoIterator<Item = (&'a str, T)>>(bindings: I) -> Self {
+        let mut keymap = Self::default();
+        for (keys, value) in bindings {
+            keymap.insert(&parse_notation(keys), value);
+        }
+        keymap
+    }
+}
+
+mod test {
+    #[test]
+    fn should_find_bindings_and_their_prefixes() {
+        use super::Keymap;
+        use crate::command::Parse;
+        use crate::key::parse_notation;
+        let keymap: Keymap<usize> = vec![("d", 1), ("dd", 2), ("<C-w>j", 3), ("<C-w>k", 4)]
+            .into_iter()
+            .collect();
+        let lookup = |keys| keymap.lookup(&parse_notation(keys));
+
+        assert!(matches!(lookup(""), Parse::Pending));
+        assert!(matches!(lookup("d"), Parse::Ambiguous(1)));
+        assert!(matches!(lookup("dd"), Parse::Complete(2)));
+        assert!(matches!(lookup("ddd"), Parse::Invalid));
+        assert!(matches!(lookup("<C-w>"), Parse::Pending));
+        assert!(matches!(lookup("<C-w>k"), Parse::Complete(4)));
+        assert!(matches!(lookup("<C-w>x"), Parse::Invalid));
+        assert!(matches!(lookup("<"), Parse::Invalid));
+
+        let mut keymap = keymap;
+        assert_eq!(Some(4), keymap.remove(&parse_notation("<C-w>k")));
+        assert_eq!(None, keymap.remove(&parse_notation("<C-w>")));
+        assert!(matches!(
+            keymap.lookup(&parse_notation("<C-w>")),
+            Parse::Pending
+        ));
+        keymap.remove(&parse_notation("<C-w>j"));
+        assert!(matches!(
+            keymap.lookup(&parse_notation("<C-w>")),
+            Parse::Invalid
+        ));
+        assert_eq!(2, keymap.bindings().len());
+    }
+}
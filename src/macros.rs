@@ -0,0 +1,108 @@
+use crate::editor::Editor;
+use crate::key::{parse_notation, Key};
+
+impl Editor {
+    // q ends the recording it's in, and isn't part of it
+    pub(crate) fn stops_recording(&mut self) -> bool {
+        if self.pending_keys != [Key::from_char('q')] {
+            return false;
+        }
+        let (name, keys) = match self.recording.take() {
+            Some(recording) => recording,
+            None => return false,
+        };
+        let keys = keys.strip_suffix('q').unwrap_or(&keys);
+        self.registers.record(name, keys);
+        self.pending_keys.clear();
+        true
+    }
+
+    // @ with a register plays it as if typed, @@ plays the last one again and @: runs the last
+    // command line
+    pub(crate) fn play_macro(&mut self, name: char, count: Option<usize>) -> Result<(), String> {
+        let name = match name {
+            '@' => self
+                .last_played
+                .ok_or("E748: No previously used register")?,
+            name => name,
+        };
+        self.last_played = Some(name);
+        let count = count.unwrap_or(1).max(1);
+        if name == ':' {
+            let line = self
+                .command_history
+                .last()
+                .cloned()
+                .ok_or("E30: No previous command line")?;
+            for _ in 0..count {
+                self.execute_command_line(&line)?;
+            }
+            return Ok(());
+        }
+        let keys = match self.register(name) {
+            Some(register) => register.text.repeat(count),
+            None => return Ok(()),
+        };
+        // the keys go in front of the ones still waiting, so the rest of a macro that plays
+        // another one runs once the other one is done
+        for key in parse_notation(&keys).into_iter().rev() {
+            self.typeahead.push_front(key);
+        }
+        Ok(())
+    }
+}
+
+mod test {
+    #[test]
+    fn should_record_and_play_macros() {
+        let text = |editor: &crate::editor::Editor, name: char| {
+            editor.register(name).map(|register| register.text)
+        };
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("a 1\nb 2\nc 3\nd 4\ne 5");
+
+        editor.type_keys("qa0ix\x1bjq");
+        assert_eq!(Some("0ix\x1bj".into()), text(&editor, 'a'));
+        assert_eq!(None, text(&editor, '"'));
+        assert_eq!("xa 1\nb 2\nc 3\nd 4\ne 5", editor.buffer.as_string());
+        editor.type_keys("2@a");
+        assert_eq!("xa 1\nxb 2\nxc 3\nd 4\ne 5", editor.buffer.as_string());
+        editor.type_keys("@@");
+        assert_eq!("xa 1\nxb 2\nxc 3\nxd 4\ne 5", editor.buffer.as_string());
+        assert_eq!((1, 4), editor.buffer.cursor());
+
+        // stops at the j that can't go further instead of going on forever
+        editor.type_keys("ggqbqqb$i-\x1bj@bq");
+        assert_eq!(Some("$i-\x1bj@b".into()), text(&editor, 'b'));
+        editor.type_keys("@b");
+        assert_eq!(
+            "xa -1\nxb -2\nxc -3\nxd -4\ne -5",
+            editor.buffer.as_string()
+        );
+        editor.type_keys("u");
+        assert_eq!("xa -1\nxb -2\nxc -3\nxd -4\ne 5", editor.buffer.as_string());
+
+        // a macro can be put, edited and yanked back
+        editor.type_keys("G$\"apFxclX\x1bF0\"ay$uu");
+        assert_eq!(Some("0iX\x1bj".into()), text(&editor, 'a'));
+        assert_eq!("xa -1\nxb -2\nxc -3\nxd -4\ne 5", editor.buffer.as_string());
+        editor.type_keys("gg@a");
+        assert_eq!("Xxa -1\n", &editor.buffer.as_string()[..7]);
+        editor.type_keys("qAkq");
+        assert_eq!(Some("0iX\x1bjk".into()), text(&editor, 'a'));
+
+        // text that looks like a key is typed again, not pressed
+        editor.type_keys("Gqc0i<lt>Esc>!<Esc>q");
+        assert_eq!(Some("0i<lt>Esc>!\x1b".into()), text(&editor, 'c'));
+        editor.type_keys("k@cgg");
+        assert!(editor
+            .buffer
+            .as_string()
+            .ends_with("\n<Esc>!xd -4\n<Esc>!e 5"));
+
+        editor.type_keys(":s/-/+/\nj@:");
+        assert_eq!("Xxa +1\nxb +2\n", &editor.buffer.as_string()[..13]);
+        editor.type_keys("2@:");
+        assert_eq!("E486: Pattern not found: -", editor.status_message);
+    }
+}
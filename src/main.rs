@@ -1,17 +1,40 @@
 use editor::{DisplayInformation, Editor};
+use key::{Key, KeyCode, Modifiers};
 use std::cmp::min;
 use std::time::{Duration, Instant};
 use unicode_segmentation::UnicodeSegmentation;
 
 use femtovg::{renderer::OpenGl, Canvas};
-use glutin::event::{Event, WindowEvent};
+use glutin::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 use glutin::event_loop::{ControlFlow, EventLoop};
 use glutin::window::WindowBuilder;
 use glutin::ContextBuilder;
 
+pub mod args;
 pub mod buffer;
+pub mod buffers;
+pub mod clipboard;
+pub mod command;
 pub mod editor;
+pub mod ex;
+pub mod key;
+pub mod keymap;
+pub mod macros;
+pub mod mapping;
+pub mod marks;
+pub mod motion;
+pub mod operator;
+pub mod options;
+pub mod register;
+pub mod repeat;
+pub mod search;
+pub mod substitute;
+pub mod tabs;
+pub mod textobject;
 pub mod ui;
+pub mod undo;
+pub mod visual;
+pub mod window;
 
 #[derive(Debug, Clone)]
 pub struct IO {
@@ -36,6 +59,18 @@ fn main() {
         }
     }));
 
+    let arguments = match args::parse_arguments(std::env::args().skip(1)) {
+        Ok(arguments) if arguments.help => {
+            println!("{}", args::USAGE);
+            return;
+        }
+        Ok(arguments) => arguments,
+        Err(error) => {
+            eprintln!("uu: {}\n{}", error, args::USAGE);
+            std::process::exit(1);
+        }
+    };
+
     let el = EventLoop::new();
 
     let (renderer, windowed_context) = {
@@ -56,15 +91,31 @@ fn main() {
         (renderer, windowed_context)
     };
 
+    let mut editor = editor::Editor::new();
+    if let Err(error) = editor.load_config() {
+        editor.status_message = error;
+    }
+
     let canvas = Canvas::new(renderer).expect("Cannot create canvas");
-    let mut ui = ui::UI::new(canvas);
+    let mut ui = ui::UI::new(canvas, editor.option("fontsize").number() as f32);
 
     let start = Instant::now();
     let mut prevt = start;
 
     let mut io: IO = Default::default();
-    let mut editor = editor::Editor::new();
-    editor.buffer = buffer::Buffer::from(include_str!("main.rs"));
+    let mut modifiers = Modifiers::default();
+    #[cfg(target_os = "linux")]
+    let wayland_display = {
+        use glutin::platform::unix::WindowExtUnix;
+        windowed_context.window().wayland_display()
+    };
+    #[cfg(not(target_os = "linux"))]
+    let wayland_display = None;
+    // without a clipboard to talk to "+ and "* only work inside uu
+    if let Some(clipboard) = clipboard::SystemClipboard::new(wayland_display) {
+        editor.clipboard = Box::new(clipboard);
+    }
+    editor.start(arguments);
 
     el.run(move |event, _, control_flow| {
         let window = windowed_context.window();
@@ -77,17 +128,42 @@ fn main() {
                 WindowEvent::Resized(physical_size) => {
                     windowed_context.resize(*physical_size);
                 }
-                WindowEvent::ReceivedCharacter(mut c) => {
-                    if c == '\r' {
-                        c = '\n';
+                WindowEvent::ModifiersChanged(state) => {
+                    modifiers = Modifiers {
+                        ctrl: state.ctrl(),
+                        alt: state.alt(),
+                        shift: state.shift(),
+                    };
+                }
+                // keys that don't type a character only come as keyboard input
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(keycode),
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Some(code) = special_key(*keycode) {
+                        let info = display_information(&ui, &io);
+                        editor.handle_input(Key::new(code, modifiers), &info);
                     }
-
-                    let cheight = ui.character_height().ceil() as u32;
-                    let window_height_in_characters = (io.window_dimensions[1] / cheight) as usize;
-                    let info = DisplayInformation {
-                        window_height_in_characters,
+                }
+                WindowEvent::ReceivedCharacter(c) => {
+                    // delete comes as keyboard input too
+                    if *c == '\x7f' {
+                        return;
+                    }
+                    let key = Key::from_char(*c);
+                    let modifiers = Modifiers {
+                        alt: modifiers.alt,
+                        shift: modifiers.shift,
+                        ..key.modifiers
                     };
-                    editor.handle_input(&c.to_string(), true, &info);
+
+                    let info = display_information(&ui, &io);
+                    editor.handle_input(Key::new(key.code, modifiers), &info);
                 }
                 WindowEvent::CursorMoved {
                     device_id: _,
@@ -105,18 +181,61 @@ fn main() {
                 let dt = now - prevt;
                 prevt = now;
 
-                editor.fade_matching_input(dt);
+                editor.count_down_timeout(dt);
 
                 io.dpi_factor = window.scale_factor();
                 let size = window.inner_size();
                 io.window_dimensions = [size.width, size.height];
 
-                ui.run(&mut io, &editor);
+                let info = display_information(&ui, &io);
+                ui.run(&mut io, &editor, &info);
 
                 windowed_context.swap_buffers().unwrap();
             }
             Event::MainEventsCleared => window.request_redraw(),
             _ => (),
         }
+        // a key, a mapping or a timed out binding may have quit
+        if editor.quit {
+            *control_flow = ControlFlow::Exit;
+        }
     });
 }
+
+fn display_information(ui: &ui::UI, io: &IO) -> DisplayInformation {
+    let cheight = ui.character_height().ceil() as u32;
+    let cwidth = ui.character_width().ceil() as u32;
+    DisplayInformation {
+        window_height_in_characters: (io.window_dimensions[1] / cheight) as usize,
+        window_width_in_characters: (io.window_dimensions[0] / cwidth) as usize,
+    }
+}
+
+fn special_key(keycode: VirtualKeyCode) -> Option<KeyCode> {
+    use VirtualKeyCode::*;
+    Some(match keycode {
+        Up => KeyCode::Up,
+        Down => KeyCode::Down,
+        Left => KeyCode::Left,
+        Right => KeyCode::Right,
+        Home => KeyCode::Home,
+        End => KeyCode::End,
+        PageUp => KeyCode::PageUp,
+        PageDown => KeyCode::PageDown,
+        Insert => KeyCode::Insert,
+        Delete => KeyCode::Delete,
+        F1 => KeyCode::F(1),
+        F2 => KeyCode::F(2),
+        F3 => KeyCode::F(3),
+        F4 => KeyCode::F(4),
+        F5 => KeyCode::F(5),
+        F6 => KeyCode::F(6),
+        F7 => KeyCode::F(7),
+        F8 => KeyCode::F(8),
+        F9 => KeyCode::F(9),
+        F10 => KeyCode::F(10),
+        F11 => KeyCode::F(11),
+        F12 => KeyCode::F(12),
+        _ => return None,
+    })
+}
@@ -0,0 +1,325 @@
+use crate::command::{split_register, Parse};
+use crate::editor::{Editor, Mode};
+use crate::key::{parse_notation, Key};
+use crate::keymap::Keymap;
+
+// like vim's maxmapdepth: a mapping that keeps expanding into itself without a key getting
+// through is given up on
+const MAX_MAPPING_DEPTH: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    pub keys: Vec<Key>,
+    // the keys aren't mapped again
+    pub noremap: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapMode {
+    Normal,
+    Visual,
+    Insert,
+}
+
+// what :map and friends added, on top of the bindings in editor.rs
+#[derive(Debug, Clone, Default)]
+pub struct Mappings {
+    normal: Keymap<Mapping>,
+    visual: Keymap<Mapping>,
+    insert: Keymap<Mapping>,
+}
+
+impl Mappings {
+    pub fn keymap(&self, mode: MapMode) -> &Keymap<Mapping> {
+        match mode {
+            MapMode::Normal => &self.normal,
+            MapMode::Visual => &self.visual,
+            MapMode::Insert => &self.insert,
+        }
+    }
+
+    pub fn keymap_mut(&mut self, mode: MapMode) -> &mut Keymap<Mapping> {
+        match mode {
+            MapMode::Normal => &mut self.normal,
+            MapMode::Visual => &mut self.visual,
+            MapMode::Insert => &mut self.insert,
+        }
+    }
+}
+
+impl Editor {
+    // a mapping at the start of the pending keys, after the count and register typed before it.
+    // also how many keys those were
+    pub(crate) fn find_mapping(&self) -> (usize, Parse<Mapping>) {
+        let mode = match self.mode {
+            _ if self.noremap => return (0, Parse::Invalid),
+            Mode::NORMAL => MapMode::Normal,
+            Mode::VISUAL(_) => MapMode::Visual,
+            Mode::INSERT => MapMode::Insert,
+            _ => return (0, Parse::Invalid),
+        };
+        let prefix = match mode {
+            MapMode::Insert => 0,
+            _ => {
+                let input = self.pending_input();
+                match split_register(&input) {
+                    Some((_, _, rest)) => input[..input.len() - rest.len()].chars().count(),
+                    None => return (0, Parse::Invalid),
+                }
+            }
+        };
+        let keys = &self.pending_keys[prefix..];
+        if keys.is_empty() {
+            return (prefix, Parse::Invalid);
+        }
+        (prefix, self.mappings.keymap(mode).lookup(keys))
+    }
+
+    // the keys of a mapping take the place of its own. a count or register before it stays
+    pub(crate) fn expand_mapping(&mut self, prefix: usize, mapping: Mapping) -> Result<(), String> {
+        self.mapping_depth += 1;
+        if self.mapping_depth > MAX_MAPPING_DEPTH {
+            self.clear_pending_keys();
+            return Err("E223: Recursive mapping".into());
+        }
+        self.pending_keys.truncate(prefix);
+        if mapping.noremap {
+            return self.without_mappings(|editor| editor.run_keys(mapping.keys));
+        }
+        for key in mapping.keys.into_iter().rev() {
+            self.typeahead.push_front(key);
+        }
+        Ok(())
+    }
+
+    // for noremap, :normal! and .
+    pub(crate) fn without_mappings<T>(&mut self, f: impl FnOnce(&mut Editor) -> T) -> T {
+        let noremap = std::mem::replace(&mut self.noremap, true);
+        let result = f(self);
+        self.noremap = noremap;
+        result
+    }
+
+    // :map {lhs} {rhs} and the rest of the family. without {rhs} they list what's mapped
+    pub(crate) fn map_command(
+        &mut self,
+        command: &str,
+        argument: &str,
+        force: bool,
+    ) -> Result<(), String> {
+        let modes = map_modes(command, force);
+        let (lhs, rhs) = split_mapping(argument);
+        if rhs.is_empty() {
+            return self.list_mappings(modes, &self.with_leader(lhs));
+        }
+        let mapping = Mapping {
+            keys: self.with_leader(rhs),
+            noremap: command.contains("nore"),
+        };
+        let lhs = self.with_leader(lhs);
+        for &mode in modes {
+            self.mappings.keymap_mut(mode).insert(&lhs, mapping.clone());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn unmap_command(
+        &mut self,
+        command: &str,
+        argument: &str,
+        force: bool,
+    ) -> Result<(), String> {
+        if argument.is_empty() {
+            return Err("E474: Invalid argument".into());
+        }
+        let lhs = self.with_leader(argument);
+        let mut found = false;
+        for &mode in map_modes(command, force) {
+            found |= self.mappings.keymap_mut(mode).remove(&lhs).is_some();
+        }
+        if found {
+            Ok(())
+        } else {
+            Err("E31: No such mapping".into())
+        }
+    }
+
+    // the mappings starting with lhs, one after the other on the message line
+    fn list_mappings(&mut self, modes: &[MapMode], lhs: &[Key]) -> Result<(), String> {
+        let mut listed = vec![];
+        for &mode in modes {
+            let letter = match mode {
+                MapMode::Normal => 'n',
+                MapMode::Visual => 'v',
+                MapMode::Insert => 'i',
+            };
+            let mut bindings = self.mappings.keymap(mode).bindings();
+            bindings.retain(|(keys, _)| keys.starts_with(lhs));
+            bindings.sort_by_key(|(keys, _)| notation(keys));
+            for (keys, mapping) in bindings {
+                let star = if mapping.noremap { "*" } else { "" };
+                let (keys, rhs) = (notation(&keys), notation(&mapping.keys));
+                listed.push(format!("{} {} {}{}", letter, keys, star, rhs));
+            }
+        }
+        if listed.is_empty() {
+            return Err("No mapping found".into());
+        }
+        self.status_message = listed.join("  |  ");
+        Ok(())
+    }
+
+    // <leader> is whatever mapleader was when the mapping was made, like in vim
+    fn with_leader(&self, keys: &str) -> Vec<Key> {
+        let mut expanded = String::new();
+        let mut rest = keys;
+        while let Some(start) = rest.to_ascii_lowercase().find("<leader>") {
+            expanded.push_str(&rest[..start]);
+            expanded.push_str(&self.mapleader);
+            rest = &rest[start + "<leader>".len()..];
+        }
+        expanded.push_str(rest);
+        parse_notation(&expanded)
+    }
+
+    // :let mapleader = "x", the only variable there is
+    pub(crate) fn let_command(&mut self, argument: &str) -> Result<(), String> {
+        let (name, value) = match argument.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => return Err(format!("E15: Invalid expression: {}", argument)),
+        };
+        if name != "mapleader" {
+            return Err(format!("E121: Undefined variable: {}", name));
+        }
+        let quoted = (value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\''));
+        if value.len() < 2 || !quoted {
+            return Err(format!("E15: Invalid expression: {}", value));
+        }
+        self.mapleader = value[1..value.len() - 1].to_string();
+        Ok(())
+    }
+
+    // runs the ex commands in a file, one per line. lines starting with " are comments. an error
+    // doesn't stop the lines after it, the first one is reported
+    pub(crate) fn source(&mut self, path: &str) -> Result<(), String> {
+        if path.is_empty() {
+            return Err("E471: Argument required".into());
+        }
+        let text =
+            std::fs::read_to_string(path).map_err(|_| format!("E484: Can't open file {}", path))?;
+        let mut first_error = None;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('"') {
+                continue;
+            }
+            if let Err(error) = self.run_command(line) {
+                first_error.get_or_insert(format!("{} line {}: {}", path, index + 1, error));
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+// map and noremap work in normal and visual mode, map! and noremap! in insert mode
+fn map_modes(command: &str, force: bool) -> &'static [MapMode] {
+    let prefix = command
+        .trim_end_matches("map")
+        .trim_end_matches("nore")
+        .trim_end_matches("un");
+    match (prefix, force) {
+        ("n", _) => &[MapMode::Normal],
+        ("v", _) => &[MapMode::Visual],
+        ("i", _) | (_, true) => &[MapMode::Insert],
+        _ => &[MapMode::Normal, MapMode::Visual],
+    }
+}
+
+// {lhs} ends at the first blank, {rhs} is everything after the blanks that follow it
+fn split_mapping(argument: &str) -> (&str, &str) {
+    match argument.find(char::is_whitespace) {
+        Some(end) => (&argument[..end], argument[end..].trim_start()),
+        None => (argument, ""),
+    }
+}
+
+// a space on its own wouldn't be seen
+fn notation(keys: &[Key]) -> String {
+    keys.iter()
+        .map(|key| match key.to_string().as_str() {
+            " " => "<Space>".to_string(),
+            other => other.to_string(),
+        })
+        .collect()
+}
+
+mod test {
+    #[test]
+    fn should_expand_mappings() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("one\ntwo\nthree\nfour");
+
+        editor.run_command("inoremap jk <Esc>").unwrap();
+        editor.type_keys("ixjk");
+        assert_eq!(crate::editor::Mode::NORMAL, editor.mode);
+        assert_eq!("xone\n", &editor.buffer.as_string()[..5]);
+        // a j that isn't followed by k is typed once the next key comes or the timeout runs out
+        editor.type_keys("0ajx<Esc>");
+        assert_eq!("xjxone\n", &editor.buffer.as_string()[..7]);
+        editor.type_keys("0ij");
+        assert_eq!("xjxone\n", &editor.buffer.as_string()[..7]);
+        editor.count_down_timeout(std::time::Duration::from_secs(2));
+        assert_eq!("jxjxone\n", &editor.buffer.as_string()[..8]);
+        editor.type_keys("<Esc>");
+
+        // counts and registers go in front of the keys the mapping is for
+        editor.run_command("let mapleader = ','").unwrap();
+        editor.run_command("nmap <Leader>d dd").unwrap();
+        editor.run_command("nnoremap dd dl").unwrap();
+        editor.type_keys("gg0,d");
+        assert_eq!("xjxone\ntwo\nthree\nfour", editor.buffer.as_string());
+        editor.run_command("nunmap dd").unwrap();
+        editor.type_keys("2,d");
+        assert_eq!("three\nfour", editor.buffer.as_string());
+        editor.run_command("nmap ,e \"a,d").unwrap();
+        editor.type_keys(",e");
+        assert_eq!("three\n", editor.register('a').unwrap().text);
+
+        // recursive mappings stop instead of going on forever
+        editor.run_command("map q 0q").unwrap();
+        editor.type_keys("q");
+        assert_eq!("E223: Recursive mapping", editor.status_message);
+        assert_eq!(
+            Err("E31: No such mapping".into()),
+            editor.run_command("iunmap q")
+        );
+        editor.run_command("unmap q").unwrap();
+        editor.run_command("map").unwrap();
+        assert_eq!("n ,d dd  |  n ,e \"a,d", editor.status_message);
+    }
+
+    #[test]
+    fn should_source_files_of_commands() {
+        let path = std::env::temp_dir().join(format!("uu-source-test-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "\" comment\nlet mapleader = \" \"\nnnoremap <leader>x dd\nfrobnicate\nimap jj <Esc>\n",
+        )
+        .unwrap();
+        let mut editor = crate::editor::Editor::new();
+        let path = path.to_str().unwrap();
+        assert_eq!(
+            Err(format!(
+                "{} line 4: E492: Not an editor command: frobnicate",
+                path
+            )),
+            editor.run_command(&format!("source {}", path))
+        );
+        editor.run_command("map").unwrap();
+        assert_eq!("n <Space>x *dd", editor.status_message);
+        editor.run_command("imap").unwrap();
+        assert_eq!("i jj <Esc>", editor.status_message);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+use crate::editor::{Editor, Mode};
+use crate::visual::VisualKind;
+use crate::Instant;
+
+impl Editor {
+    // m{mark}. uppercase marks also remember their file so they can be jumped to from another
+    pub(crate) fn set_mark(&mut self, name: char) {
+        let (x, y) = self.buffer.cursor();
+        self.buffer.set_mark(name, self.buffer.offset_of(x, y));
+        if name.is_ascii_uppercase() {
+            self.file_marks
+                .insert(name, (self.editing_file_path.clone(), (x, y)));
+        }
+    }
+
+    // the buffer only keeps the uppercase marks of its own file, the others wait here for their
+    // file to be opened again
+    pub(crate) fn save_file_marks(&mut self) {
+        let buffer = &self.buffer;
+        let path = &self.editing_file_path;
+        self.file_marks.retain(|&name, (mark_path, position)| {
+            if mark_path != path {
+                return true;
+            }
+            match buffer.mark(name) {
+                Some(offset) => {
+                    *position = buffer.position_of(offset);
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+
+    pub(crate) fn restore_file_marks(&mut self) {
+        for (&name, (path, (x, y))) in &self.file_marks {
+            if *path == self.editing_file_path {
+                let y = (*y).min(self.buffer.line_count() - 1);
+                self.buffer.set_mark(name, self.buffer.offset_of(*x, y));
+            }
+        }
+    }
+
+    // E20 for marks that aren't set. going to an uppercase mark in another file opens it
+    pub(crate) fn find_mark(&mut self, name: char) -> Result<(), String> {
+        if let Some((path, _)) = self.file_marks.get(&name) {
+            if *path != self.editing_file_path {
+                let path = path.clone();
+                self.edit_file(&path)?;
+            }
+        }
+        match self.buffer.mark(name) {
+            Some(_) => Ok(()),
+            None => Err("E20: Mark not set".into()),
+        }
+    }
+
+    // '< and '>, a selection of lines covers them from start to end
+    pub(crate) fn set_visual_marks(&mut self, kind: VisualKind) {
+        let (anchor_x, anchor_y) = self.visual_anchor;
+        let (x, y) = self.buffer.cursor();
+        let (start, end) = if (anchor_y, anchor_x) <= (y, x) {
+            ((anchor_x, anchor_y), (x, y))
+        } else {
+            ((x, y), (anchor_x, anchor_y))
+        };
+        let (start, end) = match kind {
+            VisualKind::Lines => (self.buffer.line_start(start.1), self.buffer.line_end(end.1)),
+            _ => (
+                self.buffer.offset_of(start.0, start.1),
+                self.buffer.offset_of(end.0, end.1),
+            ),
+        };
+        self.buffer.set_mark('<', start);
+        self.buffer.set_mark('>', end);
+    }
+
+    pub(crate) fn push_jump(&mut self) {
+        let (x, y) = self.buffer.cursor();
+        self.buffer.push_jump(self.buffer.offset_of(x, y));
+    }
+
+    // Ctrl-O
+    pub fn jump_older(&mut self) -> Result<(), String> {
+        let (x, y) = self.buffer.cursor();
+        let from = self.buffer.offset_of(x, y);
+        let offset = self.buffer.older_jump(from).ok_or_else(String::new)?;
+        self.go_to_offset(offset);
+        Ok(())
+    }
+
+    // Ctrl-I
+    pub fn jump_newer(&mut self) -> Result<(), String> {
+        let offset = self.buffer.newer_jump().ok_or_else(String::new)?;
+        self.go_to_offset(offset);
+        Ok(())
+    }
+
+    fn go_to_offset(&mut self, offset: usize) {
+        let (x, y) = self.buffer.position_of(offset);
+        self.buffer.set_cursor(x, y, Mode::NORMAL);
+        self.scroll_to_cursor();
+        self.cursor_animation_instant = Instant::now();
+    }
+}
+
+mod test {
+    #[test]
+    fn should_keep_marks_on_their_text() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("one\n  two\nthree four\nfive");
+
+        editor.type_keys("jmaj$mbgg");
+        editor.type_keys("ix\ny\n\x1b'a");
+        assert_eq!((2, 3), editor.buffer.cursor());
+        editor.type_keys("`b");
+        assert_eq!((9, 4), editor.buffer.cursor());
+        editor.type_keys("k0d'a");
+        assert_eq!("x\ny\none\nthree four\nfive", editor.buffer.as_string());
+        editor.type_keys("`a");
+        assert_eq!("E20: Mark not set", editor.status_message);
+        editor.type_keys("gg`.");
+        assert_eq!((0, 3), editor.buffer.cursor());
+
+        editor.type_keys("4Gwyegg`]");
+        assert_eq!((9, 3), editor.buffer.cursor());
+        editor.type_keys("`[");
+        assert_eq!((6, 3), editor.buffer.cursor());
+        editor.type_keys("ggVj\x1b:'<,'>d\n");
+        assert_eq!("one\nthree four\nfive", editor.buffer.as_string());
+
+        // uppercase marks bring their file back
+        let directory = std::env::temp_dir().join(format!("uu-marks-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let first = directory.join("first.txt");
+        let second = directory.join("second.txt");
+        std::fs::write(&first, "1\n2\n3\n").unwrap();
+        std::fs::write(&second, "4\n").unwrap();
+        editor.open_file(first.to_str().unwrap()).unwrap();
+        editor.type_keys("jmAggix\x1b");
+        editor.type_keys(&format!(":w\n:e {}\n", second.to_str().unwrap()));
+        assert_eq!("4\n", editor.buffer.as_string());
+        editor.type_keys("'A");
+        assert_eq!(first.to_str().unwrap(), editor.editing_file_path);
+        assert_eq!((0, 1), editor.buffer.cursor());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn should_go_back_and_forth_through_jumps() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("a\nb\nc\nd\ne\nf");
+
+        editor.type_keys("jG/c\n");
+        assert_eq!((0, 2), editor.buffer.cursor());
+        editor.type_keys("''");
+        assert_eq!((0, 5), editor.buffer.cursor());
+        editor.type_keys("``");
+        assert_eq!((0, 2), editor.buffer.cursor());
+        editor.type_keys("\x0f");
+        assert_eq!((0, 5), editor.buffer.cursor());
+        editor.type_keys("\x0f\x0f");
+        assert_eq!((0, 1), editor.buffer.cursor());
+        editor.type_keys("\x0f");
+        assert_eq!((0, 1), editor.buffer.cursor());
+        editor.type_keys("\t");
+        assert_eq!((0, 5), editor.buffer.cursor());
+        editor.type_keys("\t\t");
+        assert_eq!((0, 2), editor.buffer.cursor());
+
+        // jumps stay with their lines too
+        editor.type_keys("ggdd\x0f");
+        assert_eq!("b\nc\nd\ne\nf", editor.buffer.as_string());
+        assert_eq!((0, 1), editor.buffer.cursor());
+    }
+}
@@ -0,0 +1,780 @@
+use crate::buffer::Buffer;
+use crate::{min, UnicodeSegmentation};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordStart,
+    WordBackward,
+    WordEnd,
+    WordEndBackward,
+    BigWordStart,
+    BigWordBackward,
+    BigWordEnd,
+    BigWordEndBackward,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FirstLine,
+    LastLine,
+    ParagraphForward,
+    ParagraphBackward,
+    SentenceForward,
+    SentenceBackward,
+    WindowTop,
+    WindowMiddle,
+    WindowBottom,
+    MatchingBracket,
+    // N%
+    Percent,
+    // f F t T, the editor turns ; and , into these using the last search
+    FindChar(CharSearch),
+    RepeatFind,
+    RepeatFindReverse,
+    // 'x goes to the line of the mark, `x to the mark itself
+    Mark { name: char, exact: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CharSearch {
+    pub target: char,
+    pub forward: bool,
+    // t and T stop one grapheme before the match
+    pub till: bool,
+    // repeating t or T with ; or , doesn't get stuck on the match right next to the cursor
+    pub repeat: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MotionKind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+impl Motion {
+    pub fn kind(self) -> MotionKind {
+        match self {
+            Motion::Up
+            | Motion::Down
+            | Motion::FirstLine
+            | Motion::LastLine
+            | Motion::WindowTop
+            | Motion::WindowMiddle
+            | Motion::WindowBottom
+            | Motion::Percent
+            | Motion::Mark { exact: false, .. } => MotionKind::Linewise,
+            Motion::WordEnd
+            | Motion::WordEndBackward
+            | Motion::BigWordEnd
+            | Motion::BigWordEndBackward
+            | Motion::LineEnd
+            | Motion::MatchingBracket => MotionKind::Inclusive,
+            Motion::FindChar(search) if search.forward => MotionKind::Inclusive,
+            _ => MotionKind::Exclusive,
+        }
+    }
+
+    // the motions that leave where they started in the jump list
+    pub fn is_jump(self) -> bool {
+        matches!(
+            self,
+            Motion::FirstLine
+                | Motion::LastLine
+                | Motion::ParagraphForward
+                | Motion::ParagraphBackward
+                | Motion::SentenceForward
+                | Motion::SentenceBackward
+                | Motion::WindowTop
+                | Motion::WindowMiddle
+                | Motion::WindowBottom
+                | Motion::MatchingBracket
+                | Motion::Percent
+                | Motion::Mark { .. }
+        )
+    }
+}
+
+impl Buffer {
+    // where the motion lands when started from the cursor, None when it can't move at all.
+    // horizontal targets may be one past the last character of a line, which only operators use.
+    // visible is the range of lines on screen, for H, M and L
+    pub fn motion_target(
+        &self,
+        motion: Motion,
+        count: Option<usize>,
+        visible: Range<usize>,
+    ) -> Option<(usize, usize)> {
+        let (x, y) = self.cursor();
+        let last_line = self.line_count() - 1;
+        let n = count.unwrap_or(1).max(1);
+        let target = match motion {
+            Motion::Left => {
+                if x == 0 {
+                    return None;
+                }
+                (x.saturating_sub(n), y)
+            }
+            Motion::Right => {
+                let length = self.line_length(y);
+                if x >= length {
+                    return None;
+                }
+                (min(x.saturating_add(n), length), y)
+            }
+            Motion::Up | Motion::Down => {
+                let target_y = if motion == Motion::Up {
+                    y.checked_sub(1)?.saturating_sub(n - 1)
+                } else if y < last_line {
+                    min(y.saturating_add(n), last_line)
+                } else {
+                    return None;
+                };
+                let length = self.line_length(target_y);
+                (
+                    min(self.preferred_column(), length.saturating_sub(1)),
+                    target_y,
+                )
+            }
+            Motion::WordStart
+            | Motion::WordBackward
+            | Motion::WordEnd
+            | Motion::WordEndBackward
+            | Motion::BigWordStart
+            | Motion::BigWordBackward
+            | Motion::BigWordEnd
+            | Motion::BigWordEndBackward => {
+                let big = matches!(
+                    motion,
+                    Motion::BigWordStart
+                        | Motion::BigWordBackward
+                        | Motion::BigWordEnd
+                        | Motion::BigWordEndBackward
+                );
+                let mut walker = Walker::new(self, x, y);
+                walker.big = big;
+                let mut moved = false;
+                for _ in 0..n {
+                    let step = match motion {
+                        Motion::WordStart | Motion::BigWordStart => walker.word_start_forward(),
+                        Motion::WordBackward | Motion::BigWordBackward => {
+                            walker.word_start_backward()
+                        }
+                        Motion::WordEnd | Motion::BigWordEnd => walker.word_end_forward(),
+                        _ => walker.word_end_backward(),
+                    };
+                    if !step {
+                        break;
+                    }
+                    moved = true;
+                }
+                if !moved {
+                    return None;
+                }
+                walker.position()
+            }
+            Motion::LineStart => (0, y),
+            Motion::FirstNonBlank => (self.first_non_blank(y), y),
+            Motion::LineEnd => {
+                let target_y = min(y.saturating_add(n - 1), last_line);
+                (self.line_length(target_y).saturating_sub(1), target_y)
+            }
+            Motion::FirstLine | Motion::LastLine => {
+                let target_y = match count {
+                    Some(line) => min(line.max(1) - 1, last_line),
+                    None if motion == Motion::FirstLine => 0,
+                    None => last_line,
+                };
+                (self.first_non_blank(target_y), target_y)
+            }
+            Motion::WindowTop | Motion::WindowMiddle | Motion::WindowBottom => {
+                let first = min(visible.start, last_line);
+                let last = min(visible.end.max(first + 1) - 1, last_line);
+                let target_y = match motion {
+                    Motion::WindowTop => min(first.saturating_add(n - 1), last),
+                    Motion::WindowBottom => last.saturating_sub(n - 1).max(first),
+                    _ => first + (last - first) / 2,
+                };
+                (self.first_non_blank(target_y), target_y)
+            }
+            Motion::ParagraphForward | Motion::ParagraphBackward => {
+                let mut target_y = y;
+                for _ in 0..n {
+                    target_y = if motion == Motion::ParagraphForward {
+                        self.paragraph_forward(target_y)
+                    } else {
+                        self.paragraph_backward(target_y)
+                    };
+                }
+                if motion == Motion::ParagraphForward && target_y == last_line {
+                    if self.line_length(last_line) == 0 {
+                        (0, last_line)
+                    } else {
+                        (self.line_length(last_line), last_line)
+                    }
+                } else {
+                    (0, target_y)
+                }
+            }
+            Motion::SentenceForward | Motion::SentenceBackward => {
+                let mut offset = self.offset_of(x, y);
+                for _ in 0..n {
+                    offset = if motion == Motion::SentenceForward {
+                        self.sentence_forward(offset)
+                    } else {
+                        self.sentence_backward(offset)
+                    };
+                }
+                self.position_of(offset)
+            }
+            Motion::MatchingBracket => self.matching_bracket(x, y)?,
+            Motion::Percent => {
+                let percent = count.unwrap_or(100);
+                if percent > 100 {
+                    return None;
+                }
+                let target_y = (percent * self.line_count()).div_ceil(100);
+                let target_y = min(target_y.max(1) - 1, last_line);
+                (self.first_non_blank(target_y), target_y)
+            }
+            Motion::FindChar(search) => (self.find_char(x, y, search, n)?, y),
+            Motion::RepeatFind | Motion::RepeatFindReverse => return None,
+            Motion::Mark { name, exact } => {
+                let (mark_x, mark_y) = self.position_of(self.mark(name)?);
+                if exact {
+                    (mark_x, mark_y)
+                } else {
+                    (self.first_non_blank(mark_y), mark_y)
+                }
+            }
+        };
+        if target == (x, y) && !matches!(motion.kind(), MotionKind::Linewise) {
+            // motions that can't make progress fail, except the ones that are allowed to stay
+            // put like 0, ^ and $
+            if !matches!(
+                motion,
+                Motion::LineStart | Motion::FirstNonBlank | Motion::LineEnd | Motion::Mark { .. }
+            ) {
+                return None;
+            }
+        }
+        Some(target)
+    }
+
+    // the empty line after the current paragraph, or the last line
+    fn paragraph_forward(&self, y: usize) -> usize {
+        let last_line = self.line_count() - 1;
+        let mut y = y;
+        while y < last_line && self.line_length(y) == 0 {
+            y += 1;
+        }
+        while y < last_line && self.line_length(y) != 0 {
+            y += 1;
+        }
+        y
+    }
+
+    fn paragraph_backward(&self, y: usize) -> usize {
+        let mut y = y;
+        while y > 0 && self.line_length(y) == 0 {
+            y -= 1;
+        }
+        while y > 0 && self.line_length(y) != 0 {
+            y -= 1;
+        }
+        y
+    }
+
+    // lines of the paragraph holding y, or None on an empty line, which is a boundary of its own
+    pub(crate) fn paragraph_around(&self, y: usize) -> Option<(usize, usize)> {
+        if self.line_length(y) == 0 {
+            return None;
+        }
+        let mut first = y;
+        while first > 0 && self.line_length(first - 1) != 0 {
+            first -= 1;
+        }
+        let mut last = y;
+        while last + 1 < self.line_count() && self.line_length(last + 1) != 0 {
+            last += 1;
+        }
+        Some((first, last))
+    }
+
+    fn sentence_forward(&self, offset: usize) -> usize {
+        let y = self.line_of_offset(offset);
+        match self.paragraph_around(y) {
+            Some((first, last)) => {
+                let start = self.line_start(first);
+                let text = self.slice(start..self.line_end(last));
+                let next = sentence_starts(&text)
+                    .into_iter()
+                    .map(|s| start + s)
+                    .find(|&s| s > offset);
+                match next {
+                    Some(next) => next,
+                    None if last + 1 < self.line_count() => self.line_start(last + 1),
+                    None => self.len(),
+                }
+            }
+            None if y + 1 < self.line_count() => {
+                let next = self.line_start(y + 1);
+                next + byte_offset_of_grapheme(&self.line(y + 1), self.first_non_blank(y + 1))
+            }
+            None => offset,
+        }
+    }
+
+    fn sentence_backward(&self, offset: usize) -> usize {
+        let y = self.line_of_offset(offset);
+        if let Some((first, last)) = self.paragraph_around(y) {
+            let start = self.line_start(first);
+            let text = self.slice(start..self.line_end(last));
+            let previous = sentence_starts(&text)
+                .into_iter()
+                .map(|s| start + s)
+                .rfind(|&s| s < offset);
+            if let Some(previous) = previous {
+                return previous;
+            }
+            return if first > 0 {
+                self.line_start(first - 1)
+            } else {
+                0
+            };
+        }
+        if y == 0 {
+            return offset;
+        }
+        match self.paragraph_around(y - 1) {
+            Some((first, last)) => {
+                let start = self.line_start(first);
+                let text = self.slice(start..self.line_end(last));
+                start + sentence_starts(&text).last().copied().unwrap_or(0)
+            }
+            None => self.line_start(y - 1),
+        }
+    }
+
+    // the column of the count-th grapheme in the line starting with the searched character, so
+    // a letter also finds itself carrying combining accents
+    fn find_char(&self, x: usize, y: usize, search: CharSearch, count: usize) -> Option<usize> {
+        let line = graphemes_of(self, y);
+        let skip = if search.till && search.repeat { 2 } else { 1 };
+        let matches = |i: &usize| line[*i].starts_with(search.target);
+        let found = if search.forward {
+            (x.saturating_add(skip)..line.len())
+                .filter(matches)
+                .nth(count - 1)?
+        } else {
+            (0..x.checked_sub(skip - 1)?)
+                .rev()
+                .filter(matches)
+                .nth(count - 1)?
+        };
+        Some(match (search.till, search.forward) {
+            (false, _) => found,
+            (true, true) => found - 1,
+            (true, false) => found + 1,
+        })
+    }
+
+    // % jumps from the bracket under or after the cursor on this line to its partner
+    fn matching_bracket(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        const PAIRS: [(&str, &str); 3] = [("(", ")"), ("[", "]"), ("{", "}")];
+        let mut walker = Walker::new(self, x, y);
+        let (open, close, forward) = loop {
+            let grapheme = walker.get()?;
+            if let Some(&(open, close)) =
+                PAIRS.iter().find(|(o, c)| grapheme == *o || grapheme == *c)
+            {
+                break (open, close, grapheme == open);
+            }
+            if grapheme == "\n" || !walker.next() {
+                return None;
+            }
+        };
+        let mut depth = 0usize;
+        loop {
+            let grapheme = walker.get().unwrap_or("");
+            if grapheme == open {
+                depth = if forward { depth + 1 } else { depth - 1 };
+            } else if grapheme == close {
+                depth = if forward { depth - 1 } else { depth + 1 };
+            }
+            if depth == 0 {
+                return Some(walker.position());
+            }
+            let moved = if forward {
+                walker.next()
+            } else {
+                walker.prev()
+            };
+            if !moved {
+                return None;
+            }
+        }
+    }
+}
+
+// where sentences start in a paragraph: after . ! or ? followed by closing brackets or quotes
+// and then whitespace
+pub(crate) fn sentence_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![text.len() - text.trim_start().len()];
+    let mut ended = false;
+    let mut in_gap = false;
+    for (i, c) in text.char_indices() {
+        if in_gap {
+            if !c.is_whitespace() {
+                starts.push(i);
+                in_gap = false;
+                ended = false;
+            }
+        } else if ended {
+            if c.is_whitespace() {
+                in_gap = true;
+            } else if !matches!(c, ')' | ']' | '"' | '\'' | '.' | '!' | '?') {
+                ended = false;
+            }
+        } else if matches!(c, '.' | '!' | '?') {
+            ended = true;
+        }
+    }
+    starts
+}
+
+fn byte_offset_of_grapheme(line: &str, x: usize) -> usize {
+    UnicodeSegmentation::grapheme_indices(line, true)
+        .nth(x)
+        .map(|(i, _)| i)
+        .unwrap_or_else(|| line.len())
+}
+
+// words are runs of graphemes of the same class. letters, digits and _ of any script are one
+// class, except for scripts written without spaces which get a class each so that w still
+// stops when the script changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Class {
+    Blank,
+    Punctuation,
+    Word,
+    Emoji,
+    Ideograph,
+    Hiragana,
+    Katakana,
+    Hangul,
+}
+
+pub(crate) fn class_of(grapheme: &str, big: bool) -> Class {
+    let c = match grapheme.chars().next() {
+        None => return Class::Blank,
+        Some(c) => c,
+    };
+    if c.is_whitespace() {
+        return Class::Blank;
+    }
+    if big {
+        return Class::Word;
+    }
+    match c as u32 {
+        0x3040..=0x309f => Class::Hiragana,
+        0x30a0..=0x30ff | 0x31f0..=0x31ff | 0xff66..=0xff9f => Class::Katakana,
+        0x1100..=0x11ff | 0x3130..=0x318f | 0xac00..=0xd7af => Class::Hangul,
+        0x3400..=0x4dbf | 0x4e00..=0x9fff | 0xf900..=0xfaff | 0x20000..=0x2fa1f => Class::Ideograph,
+        0x1f000..=0x1faff | 0x2600..=0x27bf => Class::Emoji,
+        0x3000..=0x303f | 0xff00..=0xff65 => Class::Punctuation,
+        _ if c.is_alphanumeric() || c == '_' => Class::Word,
+        // combining marks and the like stick to whatever they were written after
+        _ if grapheme.chars().any(char::is_alphanumeric) => Class::Word,
+        _ => Class::Punctuation,
+    }
+}
+
+// steps over the buffer one grapheme at a time, caching the current line. the end of every
+// line but the last is a "\n" position, the end of the last line is the end of the buffer
+pub(crate) struct Walker<'a> {
+    buffer: &'a Buffer,
+    x: usize,
+    y: usize,
+    line: Vec<String>,
+    // W B E and gE only tell blanks from non-blanks
+    pub(crate) big: bool,
+}
+impl<'a> Walker<'a> {
+    pub(crate) fn new(buffer: &'a Buffer, x: usize, y: usize) -> Self {
+        let line = graphemes_of(buffer, y);
+        let x = min(x, line.len());
+        Self {
+            buffer,
+            x,
+            y,
+            line,
+            big: false,
+        }
+    }
+    pub(crate) fn position(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+    pub(crate) fn get(&self) -> Option<&str> {
+        if self.x < self.line.len() {
+            Some(&self.line[self.x])
+        } else if self.y + 1 < self.buffer.line_count() {
+            Some("\n")
+        } else {
+            None
+        }
+    }
+    fn class(&self) -> Class {
+        self.get()
+            .map(|g| class_of(g, self.big))
+            .unwrap_or(Class::Blank)
+    }
+    fn at_empty_line(&self) -> bool {
+        self.line.is_empty()
+    }
+    pub(crate) fn next(&mut self) -> bool {
+        if self.x < self.line.len() {
+            self.x += 1;
+            true
+        } else if self.y + 1 < self.buffer.line_count() {
+            self.y += 1;
+            self.x = 0;
+            self.line = graphemes_of(self.buffer, self.y);
+            true
+        } else {
+            false
+        }
+    }
+    pub(crate) fn prev(&mut self) -> bool {
+        if self.x > 0 {
+            self.x -= 1;
+            true
+        } else if self.y > 0 {
+            self.y -= 1;
+            self.line = graphemes_of(self.buffer, self.y);
+            self.x = self.line.len();
+            true
+        } else {
+            false
+        }
+    }
+    fn peek_next_class(&self) -> Option<Class> {
+        if self.x + 1 < self.line.len() {
+            Some(class_of(&self.line[self.x + 1], self.big))
+        } else if self.x + 1 == self.line.len() {
+            if self.y + 1 < self.buffer.line_count() {
+                Some(Class::Blank)
+            } else {
+                None
+            }
+        } else {
+            let mut ahead = Walker::new(self.buffer, self.x, self.y);
+            ahead.big = self.big;
+            ahead.next().then(|| ahead.class())
+        }
+    }
+    fn peek_prev_class(&self) -> Option<Class> {
+        if self.x > 0 {
+            Some(class_of(&self.line[self.x - 1], self.big))
+        } else if self.y > 0 {
+            Some(Class::Blank)
+        } else {
+            None
+        }
+    }
+
+    fn word_start_forward(&mut self) -> bool {
+        let start = self.position();
+        let class = self.class();
+        if class != Class::Blank {
+            while self.get().is_some() && self.class() == class {
+                if !self.next() {
+                    break;
+                }
+            }
+        }
+        // blanks and line breaks are skipped, but an empty line counts as a word
+        while self.get().is_some() && self.class() == Class::Blank {
+            if !self.next() || (self.at_empty_line() && self.x == 0) {
+                break;
+            }
+        }
+        self.position() != start
+    }
+    fn word_start_backward(&mut self) -> bool {
+        if !self.prev() {
+            return false;
+        }
+        while self.class() == Class::Blank && !(self.at_empty_line() && self.x == 0) {
+            if !self.prev() {
+                return true;
+            }
+        }
+        let class = self.class();
+        while class != Class::Blank && self.peek_prev_class() == Some(class) {
+            self.prev();
+        }
+        true
+    }
+    fn word_end_forward(&mut self) -> bool {
+        let start = self.position();
+        if !self.next() {
+            return false;
+        }
+        while self.get().is_some() && self.class() == Class::Blank {
+            self.next();
+        }
+        if self.get().is_none() {
+            // ran off the end of the buffer without finding another word
+            self.prev();
+            return self.position() != start;
+        }
+        let class = self.class();
+        while self.peek_next_class() == Some(class) {
+            self.next();
+        }
+        true
+    }
+    fn word_end_backward(&mut self) -> bool {
+        let class = self.class();
+        if class != Class::Blank {
+            while self.peek_prev_class() == Some(class) {
+                self.prev();
+            }
+        }
+        if !self.prev() {
+            return false;
+        }
+        while self.class() == Class::Blank && !(self.at_empty_line() && self.x == 0) {
+            if !self.prev() {
+                return true;
+            }
+        }
+        true
+    }
+}
+
+pub(crate) fn graphemes_of(buffer: &Buffer, y: usize) -> Vec<String> {
+    UnicodeSegmentation::graphemes(buffer.line(y).as_str(), true)
+        .map(str::to_string)
+        .collect()
+}
+
+mod test {
+    #[test]
+    fn should_move_over_words_of_any_script() {
+        use crate::motion::Motion;
+        let mut buffer = crate::buffer::Buffer::from("foo.bar  baz\n\n  naïve 日本語です ok");
+        let go = |buffer: &mut crate::buffer::Buffer, motion, count| {
+            let (x, y) = buffer.motion_target(motion, count, 0..10).unwrap();
+            buffer.set_cursor(x, y, crate::editor::Mode::NORMAL);
+            buffer.cursor()
+        };
+        assert_eq!((3, 0), go(&mut buffer, Motion::WordStart, None));
+        assert_eq!((4, 0), go(&mut buffer, Motion::WordStart, None));
+        assert_eq!((0, 1), go(&mut buffer, Motion::WordStart, Some(2)));
+        assert_eq!((2, 2), go(&mut buffer, Motion::WordStart, None));
+        assert_eq!((6, 2), go(&mut buffer, Motion::BigWordEnd, None));
+        assert_eq!((8, 2), go(&mut buffer, Motion::WordStart, Some(1)));
+        assert_eq!((10, 2), go(&mut buffer, Motion::WordEnd, None));
+        assert_eq!((12, 2), go(&mut buffer, Motion::WordEnd, None));
+        assert_eq!((8, 2), go(&mut buffer, Motion::WordBackward, Some(2)));
+        assert_eq!((6, 2), go(&mut buffer, Motion::WordEndBackward, None));
+        assert_eq!((0, 1), go(&mut buffer, Motion::WordEndBackward, None));
+        assert_eq!((9, 0), go(&mut buffer, Motion::BigWordBackward, None));
+        assert_eq!((0, 0), go(&mut buffer, Motion::BigWordBackward, None));
+        assert_eq!(
+            None,
+            buffer.motion_target(Motion::WordBackward, None, 0..10)
+        );
+        assert_eq!((2, 2), go(&mut buffer, Motion::LastLine, None));
+        assert_eq!((15, 2), go(&mut buffer, Motion::LineEnd, None));
+        assert_eq!(None, buffer.motion_target(Motion::WordEnd, Some(5), 0..10));
+        assert_eq!((0, 0), go(&mut buffer, Motion::FirstLine, None));
+        assert_eq!((0, 1), go(&mut buffer, Motion::LastLine, Some(2)));
+    }
+
+    #[test]
+    fn should_move_over_paragraphs_sentences_and_brackets() {
+        use crate::motion::Motion;
+        let mut buffer = crate::buffer::Buffer::from(
+            "One. Two (three)!  Four\nstill four.\n\nfn x() {\n    [1, (2)]\n}",
+        );
+        let go = |buffer: &mut crate::buffer::Buffer, motion, count| {
+            let (x, y) = buffer.motion_target(motion, count, 0..10).unwrap();
+            buffer.set_cursor(x, y, crate::editor::Mode::NORMAL);
+            buffer.cursor()
+        };
+        assert_eq!((5, 0), go(&mut buffer, Motion::SentenceForward, None));
+        assert_eq!((19, 0), go(&mut buffer, Motion::SentenceForward, None));
+        assert_eq!((0, 2), go(&mut buffer, Motion::SentenceForward, None));
+        assert_eq!((19, 0), go(&mut buffer, Motion::SentenceBackward, None));
+        assert_eq!((0, 0), go(&mut buffer, Motion::SentenceBackward, Some(2)));
+        assert_eq!((0, 2), go(&mut buffer, Motion::ParagraphForward, None));
+        assert_eq!((0, 5), go(&mut buffer, Motion::ParagraphForward, None));
+        assert_eq!((7, 3), go(&mut buffer, Motion::MatchingBracket, None));
+        assert_eq!((7, 4), go(&mut buffer, Motion::Down, None));
+        assert_eq!((10, 4), go(&mut buffer, Motion::MatchingBracket, None));
+        assert_eq!((8, 4), go(&mut buffer, Motion::MatchingBracket, None));
+        assert_eq!((0, 2), go(&mut buffer, Motion::ParagraphBackward, None));
+        assert_eq!((0, 0), go(&mut buffer, Motion::ParagraphBackward, None));
+        assert_eq!((0, 3), go(&mut buffer, Motion::Percent, Some(60)));
+        assert_eq!((0, 5), go(&mut buffer, Motion::WindowBottom, None));
+        assert_eq!((4, 4), go(&mut buffer, Motion::WindowBottom, Some(2)));
+        assert_eq!((0, 2), go(&mut buffer, Motion::WindowMiddle, None));
+        assert_eq!((0, 0), go(&mut buffer, Motion::WindowTop, None));
+    }
+
+    #[test]
+    fn should_find_characters_in_line() {
+        use crate::motion::{CharSearch, Motion};
+        let mut buffer = crate::buffer::Buffer::from("a, b, ce\u{301}, d\n,");
+        let go = |buffer: &mut crate::buffer::Buffer, motion, count| {
+            let (x, y) = buffer.motion_target(motion, count, 0..10)?;
+            buffer.set_cursor(x, y, crate::editor::Mode::NORMAL);
+            Some(buffer.cursor())
+        };
+        let search = |target, forward, till, repeat| {
+            Motion::FindChar(CharSearch {
+                target,
+                forward,
+                till,
+                repeat,
+            })
+        };
+        assert_eq!(
+            Some((1, 0)),
+            go(&mut buffer, search(',', true, false, false), None)
+        );
+        assert_eq!(
+            Some((8, 0)),
+            go(&mut buffer, search(',', true, false, false), Some(2))
+        );
+        assert_eq!(None, go(&mut buffer, search(',', true, false, false), None));
+        assert_eq!(
+            Some((7, 0)),
+            go(&mut buffer, search('e', false, false, false), None)
+        );
+        assert_eq!(
+            Some((5, 0)),
+            go(&mut buffer, search(',', false, true, false), None)
+        );
+        assert_eq!(None, go(&mut buffer, search(',', false, true, false), None));
+        assert_eq!(
+            Some((2, 0)),
+            go(&mut buffer, search(',', false, true, true), None)
+        );
+        assert_eq!(
+            Some((3, 0)),
+            go(&mut buffer, search(',', true, true, false), None)
+        );
+        assert_eq!(
+            Some((5, 0)),
+            go(&mut buffer, search('c', true, true, true), None)
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use crate::command::{Operator, Target};
+use crate::editor::{Editor, Mode};
+use crate::motion::{Motion, MotionKind};
+use crate::register::{Register, RegisterKind};
+use crate::{min, Instant};
+use std::cmp::max;
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Region {
+    // byte offsets into the buffer
+    Chars(Range<usize>),
+    // first and last line, inclusive
+    Lines(usize, usize),
+    // columns in graphemes, lines shorter than the block take part of it or nothing
+    Block {
+        first: usize,
+        last: usize,
+        columns: Range<usize>,
+    },
+}
+
+impl Editor {
+    // fails when there's nothing for the operator to work on
+    pub fn apply_operator(
+        &mut self,
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+    ) -> Result<(), String> {
+        let (_, y) = self.buffer.cursor();
+        let region = match target {
+            Target::Line => {
+                let lines = count.unwrap_or(1).max(1);
+                let last = min(y.saturating_add(lines - 1), self.buffer.line_count() - 1);
+                Region::Lines(y, last)
+            }
+            Target::Motion(motion) => self
+                .motion_region(operator, motion, count)
+                .ok_or_else(String::new)?,
+            Target::TextObject(object) => self
+                .buffer
+                .text_object_region(object, count)
+                .ok_or_else(String::new)?,
+            Target::Selection => match self.selection_region() {
+                Some(region) => {
+                    self.leave_visual_mode();
+                    // operators start from the top of the selection, whichever end the cursor is on
+                    let (x, y) = match &region {
+                        Region::Chars(range) => self.buffer.position_of(range.start),
+                        Region::Lines(first, _) => (self.buffer.cursor().0, *first),
+                        Region::Block { first, columns, .. } => (columns.start, *first),
+                    };
+                    self.buffer.set_cursor(x, y, Mode::NORMAL);
+                    region
+                }
+                None => return Ok(()),
+            },
+        };
+        self.operate(operator, region);
+        Ok(())
+    }
+
+    fn motion_region(
+        &mut self,
+        operator: Operator,
+        motion: Motion,
+        count: Option<usize>,
+    ) -> Option<Region> {
+        let (x, y) = self.buffer.cursor();
+        let motion = self.resolve_motion(motion)?;
+        // cw on a word behaves like ce, it doesn't eat the blanks after the word
+        let motion = if operator == Operator::Change
+            && motion == Motion::WordStart
+            && !is_blank_at(self, x, y)
+        {
+            Motion::WordEnd
+        } else {
+            motion
+        };
+        let (target_x, target_y) =
+            self.buffer
+                .motion_target(motion, count, self.visible_lines())?;
+
+        if motion.kind() == MotionKind::Linewise {
+            return Some(Region::Lines(min(y, target_y), max(y, target_y)));
+        }
+
+        let here = self.buffer.offset_of(x, y);
+        let there = self.buffer.offset_of(target_x, target_y);
+        let (start, mut end) = (min(here, there), max(here, there));
+        if motion.kind() == MotionKind::Inclusive {
+            let (end_x, end_y) = if there > here {
+                (target_x, target_y)
+            } else {
+                (x, y)
+            };
+            end = self.buffer.offset_of(end_x + 1, end_y);
+        } else if there > here && target_y > y {
+            // an exclusive motion ending at the start of a line stops at the end of the line
+            // before, and dw never crosses into the indentation of the next line
+            let line_start = self.buffer.line_start(target_y);
+            let only_indent = self.buffer.slice(line_start..there).trim().is_empty();
+            if target_x == 0 || (motion == Motion::WordStart && only_indent) {
+                end = self.buffer.line_end(target_y - 1);
+            }
+        }
+        Some(Region::Chars(start..end))
+    }
+
+    pub fn operate(&mut self, operator: Operator, region: Region) {
+        self.buffer.begin_undo_group();
+        match operator {
+            Operator::Delete => {
+                let register = self.region_register(&region);
+                self.delete_to_register(register);
+                self.delete_region(&region);
+            }
+            Operator::Change => {
+                let register = self.region_register(&region);
+                self.delete_to_register(register);
+                match region {
+                    Region::Chars(range) => {
+                        self.buffer.delete(range.clone());
+                        let (x, y) = self.buffer.position_of(range.start);
+                        self.buffer.set_cursor(x, y, Mode::INSERT);
+                    }
+                    Region::Lines(first, last) => {
+                        let range = self.buffer.line_start(first)..self.buffer.line_end(last);
+                        self.buffer.delete(range);
+                        self.buffer.set_cursor(0, first, Mode::INSERT);
+                    }
+                    Region::Block {
+                        first,
+                        last,
+                        ref columns,
+                    } => {
+                        self.delete_region(&region);
+                        self.start_block_insert(first, last, Some(columns.start), false);
+                    }
+                }
+                // the undo group stays open until the insert session ends
+                self.mode = Mode::INSERT;
+            }
+            Operator::Yank => {
+                let register = self.region_register(&region);
+                self.yank_to_register(register);
+                // '[ and '] go around the yanked text like around changed text
+                let ranges = self.region_ranges(&region);
+                if let (Some(first), Some(last)) = (ranges.first(), ranges.last()) {
+                    let last_char = self.buffer.slice(last.clone()).chars().last();
+                    let end = last.end - last_char.map_or(0, char::len_utf8);
+                    self.buffer.set_mark('[', first.start);
+                    self.buffer.set_mark(']', end);
+                }
+                match region {
+                    Region::Chars(range) => {
+                        let (x, y) = self.buffer.position_of(range.start);
+                        self.buffer.set_cursor(x, y, Mode::NORMAL);
+                    }
+                    Region::Lines(first, _) => {
+                        let (x, _) = self.buffer.cursor();
+                        self.buffer.set_cursor(x, first, Mode::NORMAL);
+                    }
+                    Region::Block { first, columns, .. } => {
+                        self.buffer.set_cursor(columns.start, first, Mode::NORMAL);
+                    }
+                }
+            }
+            Operator::Indent | Operator::Unindent => {
+                let (first, last) = self.region_lines(&region);
+                for y in first..=last {
+                    self.shift_line(y, operator == Operator::Indent);
+                }
+                let x = self.buffer.first_non_blank(first);
+                self.buffer.set_cursor(x, first, Mode::NORMAL);
+            }
+            Operator::Lowercase | Operator::Uppercase => {
+                let ranges = self.region_ranges(&region);
+                for range in ranges.iter().rev() {
+                    let text = self.buffer.slice(range.clone());
+                    let changed = if operator == Operator::Lowercase {
+                        text.to_lowercase()
+                    } else {
+                        text.to_uppercase()
+                    };
+                    if changed != text {
+                        self.buffer.delete(range.clone());
+                        self.buffer.insert(range.start, &changed);
+                    }
+                }
+                let start = ranges.first().map(|range| range.start).unwrap_or(0);
+                let (x, y) = self.buffer.position_of(start);
+                self.buffer.set_cursor(x, y, Mode::NORMAL);
+            }
+        }
+        if operator != Operator::Change {
+            self.buffer.end_undo_group();
+        }
+        self.scroll_to_cursor();
+        self.cursor_animation_instant = Instant::now();
+    }
+
+    pub(crate) fn region_register(&self, region: &Region) -> Register {
+        match region {
+            Region::Chars(range) => Register {
+                text: self.buffer.slice(range.clone()),
+                kind: RegisterKind::Chars,
+            },
+            Region::Lines(first, last) => {
+                let range = self.buffer.line_start(*first)..self.buffer.line_end(*last);
+                Register {
+                    text: self.buffer.slice(range) + "\n",
+                    kind: RegisterKind::Lines,
+                }
+            }
+            Region::Block { .. } => Register {
+                text: self
+                    .region_ranges(region)
+                    .into_iter()
+                    .map(|range| self.buffer.slice(range))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                kind: RegisterKind::Block,
+            },
+        }
+    }
+
+    // the byte ranges a region covers, one per line for blocks
+    fn region_ranges(&self, region: &Region) -> Vec<Range<usize>> {
+        match region {
+            Region::Chars(range) => vec![range.clone()],
+            Region::Lines(first, last) => {
+                let range = self.buffer.line_start(*first)..self.buffer.line_end(*last);
+                vec![range]
+            }
+            Region::Block {
+                first,
+                last,
+                columns,
+            } => (*first..=*last)
+                .map(|y| {
+                    let length = self.buffer.line_length(y);
+                    let start = self.buffer.offset_of(min(columns.start, length), y);
+                    let end = self.buffer.offset_of(min(columns.end, length), y);
+                    start..end
+                })
+                .collect(),
+        }
+    }
+
+    fn region_lines(&self, region: &Region) -> (usize, usize) {
+        match region {
+            Region::Chars(range) => (
+                self.buffer.line_of_offset(range.start),
+                self.buffer.line_of_offset(range.end),
+            ),
+            Region::Lines(first, last) | Region::Block { first, last, .. } => (*first, *last),
+        }
+    }
+
+    pub(crate) fn delete_region(&mut self, region: &Region) {
+        match region {
+            Region::Chars(range) => {
+                self.buffer.delete(range.clone());
+                let (x, y) = self.buffer.position_of(range.start);
+                self.buffer.set_cursor(x, y, Mode::NORMAL);
+            }
+            Region::Lines(first, last) => {
+                let (first, last) = (*first, *last);
+                let line_count = self.buffer.line_count();
+                let range = if last + 1 < line_count {
+                    self.buffer.line_start(first)..self.buffer.line_start(last + 1)
+                } else if first > 0 {
+                    self.buffer.line_end(first - 1)..self.buffer.len()
+                } else {
+                    0..self.buffer.len()
+                };
+                self.buffer.delete(range);
+                let y = min(first, self.buffer.line_count() - 1);
+                let x = self.buffer.first_non_blank(y);
+                self.buffer.set_cursor(x, y, Mode::NORMAL);
+            }
+            Region::Block { first, columns, .. } => {
+                for range in self.region_ranges(region).into_iter().rev() {
+                    self.buffer.delete(range);
+                }
+                self.buffer.set_cursor(columns.start, *first, Mode::NORMAL);
+            }
+        }
+    }
+
+    // by shiftwidth spaces, or a tab with noexpandtab
+    fn shift_line(&mut self, y: usize, indent: bool) {
+        let start = self.buffer.line_start(y);
+        let line = self.buffer.line(y);
+        let shiftwidth = self.option("shiftwidth").number() as usize;
+        if indent {
+            if !line.is_empty() {
+                let indent = if self.option("expandtab").boolean() {
+                    " ".repeat(shiftwidth)
+                } else {
+                    "\t".to_string()
+                };
+                self.buffer.insert(start, &indent);
+            }
+        } else {
+            let width = if line.starts_with('\t') {
+                1
+            } else {
+                min(line.len() - line.trim_start_matches(' ').len(), shiftwidth)
+            };
+            self.buffer.delete(start..start + width);
+        }
+    }
+}
+
+fn is_blank_at(editor: &Editor, x: usize, y: usize) -> bool {
+    let offset = editor.buffer.offset_of(x, y);
+    let next = editor.buffer.offset_of(x + 1, y);
+    editor.buffer.slice(offset..next).trim().is_empty()
+}
+
+mod test {
+    #[test]
+    fn should_apply_operators_to_motions_and_lines() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("one two three four\nfive six\nseven\n");
+
+        editor.type_keys("w2dw");
+        assert_eq!("one four\nfive six\nseven\n", editor.buffer.as_string());
+        assert_eq!("two three ", editor.registers.get('"').unwrap().text);
+        editor.type_keys("d$");
+        assert_eq!("one \nfive six\nseven\n", editor.buffer.as_string());
+        editor.type_keys("0cwONE\x1b");
+        assert_eq!("ONE \nfive six\nseven\n", editor.buffer.as_string());
+        editor.type_keys("jwdw");
+        assert_eq!("ONE \nfive \nseven\n", editor.buffer.as_string());
+
+        editor.type_keys("kyy");
+        assert_eq!("ONE \n", editor.registers.get('"').unwrap().text);
+        assert_eq!(
+            crate::register::RegisterKind::Lines,
+            editor.registers.get('"').unwrap().kind
+        );
+        editor.type_keys("jjgUU");
+        assert_eq!("ONE \nfive \nSEVEN\n", editor.buffer.as_string());
+        editor.type_keys("u");
+        assert_eq!("ONE \nfive \nseven\n", editor.buffer.as_string());
+    }
+
+    #[test]
+    fn should_operate_on_counted_lines() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("1\n2\n3\n4\n5\n6\n7");
+
+        editor.type_keys("j5dd");
+        assert_eq!("1\n7", editor.buffer.as_string());
+        assert_eq!("2\n3\n4\n5\n6\n", editor.registers.get('"').unwrap().text);
+        editor.type_keys("u");
+        assert_eq!("1\n2\n3\n4\n5\n6\n7", editor.buffer.as_string());
+        assert_eq!((0, 1), editor.buffer.cursor());
+
+        editor.type_keys("jc2jx\x1b");
+        assert_eq!("1\n2\nx\n6\n7", editor.buffer.as_string());
+        editor.type_keys("u");
+        assert_eq!("1\n2\n3\n4\n5\n6\n7", editor.buffer.as_string());
+
+        editor.type_keys("9jk5dd");
+        assert_eq!("1\n2\n3\n4\n5", editor.buffer.as_string());
+        editor.type_keys(">>");
+        assert_eq!("1\n2\n3\n4\n    5", editor.buffer.as_string());
+        editor.type_keys("<k");
+        assert_eq!("1\n2\n3\n4\n5", editor.buffer.as_string());
+    }
+
+    #[test]
+    fn should_operate_on_every_kind_of_motion() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("f(a, g(b)) + c.d\n\nnext\nlast");
+
+        editor.type_keys("ld%");
+        assert_eq!("f + c.d\n\nnext\nlast", editor.buffer.as_string());
+        editor.type_keys("$dB");
+        assert_eq!("f + d\n\nnext\nlast", editor.buffer.as_string());
+        editor.type_keys("d}");
+        assert_eq!("f + \n\nnext\nlast", editor.buffer.as_string());
+        editor.type_keys("jjdG");
+        assert_eq!("f + \n", editor.buffer.as_string());
+        editor.type_keys("u2Gd2L");
+        assert_eq!("f + \nlast", editor.buffer.as_string());
+    }
+
+    #[test]
+    fn should_operate_up_to_searched_characters() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("call(one, two, three);");
+
+        editor.type_keys("f(ldt)");
+        assert_eq!("call();", editor.buffer.as_string());
+        editor.type_keys("u;");
+        assert_eq!((19, 0), editor.buffer.cursor());
+        editor.type_keys("0f,;,");
+        assert_eq!((8, 0), editor.buffer.cursor());
+        editor.type_keys("lcf,X\x1b");
+        assert_eq!("call(one,X three);", editor.buffer.as_string());
+        editor.type_keys("$dT,");
+        assert_eq!("call(one,;", editor.buffer.as_string());
+    }
+
+    #[test]
+    fn should_operate_on_text_objects() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer =
+            crate::buffer::Buffer::from("print(\"hello there\")\nif x {\n    a(b);\n}\n");
+
+        editor.type_keys("fhci\"bye\x1b");
+        assert_eq!(
+            "print(\"bye\")\nif x {\n    a(b);\n}\n",
+            editor.buffer.as_string()
+        );
+        editor.type_keys("da(");
+        assert_eq!("print\nif x {\n    a(b);\n}\n", editor.buffer.as_string());
+        assert_eq!("(\"bye\")", editor.registers.get('"').unwrap().text);
+        editor.type_keys("jjfbdi{");
+        assert_eq!("print\nif x {\n}\n", editor.buffer.as_string());
+        assert_eq!(
+            crate::register::RegisterKind::Lines,
+            editor.registers.get('"').unwrap().kind
+        );
+        editor.type_keys("udiw");
+        assert_eq!("print\nif x {\n    a();\n}\n", editor.buffer.as_string());
+    }
+}
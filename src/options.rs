@@ -0,0 +1,499 @@
+use crate::editor::Editor;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionValue {
+    Boolean(bool),
+    Number(i64),
+    String(String),
+    // written as items separated by commas
+    List(Vec<String>),
+}
+
+impl OptionValue {
+    pub fn boolean(&self) -> bool {
+        match self {
+            OptionValue::Boolean(value) => *value,
+            _ => panic!("not a boolean option"),
+        }
+    }
+
+    pub fn number(&self) -> i64 {
+        match self {
+            OptionValue::Number(value) => *value,
+            _ => panic!("not a number option"),
+        }
+    }
+
+    pub fn string(&self) -> &str {
+        match self {
+            OptionValue::String(value) => value,
+            _ => panic!("not a string option"),
+        }
+    }
+
+    pub fn list(&self) -> &[String] {
+        match self {
+            OptionValue::List(value) => value,
+            _ => panic!("not a list option"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    Global,
+    // every buffer can have its own value, new ones start with the global value
+    Buffer,
+}
+
+struct OptionInfo {
+    name: &'static str,
+    short: &'static str,
+    scope: Scope,
+    default: fn() -> OptionValue,
+}
+
+static OPTIONS: &[OptionInfo] = &[
+    OptionInfo {
+        name: "backgroundcolor",
+        short: "bgc",
+        scope: Scope::Global,
+        default: || OptionValue::String("#4d4d52".into()),
+    },
+    OptionInfo {
+        name: "clipboard",
+        short: "cb",
+        scope: Scope::Global,
+        // unnamedplus makes yanks, deletes and puts without a register go through "+
+        default: || OptionValue::List(vec![]),
+    },
+    OptionInfo {
+        name: "cursorblink",
+        short: "",
+        scope: Scope::Global,
+        // milliseconds the cursor is shown and then hidden for, 0 doesn't blink
+        default: || OptionValue::Number(500),
+    },
+    OptionInfo {
+        name: "expandtab",
+        short: "et",
+        scope: Scope::Buffer,
+        default: || OptionValue::Boolean(true),
+    },
+    OptionInfo {
+        name: "fontsize",
+        short: "fs",
+        scope: Scope::Global,
+        default: || OptionValue::Number(24),
+    },
+    OptionInfo {
+        name: "foregroundcolor",
+        short: "fgc",
+        scope: Scope::Global,
+        default: || OptionValue::String("#ffffff".into()),
+    },
+    OptionInfo {
+        name: "readonly",
+        short: "ro",
+        scope: Scope::Buffer,
+        // :w needs a ! to write the buffer's own file
+        default: || OptionValue::Boolean(false),
+    },
+    OptionInfo {
+        name: "scrolloff",
+        short: "so",
+        scope: Scope::Global,
+        default: || OptionValue::Number(5),
+    },
+    OptionInfo {
+        name: "searchcolor",
+        short: "shc",
+        scope: Scope::Global,
+        default: || OptionValue::String("#8c7333".into()),
+    },
+    OptionInfo {
+        name: "selectioncolor",
+        short: "slc",
+        scope: Scope::Global,
+        default: || OptionValue::String("#737399".into()),
+    },
+    OptionInfo {
+        name: "shiftwidth",
+        short: "sw",
+        scope: Scope::Buffer,
+        default: || OptionValue::Number(4),
+    },
+    OptionInfo {
+        name: "timeoutlen",
+        short: "tm",
+        scope: Scope::Global,
+        // milliseconds to wait for the rest of a longer binding
+        default: || OptionValue::Number(1000),
+    },
+    OptionInfo {
+        name: "undodir",
+        short: "udir",
+        scope: Scope::Global,
+        // empty keeps undo files next to their file
+        default: || OptionValue::String(String::new()),
+    },
+    OptionInfo {
+        name: "undofile",
+        short: "udf",
+        scope: Scope::Global,
+        default: || OptionValue::Boolean(false),
+    },
+];
+
+// the global values of every option, buffer-local ones included
+pub fn default_options() -> HashMap<&'static str, OptionValue> {
+    OPTIONS
+        .iter()
+        .map(|info| (info.name, (info.default)()))
+        .collect()
+}
+
+fn find_option(name: &str) -> Option<&'static OptionInfo> {
+    OPTIONS
+        .iter()
+        .find(|info| info.name == name || (!info.short.is_empty() && info.short == name))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operation {
+    Set,
+    Add,
+    Remove,
+    Prepend,
+}
+
+impl Editor {
+    // the value the current buffer sees
+    pub fn option(&self, name: &str) -> &OptionValue {
+        self.buffer
+            .local_options
+            .get(name)
+            .or_else(|| self.options.get(name))
+            .unwrap_or_else(|| panic!("unknown option {}", name))
+    }
+
+    // :set, :setlocal and :setglobal. :set changes both values of a buffer-local option,
+    // :setlocal only the buffer's own and :setglobal only the one new buffers start with
+    pub(crate) fn set_command(
+        &mut self,
+        argument: &str,
+        scope: Option<Scope>,
+    ) -> Result<(), String> {
+        let items = split_items(argument);
+        if items.is_empty() || items == ["all"] {
+            return self.list_options(items.is_empty());
+        }
+        let mut shown = vec![];
+        for item in items {
+            if let Some(text) = self.set_option(&item, scope)? {
+                shown.push(text);
+            }
+        }
+        if !shown.is_empty() {
+            self.status_message = shown.join("  ");
+        }
+        Ok(())
+    }
+
+    // name, noname, invname, name!, name&, name?, name=value, name+=value, name-=value and
+    // name^=value. what to show, for the ones asking
+    fn set_option(&mut self, item: &str, scope: Option<Scope>) -> Result<Option<String>, String> {
+        let invalid = || format!("E474: Invalid argument: {}", item);
+        let name_length = item.len() - item.trim_start_matches(char::is_alphabetic).len();
+        let (name, rest) = item.split_at(name_length);
+        let (info, prefix) = match find_option(name) {
+            Some(info) => (info, ""),
+            None => {
+                let (prefix, name) = match name.strip_prefix("no") {
+                    Some(name) => ("no", name),
+                    None => ("inv", name.strip_prefix("inv").unwrap_or(name)),
+                };
+                let info =
+                    find_option(name).ok_or_else(|| format!("E518: Unknown option: {}", item))?;
+                (info, prefix)
+            }
+        };
+        let current = self.option_in_scope(info, scope).clone();
+        let is_boolean = matches!(current, OptionValue::Boolean(_));
+        let value = match (prefix, rest, &current) {
+            ("", "?", _) => return Ok(Some(show_option(info.name, &current))),
+            ("", "&", _) => (info.default)(),
+            ("", "", OptionValue::Boolean(_)) => OptionValue::Boolean(true),
+            // for the other kinds a name on its own is asking for the value
+            ("", "", _) => return Ok(Some(show_option(info.name, &current))),
+            ("no", "", _) if is_boolean => OptionValue::Boolean(false),
+            ("inv", "", OptionValue::Boolean(value)) | ("", "!", OptionValue::Boolean(value)) => {
+                OptionValue::Boolean(!value)
+            }
+            ("", _, _) if !is_boolean => {
+                let (operation, text) = parse_operation(rest).ok_or_else(invalid)?;
+                apply_operation(&current, operation, text).ok_or_else(|| match current {
+                    OptionValue::Number(_) => format!("E521: Number required after =: {}", item),
+                    _ => invalid(),
+                })?
+            }
+            _ => return Err(invalid()),
+        };
+        if !is_valid(info.name, &value) {
+            return Err(invalid());
+        }
+        self.assign_option(info, value, scope);
+        Ok(None)
+    }
+
+    fn option_in_scope(&self, info: &OptionInfo, scope: Option<Scope>) -> &OptionValue {
+        match scope {
+            Some(Scope::Global) => &self.options[info.name],
+            _ => self.option(info.name),
+        }
+    }
+
+    fn assign_option(&mut self, info: &OptionInfo, value: OptionValue, scope: Option<Scope>) {
+        if info.scope == Scope::Global {
+            self.options.insert(info.name, value);
+            return;
+        }
+        match scope {
+            Some(Scope::Buffer) => {}
+            // the buffer keeps the value it had
+            Some(Scope::Global) => {
+                let current = self.option(info.name).clone();
+                self.buffer
+                    .local_options
+                    .entry(info.name)
+                    .or_insert(current);
+                self.options.insert(info.name, value);
+                return;
+            }
+            None => {
+                self.options.insert(info.name, value.clone());
+            }
+        }
+        self.buffer.local_options.insert(info.name, value);
+    }
+
+    // :set shows the options that aren't at their default, :set all every one of them
+    fn list_options(&mut self, changed_only: bool) -> Result<(), String> {
+        let listed: Vec<_> = OPTIONS
+            .iter()
+            .filter(|info| !changed_only || *self.option(info.name) != (info.default)())
+            .map(|info| show_option(info.name, self.option(info.name)))
+            .collect();
+        self.status_message = listed.join("  ");
+        Ok(())
+    }
+
+    // the ex commands in $XDG_CONFIG_HOME/uu/uurc, or ~/.config/uu/uurc, if there is one
+    pub fn load_config(&mut self) -> Result<(), String> {
+        match config_file_path() {
+            Some(path) if path.is_file() => self.source(&path.to_string_lossy()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn config_file_path() -> Option<PathBuf> {
+    let directory = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(directory) if !directory.is_empty() => PathBuf::from(directory),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(directory.join("uu").join("uurc"))
+}
+
+// items are separated by blanks, a blank after a \ is part of its item
+fn split_items(argument: &str) -> Vec<String> {
+    let mut items = vec![];
+    let mut item = String::new();
+    let mut chars = argument.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => item.extend(chars.next()),
+            c if c.is_whitespace() => {
+                if !item.is_empty() {
+                    items.push(std::mem::take(&mut item));
+                }
+            }
+            c => item.push(c),
+        }
+    }
+    if !item.is_empty() {
+        items.push(item);
+    }
+    items
+}
+
+fn parse_operation(rest: &str) -> Option<(Operation, &str)> {
+    let operations = [
+        ("+=", Operation::Add),
+        ("-=", Operation::Remove),
+        ("^=", Operation::Prepend),
+        ("=", Operation::Set),
+        (":", Operation::Set),
+    ];
+    operations
+        .iter()
+        .find_map(|(written, operation)| Some((*operation, rest.strip_prefix(written)?)))
+}
+
+// += adds to numbers and strings and appends items to lists, -= takes them away and ^=
+// multiplies or prepends
+fn apply_operation(current: &OptionValue, operation: Operation, text: &str) -> Option<OptionValue> {
+    Some(match current {
+        OptionValue::Number(number) => {
+            let value: i64 = text.parse().ok()?;
+            OptionValue::Number(match operation {
+                Operation::Set => value,
+                Operation::Add => number.checked_add(value)?,
+                Operation::Remove => number.checked_sub(value)?,
+                Operation::Prepend => number.checked_mul(value)?,
+            })
+        }
+        OptionValue::String(string) => OptionValue::String(match operation {
+            Operation::Set => text.to_string(),
+            Operation::Add => format!("{}{}", string, text),
+            Operation::Remove => string.replacen(text, "", 1),
+            Operation::Prepend => format!("{}{}", text, string),
+        }),
+        OptionValue::List(list) => {
+            let items = text.split(',').filter(|item| !item.is_empty());
+            let mut list = list.clone();
+            match operation {
+                Operation::Set => list = items.map(String::from).collect(),
+                Operation::Add => list.extend(items.map(String::from)),
+                Operation::Remove => {
+                    let removed: Vec<_> = items.collect();
+                    list.retain(|item| !removed.contains(&item.as_str()));
+                }
+                Operation::Prepend => {
+                    list.splice(0..0, items.map(String::from));
+                }
+            }
+            OptionValue::List(list)
+        }
+        OptionValue::Boolean(_) => return None,
+    })
+}
+
+fn is_valid(name: &str, value: &OptionValue) -> bool {
+    match (name, value) {
+        ("fontsize", OptionValue::Number(size)) => *size > 0,
+        (_, OptionValue::Number(number)) => *number >= 0,
+        (name, OptionValue::String(color)) if name.ends_with("color") => {
+            parse_color(color).is_some()
+        }
+        _ => true,
+    }
+}
+
+fn show_option(name: &str, value: &OptionValue) -> String {
+    match value {
+        OptionValue::Boolean(true) => name.to_string(),
+        OptionValue::Boolean(false) => format!("no{}", name),
+        OptionValue::Number(number) => format!("{}={}", name, number),
+        OptionValue::String(string) => format!("{}={}", name, string),
+        OptionValue::List(list) => format!("{}={}", name, list.join(",")),
+    }
+}
+
+// #rrggbb
+pub fn parse_color(color: &str) -> Option<[f32; 4]> {
+    let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |i: usize| {
+        let value = u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()?;
+        Some(value as f32 / 255.0)
+    };
+    Some([channel(0)?, channel(2)?, channel(4)?, 1.0])
+}
+
+mod test {
+    #[test]
+    fn should_set_and_show_options() {
+        let mut editor = crate::editor::Editor::new();
+        let set = |editor: &mut crate::editor::Editor, argument: &str| {
+            editor
+                .run_command(&format!("set {}", argument))
+                .map(|_| std::mem::take(&mut editor.status_message))
+        };
+
+        assert_eq!(Ok("".into()), set(&mut editor, "tm=300 udf so+=2"));
+        assert_eq!(
+            Ok("timeoutlen=300  undofile  scrolloff=7".into()),
+            set(&mut editor, "timeoutlen? undofile? so")
+        );
+        set(
+            &mut editor,
+            "invundofile cb=unnamed cb+=unnamedplus cb-=unnamed",
+        )
+        .unwrap();
+        assert_eq!(
+            Ok("clipboard=unnamedplus  scrolloff=7  timeoutlen=300".into()),
+            set(&mut editor, "")
+        );
+        set(&mut editor, "so& tm& cb=").unwrap();
+        assert_eq!(Ok("".into()), set(&mut editor, ""));
+        set(&mut editor, "undodir=/tmp/with\\ space").unwrap();
+        assert_eq!("/tmp/with space", editor.option("undodir").string());
+
+        assert_eq!(
+            Err("E518: Unknown option: frobnicate".into()),
+            set(&mut editor, "frobnicate")
+        );
+        assert_eq!(
+            Err("E521: Number required after =: sw=x".into()),
+            set(&mut editor, "sw=x")
+        );
+        assert_eq!(
+            Err("E474: Invalid argument: noscrolloff".into()),
+            set(&mut editor, "noscrolloff")
+        );
+        assert_eq!(
+            Err("E474: Invalid argument: searchcolor=red".into()),
+            set(&mut editor, "searchcolor=red")
+        );
+        assert_eq!(
+            Err("E474: Invalid argument: undofile=1".into()),
+            set(&mut editor, "undofile=1")
+        );
+        set(&mut editor, "so=9223372036854775807").unwrap();
+        assert_eq!(
+            Err("E521: Number required after =: so+=1".into()),
+            set(&mut editor, "so+=1")
+        );
+        assert_eq!(
+            Err("E521: Number required after =: so^=2".into()),
+            set(&mut editor, "so^=2")
+        );
+    }
+
+    #[test]
+    fn should_keep_buffer_local_options_with_their_buffer() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("one\ntwo");
+
+        editor.run_command("setlocal sw=2").unwrap();
+        editor.type_keys(">>");
+        assert_eq!("  one\ntwo", editor.buffer.as_string());
+        editor.run_command("setlocal noet").unwrap();
+        editor.type_keys("j>>");
+        assert_eq!("  one\n\ttwo", editor.buffer.as_string());
+
+        // a new buffer starts with the global value, :set changes both
+        editor.run_command("setglobal sw=8").unwrap();
+        assert_eq!(2, editor.option("shiftwidth").number());
+        editor.buffer = crate::buffer::Buffer::from("three");
+        assert_eq!(8, editor.option("shiftwidth").number());
+        assert!(editor.option("expandtab").boolean());
+        editor.run_command("set sw=3").unwrap();
+        editor.type_keys(">>");
+        assert_eq!("   three", editor.buffer.as_string());
+        editor.run_command("setglobal sw?").unwrap();
+        assert_eq!("shiftwidth=3", editor.status_message);
+    }
+}
@@ -0,0 +1,353 @@
+use crate::clipboard::clipboard_selection;
+use crate::editor::{Editor, Mode};
+use crate::operator::Region;
+use crate::visual::VisualKind;
+use crate::{Instant, UnicodeSegmentation};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegisterKind {
+    Chars,
+    // the text ends with a line break
+    Lines,
+    // one line of the text per line of the block
+    Block,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Register {
+    pub text: String,
+    pub kind: RegisterKind,
+}
+
+impl Register {
+    pub fn chars(text: &str) -> Self {
+        Register {
+            text: text.to_string(),
+            kind: RegisterKind::Chars,
+        }
+    }
+}
+
+// "" is whatever was stored last, "a to "z are written by name and appended to as "A to "Z, "0
+// has the last yank, "1 to "9 the last deletes and "- the last delete within a line. ". is the
+// text typed in the last insert session
+#[derive(Debug, Clone, Default)]
+pub struct Registers {
+    registers: HashMap<char, Register>,
+}
+
+impl Registers {
+    pub fn get(&self, name: char) -> Option<&Register> {
+        self.registers.get(&name.to_ascii_lowercase())
+    }
+
+    pub fn set(&mut self, name: char, register: Register) {
+        self.registers.insert(name, register);
+    }
+
+    pub fn yank(&mut self, name: Option<char>, register: Register) {
+        match name {
+            Some('_') => {}
+            Some(name) if is_writable(name) => self.store_named(name, register),
+            _ => {
+                self.set('0', register.clone());
+                self.set('"', register);
+            }
+        }
+    }
+
+    pub fn delete(&mut self, name: Option<char>, register: Register) {
+        match name {
+            Some('_') => {}
+            Some(name) if is_writable(name) => self.store_named(name, register),
+            _ => {
+                if register.kind == RegisterKind::Chars && !register.text.contains('\n') {
+                    self.set('-', register.clone());
+                } else {
+                    for n in (1..9).rev() {
+                        if let Some(older) = self.registers.remove(&digit(n)) {
+                            self.set(digit(n + 1), older);
+                        }
+                    }
+                    self.set('1', register.clone());
+                }
+                self.set('"', register);
+            }
+        }
+    }
+
+    // a recording goes to its register alone, "" keeps what it had unless it's the one recorded
+    pub fn record(&mut self, name: char, keys: &str) {
+        let register = Register::chars(keys);
+        let register = match self.get(name) {
+            Some(old) if name.is_ascii_uppercase() => append(old, register),
+            _ => register,
+        };
+        self.set(name.to_ascii_lowercase(), register);
+    }
+
+    fn store_named(&mut self, name: char, register: Register) {
+        let register = match self.get(name) {
+            Some(old) if name.is_ascii_uppercase() => append(old, register),
+            _ => register,
+        };
+        self.set(name.to_ascii_lowercase(), register.clone());
+        self.set('"', register);
+    }
+}
+
+// what can follow " in normal and visual mode
+pub fn is_register_name(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "\"-_.:/%+*".contains(c)
+}
+
+// the others are read-only, or "" and "_ which have their own rules
+fn is_writable(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-'
+}
+
+fn digit(n: u32) -> char {
+    std::char::from_digit(n, 10).unwrap()
+}
+
+// appending lines to anything makes it lines, appending to a block adds lines to the block
+fn append(old: &Register, new: Register) -> Register {
+    use RegisterKind::*;
+    let text = match (old.kind, new.kind) {
+        (Chars, Chars) | (Lines, Lines) => old.text.clone() + &new.text,
+        (Lines, _) => old.text.clone() + &new.text + "\n",
+        (_, Lines) => old.text.clone() + "\n" + &new.text,
+        _ => old.text.clone() + "\n" + &new.text,
+    };
+    let kind = if old.kind == Lines || new.kind == Lines {
+        Lines
+    } else if old.kind == Block || new.kind == Block {
+        Block
+    } else {
+        Chars
+    };
+    Register { text, kind }
+}
+
+impl Editor {
+    // also the registers that come from somewhere else: ": is the last command line, "/ the
+    // last search, "% the file being edited and "+ and "* the clipboard
+    pub fn register(&self, name: char) -> Option<Register> {
+        match name {
+            '+' | '*' => self.clipboard_register(name == '*'),
+            ':' => self
+                .command_history
+                .last()
+                .map(|line| Register::chars(line)),
+            '/' => self
+                .last_search
+                .as_ref()
+                .map(|search| Register::chars(&search.pattern)),
+            '%' if !self.editing_file_path.is_empty() => {
+                Some(Register::chars(&self.editing_file_path))
+            }
+            _ => self.registers.get(name).cloned(),
+        }
+    }
+
+    // the register typed before the command, if any, is used up by the first of these
+    pub(crate) fn yank_to_register(&mut self, register: Register) {
+        let name = self.register_name.take();
+        if let Some(primary) = clipboard_selection(name, self.unnamedplus()) {
+            self.set_clipboard(primary, &register);
+        }
+        match name {
+            Some('+') | Some('*') => self.registers.set('"', register),
+            _ => self.registers.yank(name, register),
+        }
+    }
+
+    pub(crate) fn delete_to_register(&mut self, register: Register) {
+        let name = self.register_name.take();
+        if let Some(primary) = clipboard_selection(name, self.unnamedplus()) {
+            self.set_clipboard(primary, &register);
+        }
+        match name {
+            Some('+') | Some('*') => self.registers.set('"', register),
+            _ => self.registers.delete(name, register),
+        }
+    }
+
+    // p and P, in visual mode they replace the selection
+    pub fn put(&mut self, before: bool, count: Option<usize>) -> Result<(), String> {
+        let default = if self.unnamedplus() { '+' } else { '"' };
+        let name = self.register_name.take().unwrap_or(default);
+        let register = self
+            .register(name)
+            .ok_or_else(|| format!("E353: Nothing in register {}", name))?;
+        let count = count.unwrap_or(1).max(1);
+        self.buffer.begin_undo_group();
+        match self.selection_region() {
+            Some(region) => self.put_over_selection(&register, region, count),
+            None => self.put_register(&register, before, count),
+        }
+        self.buffer.end_undo_group();
+        self.scroll_to_cursor();
+        self.cursor_animation_instant = Instant::now();
+        Ok(())
+    }
+
+    fn put_over_selection(&mut self, register: &Register, region: Region, count: usize) {
+        let was_lines = self.mode == Mode::VISUAL(VisualKind::Lines);
+        self.leave_visual_mode();
+        let deleted = self.region_register(&region);
+        self.delete_region(&region);
+        self.delete_to_register(deleted);
+        let (x, y) = self.buffer.cursor();
+        let start = match region {
+            Region::Chars(ref range) => range.start,
+            _ => self.buffer.offset_of(x, y),
+        };
+        match (was_lines, register.kind) {
+            // above the lines after the deleted ones, below the line before if they were the last
+            (true, _) => {
+                let before = match region {
+                    Region::Lines(first, _) => y == first,
+                    _ => true,
+                };
+                let register = match register.kind {
+                    RegisterKind::Lines => register.clone(),
+                    _ => Register {
+                        text: register.text.clone() + "\n",
+                        kind: RegisterKind::Lines,
+                    },
+                };
+                self.put_register(&register, before, count)
+            }
+            // lines put in the middle of a line split it around them
+            (false, RegisterKind::Lines) => {
+                self.buffer.insert(start, "\n");
+                self.put_register(register, false, count);
+            }
+            // the cursor can't stay past the end of the line, so text deleted from the end is
+            // put back after it
+            (false, _) => {
+                let at_end = start == self.buffer.line_end(y) && self.buffer.line_length(y) > 0;
+                self.put_register(register, !at_end, count);
+            }
+        }
+    }
+
+    fn put_register(&mut self, register: &Register, before: bool, count: usize) {
+        let (x, y) = self.buffer.cursor();
+        // p after an empty line puts where P would
+        let column = if before || self.buffer.line_length(y) == 0 {
+            x
+        } else {
+            x + 1
+        };
+        match register.kind {
+            RegisterKind::Chars => {
+                let text = register.text.repeat(count);
+                if text.is_empty() {
+                    return;
+                }
+                let offset = self.buffer.offset_of(column, y);
+                self.buffer.insert(offset, &text);
+                // the cursor goes to the last character put, or the first if there are lines
+                let (x, y) = if text.contains('\n') {
+                    self.buffer.position_of(offset)
+                } else {
+                    let (x, y) = self.buffer.position_of(offset + text.len());
+                    (x.saturating_sub(1), y)
+                };
+                self.buffer.set_cursor(x, y, Mode::NORMAL);
+            }
+            RegisterKind::Lines => {
+                let lines = register.text.strip_suffix('\n').unwrap_or(&register.text);
+                let text = vec![lines; count].join("\n");
+                let after = if before { y } else { y + 1 };
+                self.put_lines_below(after, &text);
+                let x = self.buffer.first_non_blank(after);
+                self.buffer.set_cursor(x, after, Mode::NORMAL);
+            }
+            RegisterKind::Block => {
+                let pieces: Vec<&str> = register.text.split('\n').collect();
+                let width = pieces
+                    .iter()
+                    .map(|piece| piece.graphemes(true).count())
+                    .max()
+                    .unwrap_or(0);
+                for (i, piece) in pieces.iter().enumerate() {
+                    let line_y = y + i;
+                    if line_y >= self.buffer.line_count() {
+                        self.buffer.insert(self.buffer.len(), "\n");
+                    }
+                    // every piece is padded to the width of the block so the text after it
+                    // stays lined up
+                    let padding = " ".repeat(width - piece.graphemes(true).count());
+                    let text = (piece.to_string() + &padding).repeat(count);
+                    let length = self.buffer.line_length(line_y);
+                    if column >= length {
+                        let end = self.buffer.line_end(line_y);
+                        let text = " ".repeat(column - length) + &text;
+                        self.buffer.insert(end, text.trim_end_matches(' '));
+                    } else {
+                        let offset = self.buffer.offset_of(column, line_y);
+                        self.buffer.insert(offset, &text);
+                    }
+                }
+                self.buffer.set_cursor(column, y, Mode::NORMAL);
+            }
+        }
+    }
+}
+
+mod test {
+    #[test]
+    fn should_store_and_put_registers() {
+        use crate::register::RegisterKind;
+        let text = |editor: &crate::editor::Editor, name: char| {
+            editor.register(name).map(|register| register.text)
+        };
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("one two\nthree\nfour\n");
+
+        editor.type_keys("\"ayw\"Ayy");
+        assert_eq!(Some("one \none two\n".into()), text(&editor, 'a'));
+        assert_eq!(
+            Some(RegisterKind::Lines),
+            editor.register('a').map(|r| r.kind)
+        );
+        editor.type_keys("dwjdd");
+        assert_eq!(Some("one ".into()), text(&editor, '-'));
+        assert_eq!(Some("three\n".into()), text(&editor, '1'));
+        assert_eq!(Some("three\n".into()), text(&editor, '"'));
+        editor.type_keys("dd");
+        assert_eq!(Some("four\n".into()), text(&editor, '1'));
+        assert_eq!(Some("three\n".into()), text(&editor, '2'));
+        assert_eq!("two\n", editor.buffer.as_string());
+
+        editor.type_keys("\"2P\"_dd");
+        assert_eq!("two\n", editor.buffer.as_string());
+        assert_eq!(Some("four\n".into()), text(&editor, '"'));
+        editor.type_keys("gg\"-2p");
+        assert_eq!("tone one wo\n", editor.buffer.as_string());
+        assert_eq!((8, 0), editor.buffer.cursor());
+        editor.type_keys("\"ap");
+        assert_eq!("tone one wo\none \none two\n", editor.buffer.as_string());
+        assert_eq!((0, 1), editor.buffer.cursor());
+
+        editor.buffer = crate::buffer::Buffer::from("ab\ncd\n");
+        editor.type_keys("\x16jy$p");
+        assert_eq!(
+            Some(RegisterKind::Block),
+            editor.register('0').map(|r| r.kind)
+        );
+        assert_eq!("aba\ncdc\n", editor.buffer.as_string());
+        editor.type_keys("vp");
+        assert_eq!("aba\ncdcc\n", editor.buffer.as_string());
+        assert_eq!(Some("a".into()), text(&editor, '"'));
+        editor.type_keys("ix\x1b:s/x/y/\n");
+        assert_eq!(Some("x".into()), text(&editor, '.'));
+        assert_eq!(Some("s/x/y/".into()), text(&editor, ':'));
+        assert_eq!(Some("x".into()), text(&editor, '/'));
+        assert_eq!(None, text(&editor, '%'));
+    }
+}
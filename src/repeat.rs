@@ -0,0 +1,146 @@
+use crate::command::Command;
+use crate::editor::{Editor, Mode};
+use crate::key::Key;
+use crate::min;
+use crate::visual::VisualKind;
+use std::cmp::max;
+
+// what . does again: the command, the size of the selection it worked on if it came from
+// visual mode, and what was typed if it started an insert session
+#[derive(Debug, Clone)]
+pub(crate) struct Change {
+    command: Command,
+    selection: Option<SelectionSize>,
+    inserted: String,
+}
+
+// a selection of the same size is made at the cursor when a visual mode change is repeated
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SelectionSize {
+    kind: VisualKind,
+    lines: usize,
+    // columns on the only line, or the column of the end on the last line
+    columns: usize,
+}
+
+impl Editor {
+    pub(crate) fn selection_size(&self) -> Option<SelectionSize> {
+        let kind = match self.mode {
+            Mode::VISUAL(kind) => kind,
+            _ => return None,
+        };
+        let (anchor_x, anchor_y) = self.visual_anchor;
+        let (x, y) = self.buffer.cursor();
+        let (start, end) = if (anchor_y, anchor_x) <= (y, x) {
+            ((anchor_x, anchor_y), (x, y))
+        } else {
+            ((x, y), (anchor_x, anchor_y))
+        };
+        let lines = end.1 - start.1;
+        let columns = match kind {
+            VisualKind::Chars if lines > 0 => end.0,
+            _ => max(x, anchor_x) - min(x, anchor_x) + 1,
+        };
+        Some(SelectionSize {
+            kind,
+            lines,
+            columns,
+        })
+    }
+
+    // commands that edited the buffer become the last change. one that started an insert
+    // session only does when the session ends, so it knows what was typed
+    pub(crate) fn record_change(
+        &mut self,
+        command: Command,
+        selection: Option<SelectionSize>,
+        states_before: usize,
+    ) {
+        let change = Change {
+            command,
+            selection,
+            inserted: String::new(),
+        };
+        if self.mode == Mode::INSERT {
+            self.pending_change = Some(change);
+        } else if self.buffer.undo_state_count() > states_before {
+            self.last_change = Some(change);
+        }
+    }
+
+    pub(crate) fn finish_pending_change(&mut self) {
+        if let Some(mut change) = self.pending_change.take() {
+            change.inserted = self.inserted_text.clone();
+            self.last_change = Some(change);
+        }
+    }
+
+    // . with a count replaces the count of the change, with a register its register
+    pub fn repeat_change(
+        &mut self,
+        count: Option<usize>,
+        register: Option<char>,
+    ) -> Result<(), String> {
+        let change = match self.last_change.clone() {
+            Some(change) => change,
+            None => return Ok(()),
+        };
+        let mut command = change.command;
+        command.count = count.or(command.count);
+        command.register = register.or(command.register);
+
+        if let Some(size) = change.selection {
+            let (x, y) = self.buffer.cursor();
+            let last = min(y + size.lines, self.buffer.line_count() - 1);
+            let end_x = match size.kind {
+                VisualKind::Chars if size.lines > 0 => size.columns,
+                _ => x + size.columns - 1,
+            };
+            self.visual_anchor = (x, y);
+            self.mode = Mode::VISUAL(size.kind);
+            self.buffer.set_cursor(end_x, last, self.mode);
+        }
+        self.execute(command)?;
+
+        if self.mode == Mode::INSERT {
+            let keys = change.inserted.chars().map(Key::from_char);
+            let result = self.without_mappings(|editor| editor.run_keys(keys));
+            self.leave_insert_mode();
+            result?;
+        }
+        Ok(())
+    }
+}
+
+mod test {
+    #[test]
+    fn should_repeat_the_last_change() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("one two three\nfour five six seven\n");
+
+        editor.type_keys("ciwXX\x1bw.");
+        assert_eq!(
+            "XX XX three\nfour five six seven\n",
+            editor.buffer.as_string()
+        );
+        editor.type_keys("j0dw.");
+        assert_eq!("XX XX three\nsix seven\n", editor.buffer.as_string());
+        editor.type_keys("uu2.");
+        assert_eq!("XX XX three\nsix seven\n", editor.buffer.as_string());
+        editor.type_keys("yw.");
+        assert_eq!("XX XX three\n\n", editor.buffer.as_string());
+        editor.type_keys("u");
+
+        editor.type_keys("ggvlu");
+        assert_eq!("xx XX three\nsix seven\n", editor.buffer.as_string());
+        editor.type_keys("w.");
+        assert_eq!("xx xx three\nsix seven\n", editor.buffer.as_string());
+        editor.type_keys("0ia\nb\x1bj0.");
+        assert_eq!(
+            "a\nbxx xx three\na\nbsix seven\n",
+            editor.buffer.as_string()
+        );
+        editor.type_keys("u");
+        assert_eq!("a\nbxx xx three\nsix seven\n", editor.buffer.as_string());
+    }
+}
@@ -0,0 +1,362 @@
+use crate::buffer::Buffer;
+use crate::editor::{Editor, Mode};
+use crate::motion::{class_of, graphemes_of, Class};
+use crate::{Instant, UnicodeSegmentation};
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Search {
+    pub pattern: String,
+    pub forward: bool,
+}
+
+// a vim style regular expression: ( ) | + ? { are literal unless escaped, \< and \> match at
+// the start and end of words, \c anywhere ignores case and \C doesn't
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    regex: Regex,
+}
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        Self::with_case(pattern, None)
+    }
+
+    // ignore_case overrides \c and \C when given, like the i and I flags of :s
+    pub fn with_case(pattern: &str, ignore_case: Option<bool>) -> Result<Self, String> {
+        let mut translated = String::new();
+        let mut ignore_pattern_case = false;
+        let mut in_braces = false;
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(c @ ('(' | ')' | '|' | '+' | '?')) => translated.push(c),
+                    Some('=') => translated.push('?'),
+                    Some('{') => {
+                        in_braces = true;
+                        translated.push('{');
+                    }
+                    Some('<') | Some('>') => translated.push_str("\\b"),
+                    Some('c') => ignore_pattern_case = true,
+                    Some('C') => ignore_pattern_case = false,
+                    Some(c @ ('s' | 'S' | 'd' | 'D' | 'w' | 'W' | 'n' | 't')) => {
+                        translated.push('\\');
+                        translated.push(c);
+                    }
+                    Some(c) => translated.push_str(&regex::escape(&c.to_string())),
+                    None => return Err("E10: \\ should be followed by /, ? or &".into()),
+                },
+                '}' if in_braces => {
+                    in_braces = false;
+                    translated.push('}');
+                }
+                '(' | ')' | '|' | '+' | '?' | '{' | '}' => {
+                    translated.push('\\');
+                    translated.push(c);
+                }
+                _ => translated.push(c),
+            }
+        }
+        let regex = RegexBuilder::new(&translated)
+            .case_insensitive(ignore_case.unwrap_or(ignore_pattern_case))
+            .build()
+            .map_err(|_| format!("E383: Invalid search string: {}", pattern))?;
+        Ok(Self { regex })
+    }
+
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    // byte ranges of the matches in a line, left to right and not overlapping
+    pub fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+        self.regex
+            .find_iter(line)
+            .filter(|found| !found.range().is_empty())
+            .map(|found| found.range())
+            .collect()
+    }
+}
+
+// makes text match itself literally in a pattern
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if "\\/.*$^~[]".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl Buffer {
+    // the next match after (or before, going backward) the given position, wrapping around the
+    // ends of the buffer. the bool says whether it wrapped. lines are searched one at a time so
+    // the text is never put together in one string
+    pub fn find(
+        &self,
+        pattern: &Pattern,
+        (x, y): (usize, usize),
+        forward: bool,
+    ) -> Option<((usize, usize), bool)> {
+        let line_count = self.line_count();
+        let cursor = self.offset_of(x, y) - self.line_start(y);
+        for step in 0..=line_count {
+            let line_y = if forward {
+                (y + step) % line_count
+            } else {
+                (y + line_count - step % line_count) % line_count
+            };
+            let wrapped = if forward { line_y < y } else { line_y > y };
+            let line = self.line(line_y);
+            let matches = pattern.find_all(&line);
+            let found = match (step, forward) {
+                (0, true) => matches.iter().find(|m| m.start > cursor),
+                (0, false) => matches.iter().rfind(|m| m.start < cursor),
+                // back on the starting line after going around everything else
+                (_, true) if step == line_count => matches.iter().find(|m| m.start <= cursor),
+                (_, false) if step == line_count => matches.iter().rfind(|m| m.start >= cursor),
+                (_, true) => matches.first(),
+                (_, false) => matches.last(),
+            };
+            if let Some(found) = found {
+                let wrapped = wrapped || step == line_count;
+                return Some(((column_of(&line, found.start), line_y), wrapped));
+            }
+        }
+        None
+    }
+
+    // columns in graphemes
+    pub fn line_matches(&self, pattern: &Pattern, y: usize) -> Vec<Range<usize>> {
+        let line = self.line(y);
+        pattern
+            .find_all(&line)
+            .into_iter()
+            .map(|found| column_of(&line, found.start)..column_of(&line, found.end))
+            .collect()
+    }
+}
+
+pub(crate) fn column_of(line: &str, byte: usize) -> usize {
+    UnicodeSegmentation::graphemes(&line[..byte], true).count()
+}
+
+impl Editor {
+    pub fn enter_search_mode(&mut self, forward: bool) {
+        self.mode = Mode::COMMAND;
+        self.command_prompt = if forward { '/' } else { '?' };
+        self.command_line.clear();
+        self.command_history_index = None;
+        self.status_message.clear();
+        self.search_start = (self.buffer.cursor(), self.y_render_offset);
+    }
+
+    // shows where the pattern typed so far would go
+    pub(crate) fn preview_search(&mut self) {
+        let ((x, y), y_render_offset) = self.search_start;
+        self.buffer.set_cursor(x, y, Mode::NORMAL);
+        self.y_render_offset = y_render_offset;
+        let pattern = match Pattern::new(&self.command_line) {
+            Ok(pattern) if !self.command_line.is_empty() => pattern,
+            _ => return,
+        };
+        if let Some(((x, y), _)) = self
+            .buffer
+            .find(&pattern, (x, y), self.command_prompt == '/')
+        {
+            self.buffer.set_cursor(x, y, Mode::NORMAL);
+            self.scroll_to_cursor();
+        }
+    }
+
+    pub(crate) fn cancel_search(&mut self) {
+        let ((x, y), y_render_offset) = self.search_start;
+        self.buffer.set_cursor(x, y, Mode::NORMAL);
+        self.y_render_offset = y_render_offset;
+    }
+
+    // an empty pattern searches for the last one again
+    pub fn execute_search(&mut self, pattern: &str, forward: bool) -> Result<(), String> {
+        self.cancel_search();
+        let pattern = if pattern.is_empty() {
+            match &self.last_search {
+                Some(search) => search.pattern.clone(),
+                None => return Err("E35: No previous regular expression".into()),
+            }
+        } else {
+            self.search_history.retain(|old| old != pattern);
+            self.search_history.push(pattern.to_string());
+            pattern.to_string()
+        };
+        self.last_search = Some(Search { pattern, forward });
+        self.highlight_search = true;
+        self.search_next(false)
+    }
+
+    // :s and :g take an empty pattern to mean the last one, and what they use becomes the last
+    // one for n and N, keeping its direction
+    pub(crate) fn remember_search_pattern(&mut self, pattern: String) -> Result<String, String> {
+        let pattern = if pattern.is_empty() {
+            match &self.last_search {
+                Some(search) => search.pattern.clone(),
+                None => return Err("E35: No previous regular expression".into()),
+            }
+        } else {
+            pattern
+        };
+        let forward = !matches!(&self.last_search, Some(search) if !search.forward);
+        self.last_search = Some(Search {
+            pattern: pattern.clone(),
+            forward,
+        });
+        Ok(pattern)
+    }
+
+    // n, and N with reverse
+    pub fn search_next(&mut self, reverse: bool) -> Result<(), String> {
+        let search = match &self.last_search {
+            Some(search) => search.clone(),
+            None => return Err("E35: No previous regular expression".into()),
+        };
+        self.highlight_search = true;
+        let forward = search.forward != reverse;
+        let pattern = Pattern::new(&search.pattern)?;
+        self.cursor_animation_instant = Instant::now();
+        match self.buffer.find(&pattern, self.buffer.cursor(), forward) {
+            Some(((x, y), wrapped)) => {
+                self.status_message = match (wrapped, forward) {
+                    (false, _) => format!("{}{}", if forward { '/' } else { '?' }, search.pattern),
+                    (true, true) => "search hit BOTTOM, continuing at TOP".into(),
+                    (true, false) => "search hit TOP, continuing at BOTTOM".into(),
+                };
+                self.push_jump();
+                self.buffer.set_cursor(x, y, Mode::NORMAL);
+                self.scroll_to_cursor();
+                Ok(())
+            }
+            None => Err(format!("E486: Pattern not found: {}", search.pattern)),
+        }
+    }
+
+    // * and #: the word under or after the cursor as a whole word
+    pub fn search_word_under_cursor(&mut self, forward: bool) -> Result<(), String> {
+        let (x, y) = self.buffer.cursor();
+        let line = graphemes_of(&self.buffer, y);
+        let is_keyword = |g: &str| !matches!(class_of(g, false), Class::Blank | Class::Punctuation);
+        let start = (x..line.len())
+            .find(|&i| is_keyword(&line[i]))
+            .ok_or("E348: No string under cursor")?;
+        let class = class_of(&line[start], false);
+        let mut word_start = start;
+        while word_start > 0 && class_of(&line[word_start - 1], false) == class {
+            word_start -= 1;
+        }
+        let word_end = (start..line.len())
+            .find(|&i| class_of(&line[i], false) != class)
+            .unwrap_or(line.len());
+        let pattern = format!("\\<{}\\>", escape(&line[word_start..word_end].concat()));
+
+        // searching backward starts from the beginning of the word so it isn't found again
+        self.buffer.set_cursor(word_start, y, Mode::NORMAL);
+        self.search_history.retain(|old| *old != pattern);
+        self.search_history.push(pattern.clone());
+        self.last_search = Some(Search { pattern, forward });
+        self.search_next(false)
+    }
+
+    // what to draw as matches: what is being typed after / or ?, otherwise the last search until
+    // :nohlsearch. the ui asks every frame, so the pattern is only compiled again when it changes
+    pub fn highlighted_pattern(&self) -> Option<Pattern> {
+        let pattern = match (&self.mode, &self.last_search) {
+            (Mode::COMMAND, _) if self.command_prompt != ':' => &self.command_line,
+            (_, Some(search)) if self.highlight_search => &search.pattern,
+            _ => return None,
+        };
+        if pattern.is_empty() {
+            return None;
+        }
+        let mut highlighted = self.highlighted.borrow_mut();
+        if let Some((text, compiled)) = &*highlighted {
+            if text == pattern {
+                return compiled.clone();
+            }
+        }
+        let compiled = Pattern::new(pattern).ok();
+        *highlighted = Some((pattern.clone(), compiled.clone()));
+        compiled
+    }
+}
+
+mod test {
+    #[test]
+    fn should_find_matches_across_lines_and_wrap() {
+        use crate::search::Pattern;
+        let buffer = crate::buffer::Buffer::from("foo bar\nbarfoo\nfoo_x foo");
+        let pattern = Pattern::new("foo").unwrap();
+        assert_eq!(Some(((3, 1), false)), buffer.find(&pattern, (0, 0), true));
+        assert_eq!(Some(((0, 0), true)), buffer.find(&pattern, (6, 2), true));
+        assert_eq!(Some(((6, 2), true)), buffer.find(&pattern, (0, 0), false));
+        assert_eq!(Some(((0, 2), false)), buffer.find(&pattern, (6, 2), false));
+        let pattern = Pattern::new("\\<foo\\>").unwrap();
+        assert_eq!(Some(((6, 2), false)), buffer.find(&pattern, (0, 0), true));
+        assert_eq!(vec![6..9], buffer.line_matches(&pattern, 2));
+        assert_eq!(
+            None,
+            buffer.find(&Pattern::new("baz").unwrap(), (0, 0), true)
+        );
+        let buffer = crate::buffer::Buffer::from("日本 x 日本");
+        assert_eq!(
+            Some(((5, 0), false)),
+            buffer.find(&Pattern::new("日本").unwrap(), (0, 0), true)
+        );
+        assert_eq!(
+            Some(((0, 0), true)),
+            buffer.find(&Pattern::new("日本").unwrap(), (5, 0), true)
+        );
+    }
+
+    #[test]
+    fn should_search_incrementally_and_repeat() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("alpha beta\nbeta gamma\nalphabet alpha");
+
+        editor.type_keys("/gam");
+        assert_eq!((5, 1), editor.buffer.cursor());
+        let pattern = editor.highlighted_pattern().unwrap();
+        assert_eq!(vec![5..8], editor.buffer.line_matches(&pattern, 1));
+        editor.type_keys("m");
+        let pattern = editor.highlighted_pattern().unwrap();
+        assert_eq!(vec![5..9], editor.buffer.line_matches(&pattern, 1));
+        editor.type_keys("\x1b");
+        assert_eq!((0, 0), editor.buffer.cursor());
+        assert!(editor.highlighted_pattern().is_none());
+
+        editor.type_keys("/beta\n");
+        assert_eq!((6, 0), editor.buffer.cursor());
+        editor.type_keys("n");
+        assert_eq!((0, 1), editor.buffer.cursor());
+        assert_eq!("/beta", editor.status_message);
+        editor.type_keys("N");
+        assert_eq!((6, 0), editor.buffer.cursor());
+        editor.type_keys("?\n");
+        assert_eq!((0, 1), editor.buffer.cursor());
+        assert_eq!(
+            "search hit TOP, continuing at BOTTOM",
+            editor.status_message
+        );
+
+        editor.type_keys("gg*");
+        assert_eq!((9, 2), editor.buffer.cursor());
+        editor.type_keys("#");
+        assert_eq!((0, 0), editor.buffer.cursor());
+        assert_eq!("\\<alpha\\>", editor.last_search.as_ref().unwrap().pattern);
+        editor.type_keys("/nope\n");
+        assert_eq!("E486: Pattern not found: nope", editor.status_message);
+        assert_eq!((0, 0), editor.buffer.cursor());
+        editor.type_keys(":noh\n");
+        assert!(editor.highlighted_pattern().is_none());
+    }
+}
@@ -0,0 +1,422 @@
+use crate::editor::{Editor, Mode};
+use crate::key::Key;
+use crate::search::{column_of, Pattern};
+use crate::Instant;
+use regex::Regex;
+use std::ops::Range;
+
+// a :s in progress. with the c flag it waits in CONFIRM mode on every match
+#[derive(Debug, Clone)]
+pub(crate) struct Substitution {
+    regex: Regex,
+    replacement: String,
+    global: bool,
+    confirm: bool,
+    // the line being worked on, the last line of the range and where in the line the next
+    // match may start
+    y: usize,
+    last: usize,
+    from: usize,
+    // the match waiting for an answer
+    current: Option<Range<usize>>,
+    count: usize,
+    lines: usize,
+    last_changed_line: Option<usize>,
+}
+
+impl Editor {
+    // s/pattern/replacement/flags with lines first..=last, 0-based. flags are g for every match
+    // in a line, c to confirm each one, i and I to ignore case or not
+    pub fn substitute(&mut self, first: usize, last: usize, argument: &str) -> Result<(), String> {
+        let mut chars = argument.chars();
+        let delimiter = match chars.next() {
+            Some(c) if !c.is_alphanumeric() && c != '\\' && c != '"' && c != '|' => c,
+            _ => return Err("E146: Regular expressions can't be delimited by letters".into()),
+        };
+        let rest = chars.as_str();
+        let (pattern, rest) = split_delimited(rest, delimiter);
+        let (replacement, flags) = match rest {
+            Some(rest) => split_delimited(rest, delimiter),
+            None => (String::new(), None),
+        };
+        let flags = flags.unwrap_or_default();
+
+        let mut ignore_case = None;
+        let (mut global, mut confirm) = (false, false);
+        for flag in flags.trim().chars() {
+            match flag {
+                'g' => global = true,
+                'c' => confirm = true,
+                'i' => ignore_case = Some(true),
+                'I' => ignore_case = Some(false),
+                _ => return Err(format!("E488: Trailing characters: {}", flag)),
+            }
+        }
+
+        let pattern = self.remember_search_pattern(pattern)?;
+        let regex = Pattern::with_case(&pattern, ignore_case)?.regex().clone();
+
+        self.buffer.begin_undo_group();
+        self.substitution = Some(Substitution {
+            regex,
+            replacement,
+            global,
+            // :g can't wait for answers, so it replaces everything
+            confirm: confirm && !self.in_global,
+            y: first,
+            last,
+            from: 0,
+            current: None,
+            count: 0,
+            lines: 0,
+            last_changed_line: None,
+        });
+        self.continue_substitution();
+        if self.mode == Mode::CONFIRM {
+            return Ok(());
+        }
+        let found = self.substitution_count() != Some(0);
+        self.finish_substitution();
+        // lines :g picked that have nothing to substitute aren't errors
+        if found || self.in_global {
+            Ok(())
+        } else {
+            Err(format!("E486: Pattern not found: {}", pattern))
+        }
+    }
+
+    // y replaces, n skips, a replaces this and the rest, l replaces this one and stops, q and
+    // escape stop
+    pub(crate) fn handle_input_in_confirm_mode(&mut self, key: Key) {
+        self.pending_keys.clear();
+        match key.to_string().as_str() {
+            "y" => self.answer_substitution(true, false),
+            "n" => self.answer_substitution(false, false),
+            "a" => {
+                if let Some(substitution) = &mut self.substitution {
+                    substitution.confirm = false;
+                }
+                self.answer_substitution(true, false);
+            }
+            "l" => self.answer_substitution(true, true),
+            "q" | "<Esc>" => self.finish_substitution(),
+            _ => {}
+        }
+    }
+
+    // the match being asked about, in columns of line y
+    pub fn confirming_match(&self, y: usize) -> Option<Range<usize>> {
+        let substitution = self.substitution.as_ref()?;
+        let found = substitution.current.clone()?;
+        if substitution.y != y {
+            return None;
+        }
+        let line = self.buffer.line(y);
+        Some(column_of(&line, found.start)..column_of(&line, found.end))
+    }
+
+    fn substitution_count(&self) -> Option<usize> {
+        self.substitution
+            .as_ref()
+            .map(|substitution| substitution.count)
+    }
+
+    fn answer_substitution(&mut self, replace: bool, stop: bool) {
+        let found = match self.substitution.as_mut().and_then(|s| s.current.take()) {
+            Some(found) => found,
+            None => return,
+        };
+        if replace {
+            self.replace_match(found);
+        } else {
+            self.skip_match(found);
+        }
+        if stop {
+            self.finish_substitution();
+        } else {
+            self.continue_substitution();
+        }
+    }
+
+    // replaces matches until one needs confirming or there are none left
+    fn continue_substitution(&mut self) {
+        loop {
+            let found = match self.next_substitution_match() {
+                Some(found) => found,
+                None => {
+                    if self.mode == Mode::CONFIRM {
+                        self.finish_substitution();
+                    }
+                    return;
+                }
+            };
+            let substitution = self.substitution.as_mut().unwrap();
+            if substitution.confirm {
+                let (y, replacement) = (substitution.y, substitution.replacement.clone());
+                substitution.current = Some(found.clone());
+                let x = self.confirming_match(y).map_or(0, |columns| columns.start);
+                self.buffer.set_cursor(x, y, Mode::NORMAL);
+                self.scroll_to_cursor();
+                self.mode = Mode::CONFIRM;
+                self.status_message = format!("replace with {} (y/n/a/q/l)?", replacement);
+                return;
+            }
+            self.replace_match(found);
+        }
+    }
+
+    fn next_substitution_match(&mut self) -> Option<Range<usize>> {
+        let substitution = self.substitution.as_mut()?;
+        while substitution.y <= substitution.last {
+            let line = self.buffer.line(substitution.y);
+            if substitution.from <= line.len() {
+                if let Some(found) = substitution.regex.find_at(&line, substitution.from) {
+                    return Some(found.range());
+                }
+            }
+            substitution.y += 1;
+            substitution.from = 0;
+        }
+        None
+    }
+
+    fn replace_match(&mut self, found: Range<usize>) {
+        let substitution = self.substitution.as_mut().unwrap();
+        let y = substitution.y;
+        let line = self.buffer.line(y);
+        let mut locations = substitution.regex.capture_locations();
+        if substitution
+            .regex
+            .captures_read_at(&mut locations, &line, found.start)
+            .is_none()
+        {
+            return;
+        }
+        let groups: Vec<&str> = (0..locations.len())
+            .map(|i| {
+                locations
+                    .get(i)
+                    .map_or("", |(start, end)| &line[start..end])
+            })
+            .collect();
+        let replacement = expand_replacement(&substitution.replacement, &groups);
+        let start = self.buffer.line_start(y) + found.start;
+        self.buffer.delete(start..start + found.len());
+        self.buffer.insert(start, &replacement);
+
+        substitution.count += 1;
+        if substitution.last_changed_line != Some(y) {
+            substitution.lines += 1;
+        }
+        // line breaks in the replacement push the rest of the range down
+        let breaks = replacement.matches('\n').count();
+        substitution.y += breaks;
+        substitution.last += breaks;
+        substitution.last_changed_line = Some(substitution.y);
+        let after = match replacement.rfind('\n') {
+            Some(index) => replacement.len() - index - 1,
+            None => found.start + replacement.len(),
+        };
+        let line = self.buffer.line(substitution.y);
+        let skip = if found.is_empty() {
+            line[after..].chars().next().map_or(1, char::len_utf8)
+        } else {
+            0
+        };
+        substitution.from = after + skip;
+        if !substitution.global {
+            substitution.y += 1;
+            substitution.from = 0;
+        }
+    }
+
+    fn skip_match(&mut self, found: Range<usize>) {
+        let substitution = self.substitution.as_mut().unwrap();
+        let line = self.buffer.line(substitution.y);
+        let skip = if found.is_empty() {
+            line[found.end..].chars().next().map_or(1, char::len_utf8)
+        } else {
+            0
+        };
+        substitution.from = found.end + skip;
+        if !substitution.global {
+            substitution.y += 1;
+            substitution.from = 0;
+        }
+    }
+
+    fn finish_substitution(&mut self) {
+        let substitution = match self.substitution.take() {
+            Some(substitution) => substitution,
+            None => return,
+        };
+        self.buffer.end_undo_group();
+        self.mode = Mode::NORMAL;
+        if let Some(y) = substitution.last_changed_line {
+            let x = self.buffer.first_non_blank(y);
+            self.buffer.set_cursor(x, y, Mode::NORMAL);
+            self.scroll_to_cursor();
+        }
+        self.status_message = format!(
+            "{} substitution{} on {} line{}",
+            substitution.count,
+            if substitution.count == 1 { "" } else { "s" },
+            substitution.lines,
+            if substitution.lines == 1 { "" } else { "s" },
+        );
+        self.cursor_animation_instant = Instant::now();
+    }
+}
+
+// the text up to an unescaped delimiter, with the delimiter unescaped, and whatever comes after
+// the delimiter if there was one
+pub(crate) fn split_delimited(text: &str, delimiter: char) -> (String, Option<&str>) {
+    let mut result = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == delimiter {
+            return (result, Some(&text[i + c.len_utf8()..]));
+        }
+        if c == '\\' {
+            match chars.next() {
+                Some((_, next)) if next == delimiter => result.push(next),
+                Some((_, next)) => {
+                    result.push('\\');
+                    result.push(next);
+                }
+                None => result.push('\\'),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    (result, None)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Case {
+    Keep,
+    Upper,
+    Lower,
+}
+
+// & and \0 are the whole match, \1 to \9 groups, \r and \n break the line. \u and \l change the
+// case of the next character, \U and \L of everything until \E or \e
+fn expand_replacement(replacement: &str, groups: &[&str]) -> String {
+    let mut result = String::new();
+    let (mut case, mut next_case) = (Case::Keep, Case::Keep);
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        let text = match c {
+            '&' => groups[0].to_string(),
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => {
+                    let group = digit as usize - '0' as usize;
+                    groups.get(group).copied().unwrap_or("").to_string()
+                }
+                Some('r') | Some('n') => "\n".into(),
+                Some('t') => "\t".into(),
+                Some('u') => {
+                    next_case = Case::Upper;
+                    continue;
+                }
+                Some('l') => {
+                    next_case = Case::Lower;
+                    continue;
+                }
+                Some(modifier @ 'U') | Some(modifier @ 'L') | Some(modifier @ 'E')
+                | Some(modifier @ 'e') => {
+                    case = match modifier {
+                        'U' => Case::Upper,
+                        'L' => Case::Lower,
+                        _ => Case::Keep,
+                    };
+                    continue;
+                }
+                Some(other) => other.to_string(),
+                None => "\\".into(),
+            },
+            _ => c.to_string(),
+        };
+        for c in text.chars() {
+            let applied = if next_case != Case::Keep {
+                next_case
+            } else {
+                case
+            };
+            next_case = Case::Keep;
+            match applied {
+                Case::Keep => result.push(c),
+                Case::Upper => result.extend(c.to_uppercase()),
+                Case::Lower => result.extend(c.to_lowercase()),
+            }
+        }
+    }
+    result
+}
+
+mod test {
+    #[test]
+    fn should_substitute_with_groups_and_case_changes() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("foo bar foo\nbar foo\nbaz\n");
+
+        editor.execute_command_line("%s/foo/qux/").unwrap();
+        assert_eq!("qux bar foo\nbar qux\nbaz\n", editor.buffer.as_string());
+        assert_eq!("2 substitutions on 2 lines", editor.status_message);
+        assert_eq!((0, 1), editor.buffer.cursor());
+        editor.undo();
+        assert_eq!("foo bar foo\nbar foo\nbaz\n", editor.buffer.as_string());
+
+        editor
+            .execute_command_line("1s/\\(f\\)\\(o\\+\\)/\\u\\2-&-\\U\\1\\ex/g")
+            .unwrap();
+        assert_eq!(
+            "Oo-foo-Fx bar Oo-foo-Fx\nbar foo\nbaz\n",
+            editor.buffer.as_string()
+        );
+        editor.undo();
+        editor
+            .execute_command_line("2,3s#ba\\(.\\)#[\\1]\\r#")
+            .unwrap();
+        assert_eq!("foo bar foo\n[r]\n foo\n[z]\n\n", editor.buffer.as_string());
+        editor.undo();
+        editor.execute_command_line(":s/X*/-/g").unwrap();
+        assert_eq!(
+            "-f-o-o- -b-a-r- -f-o-o-\nbar foo\nbaz\n",
+            editor.buffer.as_string()
+        );
+        editor.undo();
+        assert_eq!(
+            Err("E486: Pattern not found: BAR".into()),
+            editor.execute_command_line("%s/BAR/x/I")
+        );
+        editor.execute_command_line("%s/BAR/x/i").unwrap();
+        assert_eq!("foo x foo\nx foo\nbaz\n", editor.buffer.as_string());
+    }
+
+    #[test]
+    fn should_confirm_each_substitution() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("a a\na a\na a\n");
+
+        editor.type_keys(":%s/a/b/gc\n");
+        assert_eq!(crate::editor::Mode::CONFIRM, editor.mode);
+        assert_eq!(Some(0..1), editor.confirming_match(0));
+        editor.type_keys("yn");
+        assert_eq!(Some(0..1), editor.confirming_match(1));
+        editor.type_keys("ny");
+        assert_eq!(Some(0..1), editor.confirming_match(2));
+        editor.type_keys("a");
+        assert_eq!("b a\na b\nb b\n", editor.buffer.as_string());
+        assert_eq!("4 substitutions on 3 lines", editor.status_message);
+        assert_eq!(crate::editor::Mode::NORMAL, editor.mode);
+        editor.type_keys("u");
+        assert_eq!("a a\na a\na a\n", editor.buffer.as_string());
+
+        editor.type_keys(":%s/a/b/c\nnl");
+        assert_eq!("a a\nb a\na a\n", editor.buffer.as_string());
+        editor.type_keys(":%s/a/b/gc\ny\x1bdl");
+        assert_eq!(" a\nb a\na a\n", editor.buffer.as_string());
+    }
+}
@@ -0,0 +1,287 @@
+use crate::editor::Editor;
+use crate::window::{Layout, Window};
+
+// windows with a layout of their own. the current tab page's are the editor's
+#[derive(Debug, Clone)]
+pub struct TabPage {
+    pub(crate) layout: Layout,
+    pub(crate) windows: Vec<Window>,
+    pub(crate) current_window: usize,
+}
+
+impl Editor {
+    pub fn tab_count(&self) -> usize {
+        self.other_tabs.len() + 1
+    }
+
+    // the current window is saved with its tab page
+    fn take_current_tab(&mut self) -> TabPage {
+        self.save_current_window();
+        TabPage {
+            layout: std::mem::replace(&mut self.layout, Layout::Window(0)),
+            windows: std::mem::take(&mut self.windows),
+            current_window: self.current_window,
+        }
+    }
+
+    fn show_tab(&mut self, tab: TabPage) -> Result<(), String> {
+        self.layout = tab.layout;
+        self.windows = tab.windows;
+        self.current_window = tab.current_window;
+        self.enter_window(tab.current_window)
+    }
+
+    // counting from 0
+    pub(crate) fn go_to_tab(&mut self, index: usize) -> Result<(), String> {
+        if index == self.current_tab {
+            return Ok(());
+        }
+        let current = self.take_current_tab();
+        self.other_tabs.insert(self.current_tab, current);
+        let tab = self.other_tabs.remove(index);
+        self.current_tab = index;
+        self.show_tab(tab)
+    }
+
+    // gt goes to the next tab page, or to the Nth with a count. gT goes back that many
+    pub(crate) fn tab_command(
+        &mut self,
+        count: Option<usize>,
+        forward: bool,
+    ) -> Result<(), String> {
+        match (count, forward) {
+            (Some(number), true) if number > self.tab_count() => Err(String::new()),
+            (Some(number), true) => self.go_to_tab(number - 1),
+            (count, _) => self.next_tab(count.unwrap_or(1), forward),
+        }
+    }
+
+    // going around past the last tab page or the first
+    pub fn next_tab(&mut self, count: usize, forward: bool) -> Result<(), String> {
+        let tabs = self.tab_count();
+        let count = count % tabs;
+        let index = if forward {
+            self.current_tab + count
+        } else {
+            self.current_tab + tabs - count
+        };
+        self.go_to_tab(index % tabs)
+    }
+
+    // the tab page a command was given, the current one without an argument
+    pub(crate) fn tab_index(&self, argument: &str) -> Result<usize, String> {
+        let invalid = || format!("E474: Invalid argument: {}", argument);
+        match argument {
+            "" => Ok(self.current_tab),
+            "$" => Ok(self.other_tabs.len()),
+            _ => match argument.parse::<usize>() {
+                Ok(number) if (1..=self.tab_count()).contains(&number) => Ok(number - 1),
+                _ => Err(invalid()),
+            },
+        }
+    }
+
+    // :tabnew, after the current tab page with a window on an empty buffer, or on the file
+    pub fn new_tab(&mut self, path: &str) -> Result<(), String> {
+        let current = self.take_current_tab();
+        self.other_tabs.insert(self.current_tab, current);
+        self.current_tab += 1;
+        self.last_window_id += 1;
+        let id = self.last_window_id;
+        self.new_buffer();
+        self.layout = Layout::Window(id);
+        self.windows = vec![Window {
+            id,
+            buffer_number: self.buffer_number,
+            cursor: (0, 0),
+            y_render_offset: 0,
+        }];
+        self.current_window = id;
+        match path {
+            "" => Ok(()),
+            path => self.edit_file(path),
+        }
+    }
+
+    // :tabclose. the buffers of its windows stay in the buffer list with their changes, and the
+    // tab page after it becomes the current one, or the one before when it was the last
+    pub fn close_tab(&mut self, argument: &str) -> Result<(), String> {
+        let index = self.tab_index(argument)?;
+        if self.other_tabs.is_empty() {
+            return Err("E784: Cannot close last tab page".into());
+        }
+        if index != self.current_tab {
+            if index < self.current_tab {
+                self.other_tabs.remove(index);
+                self.current_tab -= 1;
+            } else {
+                self.other_tabs.remove(index - 1);
+            }
+            return Ok(());
+        }
+        self.save_current_window();
+        let next = self.current_tab.min(self.other_tabs.len() - 1);
+        let tab = self.other_tabs.remove(next);
+        self.current_tab = next;
+        self.show_tab(tab)
+    }
+
+    // :tabmove N puts the current tab page after the Nth, 0 for the first. +N and -N move it
+    // by that many, and it's moved to the end without N
+    pub fn move_tab(&mut self, argument: &str) -> Result<(), String> {
+        let invalid = || format!("E474: Invalid argument: {}", argument);
+        let last = self.other_tabs.len();
+        let index = match argument {
+            "" | "$" => last,
+            _ if argument.starts_with('+') || argument.starts_with('-') => {
+                let offset = match &argument[1..] {
+                    "" => 1,
+                    offset => offset.parse().map_err(|_| invalid())?,
+                };
+                let index = if argument.starts_with('+') {
+                    self.current_tab.checked_add(offset)
+                } else {
+                    self.current_tab.checked_sub(offset)
+                };
+                index.filter(|&index| index <= last).ok_or_else(invalid)?
+            }
+            _ => {
+                let number: usize = argument.parse().map_err(|_| invalid())?;
+                if number > self.current_tab {
+                    (number - 1).min(last)
+                } else {
+                    number
+                }
+            }
+        };
+        self.current_tab = index;
+        Ok(())
+    }
+
+    // windows of the other tab pages on a buffer that's going away are closed, with the tab
+    // pages that are left without any
+    pub(crate) fn close_tab_windows_of_buffer(&mut self, number: usize) {
+        let mut index = 0;
+        while index < self.other_tabs.len() {
+            let tab = &mut self.other_tabs[index];
+            let closed: Vec<_> = tab
+                .windows
+                .iter()
+                .filter(|window| window.buffer_number == number)
+                .map(|window| window.id)
+                .collect();
+            if closed.len() == tab.windows.len() {
+                self.other_tabs.remove(index);
+                if index < self.current_tab {
+                    self.current_tab -= 1;
+                }
+                continue;
+            }
+            for id in closed {
+                tab.layout.remove(id);
+                tab.windows.retain(|window| window.id != id);
+            }
+            if !tab
+                .windows
+                .iter()
+                .any(|window| window.id == tab.current_window)
+            {
+                tab.current_window = tab.windows[0].id;
+            }
+            index += 1;
+        }
+    }
+
+    // for the tab line, the buffer of each tab page's current window and how many windows it
+    // has, with whether it's the current tab page
+    pub fn tab_labels(&self) -> Vec<(String, bool)> {
+        let label = |windows: usize, buffer_number| match windows {
+            1 => self.buffer_name(buffer_number).to_string(),
+            _ => format!("{} {}", windows, self.buffer_name(buffer_number)),
+        };
+        let mut labels: Vec<_> = self
+            .other_tabs
+            .iter()
+            .map(|tab| {
+                let current = tab
+                    .windows
+                    .iter()
+                    .find(|window| window.id == tab.current_window);
+                (
+                    label(tab.windows.len(), current.unwrap().buffer_number),
+                    false,
+                )
+            })
+            .collect();
+        let current = label(self.windows.len(), self.buffer_number);
+        labels.insert(self.current_tab, (current, true));
+        labels
+    }
+}
+
+mod test {
+    #[test]
+    fn should_keep_a_window_layout_in_each_tab_page() {
+        let labels = |editor: &crate::editor::Editor| {
+            let labels = editor.tab_labels().into_iter();
+            labels
+                .map(|(label, current)| {
+                    if current {
+                        format!("*{}", label)
+                    } else {
+                        label
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("one\ntwo");
+
+        editor.type_keys(":vsplit<CR>j:tabnew<CR>inew<Esc>");
+        assert_eq!(vec!["2 [No Name]", "*[No Name]"], labels(&editor));
+        // the tab line takes the first line
+        let rects = editor.window_rects(&editor.current_display_info);
+        assert_eq!(1, rects.len());
+        assert_eq!((1, 38), (rects[0].1.y, rects[0].1.height));
+
+        editor.type_keys("gt");
+        assert_eq!(2, editor.windows.len());
+        assert_eq!("one\ntwo", editor.buffer.as_string());
+        assert_eq!((0, 1), editor.buffer.cursor());
+        editor.type_keys(":tabnew<CR>:tabmove 0<CR>");
+        assert_eq!(
+            vec!["*[No Name]", "2 [No Name]", "[No Name]"],
+            labels(&editor)
+        );
+        editor.type_keys(":tabm<CR>gT");
+        assert_eq!(
+            vec!["2 [No Name]", "*[No Name]", "[No Name]"],
+            labels(&editor)
+        );
+        assert_eq!("new", editor.buffer.as_string());
+        // with a count gt goes to that tab page, gT goes back that many
+        editor.type_keys("3gt");
+        assert_eq!(2, editor.current_tab);
+        editor.type_keys("2gT4gt");
+        assert_eq!(0, editor.current_tab);
+        editor.type_keys("2gt");
+        assert_eq!("new", editor.buffer.as_string());
+        assert_eq!(
+            Err("E474: Invalid argument: +2".into()),
+            editor.run_command("tabmove +2")
+        );
+
+        // closing the last window closes the tab page, and the buffer stays listed
+        editor.type_keys(":q<CR>");
+        assert_eq!(vec!["2 [No Name]", "*[No Name]"], labels(&editor));
+        assert!(!editor.quit);
+        editor.type_keys(":tabclose<CR>");
+        assert_eq!(vec!["*2 [No Name]"], labels(&editor));
+        assert_eq!((0, 1), editor.buffer.cursor());
+        assert_eq!(
+            Err("E784: Cannot close last tab page".into()),
+            editor.run_command("tabclose")
+        );
+        assert_eq!(2, editor.other_buffers.len());
+    }
+}
@@ -0,0 +1,472 @@
+use crate::buffer::Buffer;
+use crate::min;
+use crate::motion::{class_of, graphemes_of, sentence_starts, Class, Walker};
+use crate::operator::Region;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextObjectKind {
+    Word,
+    BigWord,
+    Sentence,
+    Paragraph,
+    Quote(char),
+    Bracket(char, char),
+    Tag,
+}
+
+// i{object} selects the inside, a{object} also takes the delimiters or surrounding blanks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextObject {
+    pub kind: TextObjectKind,
+    pub around: bool,
+}
+
+pub fn parse_text_object(keys: &str) -> Option<TextObject> {
+    let mut chars = keys.chars();
+    let around = match chars.next()? {
+        'i' => false,
+        'a' => true,
+        _ => return None,
+    };
+    let kind = match chars.next()? {
+        'w' => TextObjectKind::Word,
+        'W' => TextObjectKind::BigWord,
+        's' => TextObjectKind::Sentence,
+        'p' => TextObjectKind::Paragraph,
+        c @ ('"' | '\'' | '`') => TextObjectKind::Quote(c),
+        '(' | ')' | 'b' => TextObjectKind::Bracket('(', ')'),
+        '[' | ']' => TextObjectKind::Bracket('[', ']'),
+        '{' | '}' | 'B' => TextObjectKind::Bracket('{', '}'),
+        '<' | '>' => TextObjectKind::Bracket('<', '>'),
+        't' => TextObjectKind::Tag,
+        _ => return None,
+    };
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(TextObject { kind, around })
+}
+
+impl Buffer {
+    // the region the object covers around the cursor, None when the cursor isn't in one
+    pub fn text_object_region(&self, object: TextObject, count: Option<usize>) -> Option<Region> {
+        let (x, y) = self.cursor();
+        let count = count.unwrap_or(1).max(1);
+        match object.kind {
+            TextObjectKind::Word => self.word_object(x, y, count, object.around, false),
+            TextObjectKind::BigWord => self.word_object(x, y, count, object.around, true),
+            TextObjectKind::Sentence => self.sentence_object(x, y, object.around),
+            TextObjectKind::Paragraph => self.paragraph_object(y, count, object.around),
+            TextObjectKind::Quote(quote) => self.quote_object(x, y, quote, object.around),
+            TextObjectKind::Bracket(open, close) => {
+                self.bracket_object(x, y, open, close, count, object.around)
+            }
+            TextObjectKind::Tag => self.tag_object(x, y, count, object.around),
+        }
+    }
+
+    fn word_object(
+        &self,
+        x: usize,
+        y: usize,
+        count: usize,
+        around: bool,
+        big: bool,
+    ) -> Option<Region> {
+        let line = graphemes_of(self, y);
+        if line.is_empty() {
+            return None;
+        }
+        let x = min(x, line.len() - 1);
+        let class = |i: usize| class_of(&line[i], big);
+        // last index of the run of graphemes of the same class starting at i
+        let run_end = |i: usize| {
+            let mut end = i;
+            while end + 1 < line.len() && class(end + 1) == class(i) {
+                end += 1;
+            }
+            end
+        };
+        let mut start = x;
+        while start > 0 && class(start - 1) == class(x) {
+            start -= 1;
+        }
+        let mut end = run_end(x);
+
+        if !around {
+            // iw counts blank runs as words too
+            for _ in 1..count {
+                if end + 1 < line.len() {
+                    end = run_end(end + 1);
+                }
+            }
+        } else if class(x) == Class::Blank {
+            // aw on blanks takes the blanks and the word after them
+            for i in 0..count {
+                if i > 0 && end + 1 < line.len() {
+                    end = run_end(end + 1);
+                }
+                if end + 1 < line.len() {
+                    end = run_end(end + 1);
+                }
+            }
+        } else {
+            for i in 0..count {
+                if i > 0 && end + 1 < line.len() {
+                    end = run_end(end + 1);
+                }
+                if end + 1 < line.len() && class(end + 1) == Class::Blank {
+                    end = run_end(end + 1);
+                } else if i == 0 {
+                    // no blanks after the word, take the ones before it
+                    while start > 0 && class(start - 1) == Class::Blank {
+                        start -= 1;
+                    }
+                }
+            }
+        }
+        Some(Region::Chars(
+            self.offset_of(start, y)..self.offset_of(end + 1, y),
+        ))
+    }
+
+    fn sentence_object(&self, x: usize, y: usize, around: bool) -> Option<Region> {
+        let (first, last) = self.paragraph_around(y)?;
+        let paragraph_start = self.line_start(first);
+        let text = self.slice(paragraph_start..self.line_end(last));
+        let offset = self.offset_of(x, y) - paragraph_start;
+        let starts = sentence_starts(&text);
+        let index = starts.iter().rposition(|&s| s <= offset).unwrap_or(0);
+        let start = starts[index];
+        let next = starts.get(index + 1).copied().unwrap_or(text.len());
+        let inner_end = start + text[start..next].trim_end().len();
+        let (start, end) = if !around {
+            (start, inner_end)
+        } else if inner_end < next {
+            (start, next)
+        } else {
+            // the last sentence has no blanks after it, take the ones before it
+            let before = text[..start].trim_end().len();
+            (before, inner_end)
+        };
+        Some(Region::Chars(
+            paragraph_start + start..paragraph_start + end,
+        ))
+    }
+
+    fn paragraph_object(&self, y: usize, count: usize, around: bool) -> Option<Region> {
+        let last_line = self.line_count() - 1;
+        let empty = |y: usize| self.line_length(y) == 0;
+        // last line of the block of lines that are all empty or all not empty starting at y
+        let block_end = |y: usize| {
+            let mut end = y;
+            while end < last_line && empty(end + 1) == empty(y) {
+                end += 1;
+            }
+            end
+        };
+        let mut first = y;
+        while first > 0 && empty(first - 1) == empty(y) {
+            first -= 1;
+        }
+        let mut last = block_end(y);
+        let blocks = if around { count * 2 } else { count };
+        for _ in 1..blocks {
+            if last == last_line {
+                break;
+            }
+            last = block_end(last + 1);
+        }
+        if around && empty(last) == empty(y) && !empty(y) {
+            // no empty lines after the paragraph, take the ones before it
+            while first > 0 && empty(first - 1) {
+                first -= 1;
+            }
+        }
+        Some(Region::Lines(first, last))
+    }
+
+    fn quote_object(&self, x: usize, y: usize, quote: char, around: bool) -> Option<Region> {
+        let line = graphemes_of(self, y);
+        let is_quote = |i: usize| {
+            line[i].starts_with(quote)
+                && line[i].chars().count() == 1
+                && (i == 0 || line[i - 1] != "\\")
+        };
+        let quotes = (0..line.len()).filter(|&i| is_quote(i)).collect::<Vec<_>>();
+        // quotes pair up from the start of the line, the cursor is either inside a pair or before
+        // the next one on the line
+        let pair = quotes
+            .chunks(2)
+            .filter(|pair| pair.len() == 2)
+            .find(|pair| x <= pair[1])?;
+        let (open, close) = (pair[0], pair[1]);
+        let (mut start, mut end) = if around {
+            (open, close + 1)
+        } else {
+            (open + 1, close)
+        };
+        if around {
+            let blank = |i: usize| line[i].trim().is_empty();
+            if end < line.len() && blank(end) {
+                while end < line.len() && blank(end) {
+                    end += 1;
+                }
+            } else {
+                while start > 0 && blank(start - 1) {
+                    start -= 1;
+                }
+            }
+        }
+        Some(Region::Chars(
+            self.offset_of(start, y)..self.offset_of(end, y),
+        ))
+    }
+
+    fn bracket_object(
+        &self,
+        x: usize,
+        y: usize,
+        open: char,
+        close: char,
+        count: usize,
+        around: bool,
+    ) -> Option<Region> {
+        let (open, close) = (open.to_string(), close.to_string());
+        let mut walker = Walker::new(self, x, y);
+        if walker.get() != Some(open.as_str()) && !unmatched_open(&mut walker, &open, &close) {
+            return None;
+        }
+        for _ in 1..count {
+            if !unmatched_open(&mut walker, &open, &close) {
+                return None;
+            }
+        }
+        let (open_x, open_y) = walker.position();
+        let mut depth = 0;
+        let (close_x, close_y) = loop {
+            if !walker.next() {
+                return None;
+            }
+            let grapheme = walker.get();
+            if grapheme == Some(open.as_str()) {
+                depth += 1;
+            } else if grapheme == Some(close.as_str()) {
+                if depth == 0 {
+                    break walker.position();
+                }
+                depth -= 1;
+            }
+        };
+
+        if around {
+            return Some(Region::Chars(
+                self.offset_of(open_x, open_y)..self.offset_of(close_x + 1, close_y),
+            ));
+        }
+        // a block whose brackets sit on their own lines selects the lines in between
+        let opens_line = open_x + 1 == self.line_length(open_y);
+        let closes_line = self.first_non_blank(close_y) == close_x;
+        if opens_line && closes_line && close_y > open_y + 1 {
+            return Some(Region::Lines(open_y + 1, close_y - 1));
+        }
+        Some(Region::Chars(
+            self.offset_of(open_x + 1, open_y)..self.offset_of(close_x, close_y),
+        ))
+    }
+
+    // tags are paired in one pass from the start of the buffer: a closing tag closes the last
+    // open tag of its name, and the ones opened after that and never closed are dropped like
+    // html parsers do. the pairs around the cursor close from the innermost out
+    fn tag_object(&self, x: usize, y: usize, count: usize, around: bool) -> Option<Region> {
+        let cursor = self.offset_of(x, y);
+        let mut walker = Walker::new(self, 0, 0);
+        let mut open: Vec<Tag> = vec![];
+        // how many of each name are open, so a closing tag without one doesn't search them all
+        let mut open_names: HashMap<String, usize> = HashMap::new();
+        let mut found = 0;
+        loop {
+            let (tag_x, tag_y) = walker.position();
+            let tag = match walker.get() {
+                Some("<") => self.tag_at(tag_x, tag_y),
+                _ => None,
+            };
+            if let Some(tag) = tag {
+                // nothing still open can be around the cursor any more
+                if tag.start > cursor && open.first().is_none_or(|first| first.start > cursor) {
+                    return None;
+                }
+                if !tag.closing {
+                    *open_names.entry(tag.name.clone()).or_default() += 1;
+                    open.push(tag);
+                } else if open_names.get(&tag.name).is_some_and(|&n| n > 0) {
+                    let index = open.iter().rposition(|o| o.name == tag.name)?;
+                    for unclosed in open.drain(index + 1..) {
+                        *open_names.get_mut(&unclosed.name)? -= 1;
+                    }
+                    let pair = open.pop()?;
+                    *open_names.get_mut(&pair.name)? -= 1;
+                    // the cursor can be on the closing tag of its pair
+                    if pair.start <= cursor && cursor < tag.end {
+                        found += 1;
+                        if found == count {
+                            return Some(Region::Chars(if around {
+                                pair.start..tag.end
+                            } else {
+                                pair.end..tag.start
+                            }));
+                        }
+                    }
+                }
+            }
+            if !walker.next() {
+                return None;
+            }
+        }
+    }
+
+    // the tag starting at the < at x, y. None for comments, self-closing tags and a < that
+    // doesn't start a tag
+    fn tag_at(&self, x: usize, y: usize) -> Option<Tag> {
+        let mut walker = Walker::new(self, x, y);
+        let mut inside = String::new();
+        loop {
+            if !walker.next() {
+                return None;
+            }
+            match walker.get()? {
+                ">" => break,
+                "<" => return None,
+                grapheme => inside.push_str(grapheme),
+            }
+        }
+        if inside.starts_with('!') || inside.starts_with('?') || inside.ends_with('/') {
+            return None;
+        }
+        let name = inside
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or("");
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || "-_:.".contains(c))
+        {
+            return None;
+        }
+        let (end_x, end_y) = walker.position();
+        Some(Tag {
+            name: name.to_string(),
+            closing: inside.starts_with('/'),
+            start: self.offset_of(x, y),
+            end: self.offset_of(end_x + 1, end_y),
+        })
+    }
+}
+
+// moves back to the open bracket the walker is inside of
+fn unmatched_open(walker: &mut Walker, open: &str, close: &str) -> bool {
+    let mut depth = 0;
+    loop {
+        if !walker.prev() {
+            return false;
+        }
+        let grapheme = walker.get();
+        if grapheme == Some(close) {
+            depth += 1;
+        } else if grapheme == Some(open) {
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+        }
+    }
+}
+
+struct Tag {
+    name: String,
+    closing: bool,
+    // offsets of the < and of what's after the >
+    start: usize,
+    end: usize,
+}
+
+mod test {
+    #[test]
+    fn should_find_text_objects_around_cursor() {
+        use crate::operator::Region;
+        use crate::textobject::parse_text_object;
+        let region = |text: &str, x, y, keys, count| {
+            let mut buffer = crate::buffer::Buffer::from(text);
+            buffer.set_cursor(x, y, crate::editor::Mode::NORMAL);
+            match buffer.text_object_region(parse_text_object(keys).unwrap(), count) {
+                Some(Region::Chars(range)) => buffer.slice(range),
+                Some(Region::Lines(first, last)) => format!("lines {}-{}", first, last),
+                Some(Region::Block { .. }) => "block".to_string(),
+                None => "none".to_string(),
+            }
+        };
+
+        assert_eq!("two", region("one two  three", 5, 0, "iw", None));
+        assert_eq!("two  ", region("one two  three", 5, 0, "aw", None));
+        assert_eq!(" three", region("one two three", 9, 0, "aw", None));
+        assert_eq!("two three", region("one two three", 4, 0, "iw", Some(3)));
+        assert_eq!("a.b ", region("x a.b y", 3, 0, "aW", None));
+        assert_eq!(
+            "Second one!",
+            region("First.  Second one! Third", 10, 0, "is", None)
+        );
+        assert_eq!(
+            "Second one! ",
+            region("First.  Second one! Third", 10, 0, "as", None)
+        );
+        assert_eq!("lines 2-3", region("a\n\nb\nc\n\n\nd", 0, 3, "ip", None));
+        assert_eq!("lines 2-5", region("a\n\nb\nc\n\n\nd", 0, 3, "ap", None));
+        assert_eq!("lines 1-3", region("a\n\nb\nc", 0, 3, "ap", None));
+        assert_eq!(
+            "b \\\"c\\\"",
+            region("a \"b \\\"c\\\"\" d", 2, 0, "i\"", None)
+        );
+        assert_eq!("'y' ", region("x = 'y' + 'z'", 0, 0, "a'", None));
+        assert_eq!("z", region("x = 'y' + 'z'", 9, 0, "i'", None));
+        assert_eq!("b, (c)", region("f(a(b, (c)), d)", 5, 0, "i)", None));
+        assert_eq!(
+            "(a(b, (c)), d)",
+            region("f(a(b, (c)), d)", 5, 0, "a(", Some(2))
+        );
+        assert_eq!("c", region("f(a(b, (c)), d)", 7, 0, "ib", None));
+        assert_eq!(
+            "lines 1-2",
+            region("fn x() {\n    a;\n    b;\n}", 4, 1, "i{", None)
+        );
+        assert_eq!("none", region("a [b] c", 6, 0, "i[", None));
+        assert_eq!(
+            "b",
+            region("<a href=\"x\">a <b>b</b><br/></a>", 16, 0, "it", None)
+        );
+        assert_eq!(
+            "<b>b</b>",
+            region("<a href=\"x\">a <b>b</b><br/></a>", 16, 0, "at", None)
+        );
+        assert_eq!(
+            "a <b>b</b><br/>",
+            region("<a href=\"x\">a <b>b</b><br/></a>", 16, 0, "it", Some(2))
+        );
+        assert_eq!(
+            "<b>b</b>",
+            region("<a href=\"x\">a <b>b</b><br/></a>", 19, 0, "at", None)
+        );
+        assert_eq!(
+            "\n  <p>x</p>\n  <p>y\n",
+            region("<div>\n  <p>x</p>\n  <p>y\n</div>", 4, 2, "it", None)
+        );
+        assert_eq!(
+            "<i>a <i>b</i></i>",
+            region("<i>a <i>b</i></i> < c", 1, 0, "at", None)
+        );
+        assert_eq!("none", region("<i>a</i> b", 9, 0, "it", None));
+        assert_eq!("<br>x", region("<b><br>x</b><p>y", 7, 0, "it", None));
+    }
+}
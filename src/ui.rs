@@ -1,51 +1,68 @@
+use crate::editor::DisplayInformation;
+use crate::options::parse_color;
 use crate::{editor, Editor, IO};
 use femtovg::{renderer::OpenGl, Canvas, Color, FontId, FontMetrics, Paint, Path};
 use resource::resource;
 
 pub struct UI {
     pub canvas: Canvas<OpenGl>,
-    background_color: [f32; 4],
-    foreground_color: [f32; 4],
 
     font: FontId,
+    font_size: f32,
     font_metrics: Option<FontMetrics>,
+    character_width: f32,
 }
 impl UI {
-    pub fn new(mut canvas: Canvas<OpenGl>) -> Self {
-        let background_color = [0.3, 0.3, 0.32, 1.0];
-        let foreground_color = [1.0, 1.0, 1.0, 1.0];
-
+    pub fn new(mut canvas: Canvas<OpenGl>, font_size: f32) -> Self {
         let font = canvas
             .add_font_mem(&resource!("src/Cousine-Regular.ttf"))
             .expect("Cannot add font");
         let mut ui = Self {
             canvas,
-            background_color,
-            foreground_color,
             font,
+            font_size,
             font_metrics: None,
+            character_width: 0.0,
         };
+        ui.set_font_size(font_size);
 
+        ui
+    }
+
+    // the metrics are measured again for the new size
+    fn set_font_size(&mut self, font_size: f32) {
+        self.font_size = font_size;
         // TODO: i'm forced to Option<FontMetrics> :(
-        let paint = ui.get_paint(ui.foreground_color);
-        let font_metrics = ui
+        let paint = self.get_paint([1.0, 1.0, 1.0, 1.0]);
+        let font_metrics = self
             .canvas
             .measure_font(paint)
             .expect("Unexpected error: Can't measure font");
-        ui.font_metrics = Some(font_metrics);
-
-        ui
+        self.font_metrics = Some(font_metrics);
+        let text_metrics = self
+            .canvas
+            .measure_text(0.0, 0.0, "A", paint)
+            .expect("Unexpected error: Can't measure font");
+        self.character_width = text_metrics.width();
     }
 
     fn get_paint(&self, color: [f32; 4]) -> Paint {
         let mut paint = Paint::color(Color::rgbf(color[0], color[1], color[2]));
         paint.set_font(&[self.font]);
-        paint.set_font_size(24.0);
+        paint.set_font_size(self.font_size);
         paint.set_text_baseline(femtovg::Baseline::Top);
         paint
     }
 
-    pub fn run(&mut self, io: &mut IO, editor: &Editor) {
+    pub fn run(&mut self, io: &mut IO, editor: &Editor, info: &DisplayInformation) {
+        let font_size = editor.option("fontsize").number() as f32;
+        if font_size != self.font_size {
+            self.set_font_size(font_size);
+        }
+        // :set only lets valid colors through
+        let color = |name| parse_color(editor.option(name).string()).unwrap();
+        let background_color = color("backgroundcolor");
+
         self.canvas.set_size(
             io.window_dimensions[0],
             io.window_dimensions[1],
@@ -57,92 +74,198 @@ impl UI {
             io.window_dimensions[0],
             io.window_dimensions[1],
             Color::rgbf(
-                self.background_color[0],
-                self.background_color[1],
-                self.background_color[2],
+                background_color[0],
+                background_color[1],
+                background_color[2],
             ),
         );
 
-        let foreground_paint = self.get_paint(self.foreground_color);
-        let background_paint = self.get_paint(self.background_color);
+        let foreground_paint = self.get_paint(color("foregroundcolor"));
+        let background_paint = self.get_paint(background_color);
+        let selection_paint = self.get_paint(color("selectioncolor"));
+        let search_paint = self.get_paint(color("searchcolor"));
 
-        let cursor_color_ms_interval = 500;
+        let cursor_color_ms_interval = editor.option("cursorblink").number() as u128;
         let elapsed_ms = editor.cursor_animation_instant.elapsed().as_millis();
-        let cursor_paint = if (elapsed_ms / cursor_color_ms_interval) % 2 == 0 {
-            foreground_paint
-        } else {
-            background_paint
-        };
+        let cursor_paint =
+            if cursor_color_ms_interval == 0 || (elapsed_ms / cursor_color_ms_interval) % 2 == 0 {
+                foreground_paint
+            } else {
+                background_paint
+            };
 
-        let text_metrics = self
-            .canvas
-            .measure_text(0.0, 0.0, "A", foreground_paint)
-            .expect("Unexpected error: Can't measure font");
-
-        let font_width = text_metrics.width();
+        let font_width = self.character_width;
+        let character_height = self.character_height();
 
         let cursor_width = match editor.mode {
-            editor::Mode::INSERT => font_width / 4.0,
+            editor::Mode::INSERT | editor::Mode::COMMAND => font_width / 4.0,
             _ => font_width,
         };
 
-        let cursor = editor.buffer.cursor();
+        let command_line_y =
+            info.window_height_in_characters.saturating_sub(1) as f32 * character_height;
 
-        let cursor_screen_x = cursor.0 as f32 * font_width;
-        let cursor_screen_y = (cursor.1 - editor.y_render_offset) as f32 * self.character_height();
+        let views = editor.window_views(info);
+        let current = views.iter().find(|view| view.current).unwrap();
+        // while typing a command the cursor sits at the end of the command line
+        let (cursor_screen_x, cursor_screen_y) = match editor.mode {
+            editor::Mode::COMMAND => (
+                (editor.command_line.chars().count() + 1) as f32 * font_width,
+                command_line_y,
+            ),
+            _ => (
+                (current.rect.x + current.cursor.0) as f32 * font_width,
+                (current.rect.y + current.cursor.1 - current.y_render_offset) as f32
+                    * character_height,
+            ),
+        };
         let mut cursor_target = Path::new();
         cursor_target.rect(
             cursor_screen_x,
             cursor_screen_y,
             cursor_width,
-            self.character_height(),
-        );
-        self.canvas.fill_path(&mut cursor_target, cursor_paint);
-
-        let _window_width_in_characters = io.window_dimensions[0] / font_width as u32;
-        let window_height_in_characters = io.window_dimensions[1] / self.character_height() as u32;
-
-        let status_line_y = (window_height_in_characters - 2) as f32 * self.character_height();
-        let mut status_line_rect = Path::new();
-        status_line_rect.rect(
-            0.0,
-            status_line_y,
-            io.window_dimensions[0] as f32,
-            self.character_height(),
-        );
-        self.canvas
-            .fill_path(&mut status_line_rect, foreground_paint);
-
-        let status_text = format!(
-            " {} > {} < $ {} {:?} [{}]",
-            cursor.1,
-            editor.editing_file_path,
-            editor.matching_input,
-            editor.matching_input_timeout,
-            editor.buffer.line_separator_format,
+            character_height,
         );
 
-        self.canvas
-            .fill_text(0.0, status_line_y, status_text.as_str(), background_paint)
-            .expect("Unexpected rendering error");
+        // the tab pages along the top when there's more than one, the current one standing out
+        if editor.tab_count() > 1 {
+            let mut tab_line_rect = Path::new();
+            tab_line_rect.rect(0.0, 0.0, io.window_dimensions[0] as f32, character_height);
+            self.canvas.fill_path(&mut tab_line_rect, selection_paint);
+            let mut x = 0.0;
+            for (label, current) in editor.tab_labels() {
+                let label = format!(" {} ", label);
+                let width = label.chars().count() as f32 * font_width;
+                let text_paint = if current {
+                    let mut label_rect = Path::new();
+                    label_rect.rect(x, 0.0, width, character_height);
+                    self.canvas.fill_path(&mut label_rect, foreground_paint);
+                    background_paint
+                } else {
+                    foreground_paint
+                };
+                self.canvas
+                    .fill_text(x, 0.0, label.as_str(), text_paint)
+                    .expect("Unexpected rendering error");
+                x += width + font_width;
+            }
+        }
+
+        let search_pattern = editor.highlighted_pattern();
+        for view in &views {
+            let left = view.rect.x as f32 * font_width;
+            let top = view.rect.y as f32 * character_height;
+            let width = view.rect.width as f32 * font_width;
+            let height = view.rect.height as f32 * character_height;
+            let text_height = view.rect.height.saturating_sub(1);
+
+            // a column between windows side by side
+            if view.rect.x > 0 {
+                let mut separator = Path::new();
+                separator.rect(left - font_width, top, font_width, height);
+                self.canvas.fill_path(&mut separator, selection_paint);
+            }
+
+            self.canvas.save();
+            self.canvas.scissor(left, top, width, height);
 
-        for (line_index, line) in editor
-            .buffer
-            .as_string()
-            .split('\n')
-            .skip(editor.y_render_offset)
-            .take((window_height_in_characters - 2) as usize)
-            .enumerate()
-        {
-            let y = line_index as f32 * self.character_height();
+            let visible_lines = (view.y_render_offset..view.buffer.line_count()).take(text_height);
+            for (line_index, buffer_line) in visible_lines.enumerate() {
+                let line = view.buffer.line(buffer_line);
+                let y = top + line_index as f32 * character_height;
+                let matches = search_pattern
+                    .iter()
+                    .flat_map(|pattern| view.buffer.line_matches(pattern, buffer_line));
+                for columns in matches {
+                    let mut found = Path::new();
+                    found.rect(
+                        left + columns.start as f32 * font_width,
+                        y,
+                        columns.len() as f32 * font_width,
+                        character_height,
+                    );
+                    self.canvas.fill_path(&mut found, search_paint);
+                }
+                // the match :s is asking about is drawn like a selection
+                let selected = if view.current {
+                    editor
+                        .selected_columns(buffer_line)
+                        .or_else(|| editor.confirming_match(buffer_line))
+                } else {
+                    None
+                };
+                if let Some(columns) = selected {
+                    let mut selection = Path::new();
+                    selection.rect(
+                        left + columns.start as f32 * font_width,
+                        y,
+                        columns.len() as f32 * font_width,
+                        character_height,
+                    );
+                    self.canvas.fill_path(&mut selection, selection_paint);
+                }
+                // over the highlights and the selection, under the text
+                if view.current
+                    && buffer_line == view.cursor.1
+                    && editor.mode != editor::Mode::COMMAND
+                {
+                    self.canvas.fill_path(&mut cursor_target, cursor_paint);
+                }
+                self.canvas
+                    .fill_text(left, y, line.as_str(), foreground_paint)
+                    .expect("Unexpected rendering error");
+            }
+
+            // the current window's status line stands out and has the editor's state on it
+            let status_line_y = top + text_height as f32 * character_height;
+            let mut status_line_rect = Path::new();
+            status_line_rect.rect(left, status_line_y, width, character_height);
+            let status_text = if view.current {
+                let recording = match &editor.recording {
+                    Some((name, _)) => format!(" recording @{}", name),
+                    None => String::new(),
+                };
+                self.canvas
+                    .fill_path(&mut status_line_rect, foreground_paint);
+                format!(
+                    " {} > {} < $ {} {:?} [{}]{}",
+                    view.cursor.1,
+                    view.path,
+                    editor.pending_input(),
+                    editor.pending_timeout,
+                    view.buffer.line_separator_format,
+                    recording,
+                )
+            } else {
+                self.canvas
+                    .fill_path(&mut status_line_rect, selection_paint);
+                format!(" {} > {} <", view.cursor.1, view.path)
+            };
             self.canvas
-                .fill_text(0.0, y, line, foreground_paint)
+                .fill_text(left, status_line_y, status_text.as_str(), background_paint)
                 .expect("Unexpected rendering error");
+
+            self.canvas.restore();
         }
 
+        let command_text = match editor.mode {
+            editor::Mode::COMMAND => format!("{}{}", editor.command_prompt, editor.command_line),
+            _ => editor.status_message.clone(),
+        };
+        if editor.mode == editor::Mode::COMMAND {
+            self.canvas.fill_path(&mut cursor_target, cursor_paint);
+        }
+        self.canvas
+            .fill_text(0.0, command_line_y, command_text.as_str(), foreground_paint)
+            .expect("Unexpected rendering error");
+
         self.canvas.flush();
     }
 
+    pub fn character_width(&self) -> f32 {
+        self.character_width
+    }
+
     pub fn character_height(&self) -> f32 {
         self.font_metrics
             .as_ref()
@@ -0,0 +1,322 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    INSERT { offset: usize, text: String },
+    DELETE { offset: usize, text: String },
+}
+impl Edit {
+    pub fn inverse(&self) -> Edit {
+        match self {
+            Edit::INSERT { offset, text } => Edit::DELETE {
+                offset: *offset,
+                text: text.clone(),
+            },
+            Edit::DELETE { offset, text } => Edit::INSERT {
+                offset: *offset,
+                text: text.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndoStep {
+    pub edits: Vec<Edit>,
+    pub cursor_before: (usize, usize),
+    pub cursor_after: (usize, usize),
+}
+
+#[derive(Debug, Clone)]
+struct UndoNode {
+    parent: usize,
+    // the branch redo follows: the child created or visited last
+    redo_child: Option<usize>,
+    step: UndoStep,
+    time: SystemTime,
+}
+
+// every state the buffer has been in, numbered in the order they were created. node 0 is the
+// original text; undoing and then editing adds a sibling branch instead of dropping the old one.
+#[derive(Debug, Clone)]
+pub struct UndoTree {
+    nodes: Vec<UndoNode>,
+    current: usize,
+}
+impl Default for UndoTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl UndoTree {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![UndoNode {
+                parent: 0,
+                redo_child: None,
+                step: UndoStep {
+                    edits: vec![],
+                    cursor_before: (0, 0),
+                    cursor_after: (0, 0),
+                },
+                time: SystemTime::now(),
+            }],
+            current: 0,
+        }
+    }
+    pub fn current(&self) -> usize {
+        self.current
+    }
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+    pub fn time_of(&self, state: usize) -> SystemTime {
+        self.nodes[state].time
+    }
+    // the newest state that already existed at the given time
+    pub fn state_at(&self, time: SystemTime) -> usize {
+        self.nodes
+            .iter()
+            .rposition(|node| node.time <= time)
+            .unwrap_or(0)
+    }
+    pub fn commit(&mut self, step: UndoStep) {
+        let index = self.nodes.len();
+        self.nodes.push(UndoNode {
+            parent: self.current,
+            redo_child: None,
+            step,
+            time: SystemTime::now(),
+        });
+        self.nodes[self.current].redo_child = Some(index);
+        self.current = index;
+    }
+    pub fn undo(&mut self) -> Option<UndoStep> {
+        if self.current == 0 {
+            return None;
+        }
+        let node = &self.nodes[self.current];
+        let step = node.step.clone();
+        let parent = node.parent;
+        self.nodes[parent].redo_child = Some(self.current);
+        self.current = parent;
+        Some(step)
+    }
+    pub fn redo(&mut self) -> Option<UndoStep> {
+        let child = self.nodes[self.current].redo_child?;
+        self.current = child;
+        Some(self.nodes[child].step.clone())
+    }
+    // the steps to undo (in order) and then redo (in order) to get from the current state to
+    // target, possibly across branches
+    pub fn travel(&mut self, target: usize) -> (Vec<UndoStep>, Vec<UndoStep>) {
+        debug_assert!(target < self.nodes.len());
+        let mut target_path = vec![target];
+        while *target_path.last().unwrap() != 0 {
+            target_path.push(self.nodes[*target_path.last().unwrap()].parent);
+        }
+
+        let mut undos = vec![];
+        while !target_path.contains(&self.current) {
+            undos.push(self.undo().unwrap());
+        }
+
+        let common = target_path.iter().position(|&n| n == self.current).unwrap();
+        let mut redos = vec![];
+        for &node in target_path[..common].iter().rev() {
+            self.nodes[self.current].redo_child = Some(node);
+            redos.push(self.redo().unwrap());
+        }
+        (undos, redos)
+    }
+
+    pub fn serialize(&self, content_hash: u64) -> String {
+        let mut out = String::new();
+        out.push_str("uu-undo 1\n");
+        out.push_str(&format!("hash {:016x}\n", content_hash));
+        out.push_str(&format!("current {}\n", self.current));
+        out.push_str(&format!("nodes {}\n", self.nodes.len()));
+        for node in &self.nodes {
+            let time = node.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+            out.push_str(&format!(
+                "node {} {} {} {} {} {} {} {}\n",
+                node.parent,
+                node.redo_child.map(|c| c as i64).unwrap_or(-1),
+                time.as_millis(),
+                node.step.cursor_before.0,
+                node.step.cursor_before.1,
+                node.step.cursor_after.0,
+                node.step.cursor_after.1,
+                node.step.edits.len(),
+            ));
+            for edit in &node.step.edits {
+                let (kind, offset, text) = match edit {
+                    Edit::INSERT { offset, text } => ('i', offset, text),
+                    Edit::DELETE { offset, text } => ('d', offset, text),
+                };
+                out.push_str(&format!("{} {} {}\n{}\n", kind, offset, text.len(), text));
+            }
+        }
+        out
+    }
+    // None when the data is malformed or was written for different file contents than text
+    pub fn deserialize(data: &str, content_hash: u64, text: &str) -> Option<Self> {
+        let mut reader = Reader { rest: data };
+        if reader.line()? != "uu-undo 1" {
+            return None;
+        }
+        let hash = reader.field("hash")?;
+        if u64::from_str_radix(hash, 16).ok()? != content_hash {
+            return None;
+        }
+        let current = reader.field("current")?.parse().ok()?;
+        let count: usize = reader.field("nodes")?.parse().ok()?;
+
+        let mut nodes = vec![];
+        for _ in 0..count {
+            let numbers = reader
+                .field("node")?
+                .split(' ')
+                .map(|n| n.parse::<i128>().ok())
+                .collect::<Option<Vec<_>>>()?;
+            if numbers.len() != 8 || numbers.iter().any(|&n| n < -1) {
+                return None;
+            }
+            let mut edits = vec![];
+            for _ in 0..numbers[7] {
+                let header = reader.line()?;
+                let mut parts = header.split(' ');
+                let kind = parts.next()?;
+                let offset = parts.next()?.parse().ok()?;
+                let length = parts.next()?.parse().ok()?;
+                let text = reader.take(length)?.to_string();
+                edits.push(match kind {
+                    "i" => Edit::INSERT { offset, text },
+                    "d" => Edit::DELETE { offset, text },
+                    _ => return None,
+                });
+            }
+            nodes.push(UndoNode {
+                parent: numbers[0] as usize,
+                redo_child: if numbers[1] < 0 {
+                    None
+                } else {
+                    Some(numbers[1] as usize)
+                },
+                step: UndoStep {
+                    edits,
+                    cursor_before: (numbers[3] as usize, numbers[4] as usize),
+                    cursor_after: (numbers[5] as usize, numbers[6] as usize),
+                },
+                time: UNIX_EPOCH + Duration::from_millis(numbers[2] as u64),
+            });
+        }
+
+        let valid = current < count
+            && nodes.iter().enumerate().all(|(i, node)| {
+                (i == 0 || node.parent < i) && node.redo_child.iter().all(|&c| c > i && c < count)
+            });
+        if !valid || !edits_fit(&nodes, current, text) {
+            return None;
+        }
+        Some(Self { nodes, current })
+    }
+}
+
+// every edit has to fit the text it's made to, going back from the current text to the original
+// one and from there to all the other states. they are played on a copy, so an offset inside a
+// character or a deletion of text that isn't there is found here instead of when undoing
+fn edits_fit(nodes: &[UndoNode], current: usize, text: &str) -> bool {
+    let mut scratch = text.to_string();
+    let mut node = current;
+    while node != 0 {
+        if !apply_step(&mut scratch, &nodes[node].step, true) {
+            return false;
+        }
+        node = nodes[node].parent;
+    }
+    let mut children = vec![vec![]; nodes.len()];
+    for (i, node) in nodes.iter().enumerate().skip(1) {
+        children[node.parent].push(i);
+    }
+    // depth first, undoing each node's step once its children are done
+    let mut pending: Vec<(usize, bool)> = children[0].iter().map(|&child| (child, true)).collect();
+    while let Some((node, redo)) = pending.pop() {
+        if !apply_step(&mut scratch, &nodes[node].step, !redo) {
+            return false;
+        }
+        if redo {
+            if node == current && scratch != text {
+                return false;
+            }
+            pending.push((node, false));
+            pending.extend(children[node].iter().map(|&child| (child, true)));
+        }
+    }
+    true
+}
+
+fn apply_step(text: &mut String, step: &UndoStep, undo: bool) -> bool {
+    if undo {
+        step.edits.iter().rev().all(|edit| apply(text, edit, true))
+    } else {
+        step.edits.iter().all(|edit| apply(text, edit, false))
+    }
+}
+
+fn apply(text: &mut String, edit: &Edit, undo: bool) -> bool {
+    let (offset, edit_text, insert) = match edit {
+        Edit::INSERT { offset, text } => (*offset, text, !undo),
+        Edit::DELETE { offset, text } => (*offset, text, undo),
+    };
+    if insert {
+        if !text.is_char_boundary(offset) {
+            return false;
+        }
+        text.insert_str(offset, edit_text);
+        return true;
+    }
+    let end = match offset.checked_add(edit_text.len()) {
+        Some(end) if text.get(offset..end) == Some(edit_text.as_str()) => end,
+        _ => return false,
+    };
+    text.replace_range(offset..end, "");
+    true
+}
+
+// fnv-1a, stable across builds so undo files keep matching their text
+pub fn content_hash<'a>(chunks: impl Iterator<Item = &'a str>) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for chunk in chunks {
+        for byte in chunk.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+struct Reader<'a> {
+    rest: &'a str,
+}
+impl<'a> Reader<'a> {
+    fn line(&mut self) -> Option<&'a str> {
+        let end = self.rest.find('\n')?;
+        let line = &self.rest[..end];
+        self.rest = &self.rest[end + 1..];
+        Some(line)
+    }
+    fn field(&mut self, name: &str) -> Option<&'a str> {
+        let line = self.line()?;
+        let value = line.strip_prefix(name)?;
+        value.strip_prefix(' ')
+    }
+    fn take(&mut self, length: usize) -> Option<&'a str> {
+        let text = self.rest.get(..length)?;
+        self.rest = self.rest.get(length..)?.strip_prefix('\n')?;
+        Some(text)
+    }
+}
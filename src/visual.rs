@@ -0,0 +1,343 @@
+use crate::editor::{Editor, Mode};
+use crate::operator::Region;
+use crate::textobject::TextObject;
+use crate::{min, Instant};
+use std::cmp::max;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VisualKind {
+    // v
+    Chars,
+    // V
+    Lines,
+    // ctrl-v
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    pub kind: VisualKind,
+    pub anchor: (usize, usize),
+    pub cursor: (usize, usize),
+}
+
+// what was inserted on the first line of a block gets copied to the other lines when the insert
+// session ends
+#[derive(Debug, Clone)]
+pub(crate) struct BlockInsert {
+    first: usize,
+    last: usize,
+    // None appends at the end of every line
+    column: Option<usize>,
+    // short lines are padded with spaces when appending, skipped when inserting
+    pad: bool,
+    line_count: usize,
+    length_before: usize,
+}
+
+impl Editor {
+    // v, V and ctrl-v start a selection, switch to another kind of selection, or leave visual
+    // mode when pressed again
+    pub fn toggle_visual_mode(&mut self, kind: VisualKind) {
+        match self.mode {
+            Mode::VISUAL(current) if current == kind => self.leave_visual_mode(),
+            Mode::VISUAL(_) => self.mode = Mode::VISUAL(kind),
+            _ => {
+                self.visual_anchor = self.buffer.cursor();
+                self.mode = Mode::VISUAL(kind);
+            }
+        }
+        self.cursor_animation_instant = Instant::now();
+    }
+
+    pub fn leave_visual_mode(&mut self) {
+        if let Mode::VISUAL(kind) = self.mode {
+            self.last_visual = Some(Selection {
+                kind,
+                anchor: self.visual_anchor,
+                cursor: self.buffer.cursor(),
+            });
+            self.set_visual_marks(kind);
+        }
+        self.mode = Mode::NORMAL;
+        self.move_cursor_horizontal(0);
+    }
+
+    // gv
+    pub fn reselect_visual(&mut self) {
+        if let Some(selection) = self.last_visual {
+            let (x, y) = selection.cursor;
+            self.visual_anchor = selection.anchor;
+            self.mode = Mode::VISUAL(selection.kind);
+            self.buffer.set_cursor(x, y, self.mode);
+            self.scroll_to_cursor();
+            self.cursor_animation_instant = Instant::now();
+        }
+    }
+
+    // o
+    pub fn swap_visual_ends(&mut self) {
+        let (x, y) = self.visual_anchor;
+        self.visual_anchor = self.buffer.cursor();
+        self.buffer.set_cursor(x, y, self.mode);
+        self.scroll_to_cursor();
+        self.cursor_animation_instant = Instant::now();
+    }
+
+    pub fn select_text_object(&mut self, object: TextObject, count: Option<usize>) {
+        match self.buffer.text_object_region(object, count) {
+            Some(Region::Chars(range)) if !range.is_empty() => {
+                self.visual_anchor = self.buffer.position_of(range.start);
+                let (x, y) = self.buffer.position_of(range.end - 1);
+                self.buffer.set_cursor(x, y, self.mode);
+            }
+            Some(Region::Lines(first, last)) => {
+                if self.mode == Mode::VISUAL(VisualKind::Chars) {
+                    self.mode = Mode::VISUAL(VisualKind::Lines);
+                }
+                self.visual_anchor = (0, first);
+                self.buffer.set_cursor(0, last, self.mode);
+            }
+            _ => {}
+        }
+        self.scroll_to_cursor();
+        self.cursor_animation_instant = Instant::now();
+    }
+
+    pub fn selection_region(&self) -> Option<Region> {
+        let kind = match self.mode {
+            Mode::VISUAL(kind) => kind,
+            _ => return None,
+        };
+        let (anchor_x, anchor_y) = self.visual_anchor;
+        let (x, y) = self.buffer.cursor();
+        let (first, last) = (min(anchor_y, y), max(anchor_y, y));
+        Some(match kind {
+            VisualKind::Chars => {
+                let anchor = self.buffer.offset_of(anchor_x, anchor_y);
+                let cursor = self.buffer.offset_of(x, y);
+                let (end_x, end_y) = self.buffer.position_of(max(anchor, cursor));
+                // a selection ending on an empty line takes its line break
+                let end = if end_x >= self.buffer.line_length(end_y) {
+                    min(self.buffer.line_end(end_y) + 1, self.buffer.len())
+                } else {
+                    self.buffer.offset_of(end_x + 1, end_y)
+                };
+                Region::Chars(min(anchor, cursor)..end)
+            }
+            VisualKind::Lines => Region::Lines(first, last),
+            VisualKind::Block => Region::Block {
+                first,
+                last,
+                columns: self.block_columns(),
+            },
+        })
+    }
+
+    // the columns of line y drawn as selected, one past the end of the line stands for its
+    // line break
+    pub fn selected_columns(&self, y: usize) -> Option<Range<usize>> {
+        let region = self.selection_region()?;
+        let length = self.buffer.line_length(y);
+        match region {
+            Region::Chars(range) => {
+                let (start_x, start_y) = self.buffer.position_of(range.start);
+                let (end_x, end_y) = self.buffer.position_of(range.end);
+                if y < start_y || y > end_y || (y == end_y && end_x == 0 && y > start_y) {
+                    return None;
+                }
+                let start = if y == start_y { start_x } else { 0 };
+                let end = if y == end_y { end_x } else { length + 1 };
+                Some(start..max(start + 1, end))
+            }
+            Region::Lines(first, last) if first <= y && y <= last => Some(0..length + 1),
+            Region::Block {
+                first,
+                last,
+                columns,
+            } if first <= y && y <= last => Some(columns.start..min(columns.end, length + 1)),
+            _ => None,
+        }
+    }
+
+    // I and A: insert before or append after the block on every line. in the other kinds of
+    // selection they insert before or after the selection
+    pub fn block_insert(&mut self, append: bool) {
+        let region = match self.selection_region() {
+            Some(region) => region,
+            None => return,
+        };
+        self.leave_visual_mode();
+        match region {
+            Region::Block {
+                first,
+                last,
+                columns,
+            } => {
+                let column = if !append {
+                    Some(columns.start)
+                } else if columns.end == usize::MAX {
+                    None
+                } else {
+                    Some(columns.end)
+                };
+                self.buffer.set_cursor(columns.start, first, Mode::NORMAL);
+                self.enter_insert_mode();
+                self.start_block_insert(first, last, column, append);
+            }
+            Region::Chars(range) => {
+                let (x, y) = self
+                    .buffer
+                    .position_of(if append { range.end } else { range.start });
+                self.buffer.set_cursor(x, y, Mode::INSERT);
+                self.enter_insert_mode();
+            }
+            Region::Lines(first, last) => {
+                let (x, y) = if append {
+                    (self.buffer.line_length(last), last)
+                } else {
+                    (self.buffer.first_non_blank(first), first)
+                };
+                self.buffer.set_cursor(x, y, Mode::INSERT);
+                self.enter_insert_mode();
+            }
+        }
+    }
+
+    // expects the undo group of the insert session to be open already
+    pub(crate) fn start_block_insert(
+        &mut self,
+        first: usize,
+        last: usize,
+        column: Option<usize>,
+        pad: bool,
+    ) {
+        let length = self.buffer.line_length(first);
+        let x = match column {
+            Some(column) if pad && column > length => {
+                let end = self.buffer.line_end(first);
+                self.buffer.insert(end, &" ".repeat(column - length));
+                column
+            }
+            Some(column) => min(column, length),
+            None => length,
+        };
+        self.buffer.set_cursor(x, first, Mode::INSERT);
+        self.block_insert = Some(BlockInsert {
+            first,
+            last,
+            column,
+            pad,
+            line_count: self.buffer.line_count(),
+            length_before: self.buffer.line_length(first),
+        });
+    }
+
+    // copies the text typed on the first line of the block to the rest of it. nothing is copied
+    // when the insert session broke lines or left the first line
+    pub(crate) fn finish_block_insert(&mut self) {
+        let insert = match self.block_insert.take() {
+            Some(insert) => insert,
+            None => return,
+        };
+        let (_, y) = self.buffer.cursor();
+        let length = self.buffer.line_length(insert.first);
+        if y != insert.first
+            || self.buffer.line_count() != insert.line_count
+            || length <= insert.length_before
+        {
+            return;
+        }
+        let start = insert.column.unwrap_or(insert.length_before);
+        let inserted = length - insert.length_before;
+        let text = self.buffer.slice(
+            self.buffer.offset_of(start, insert.first)
+                ..self.buffer.offset_of(start + inserted, insert.first),
+        );
+        for y in insert.first + 1..=insert.last {
+            let length = self.buffer.line_length(y);
+            match insert.column {
+                None => {
+                    let end = self.buffer.line_end(y);
+                    self.buffer.insert(end, &text);
+                }
+                Some(column) if column <= length => {
+                    let offset = self.buffer.offset_of(column, y);
+                    self.buffer.insert(offset, &text);
+                }
+                Some(column) if insert.pad => {
+                    let end = self.buffer.line_end(y);
+                    self.buffer
+                        .insert(end, &(" ".repeat(column - length) + &text));
+                }
+                Some(_) => {}
+            }
+        }
+        self.buffer.set_cursor(start, insert.first, Mode::INSERT);
+    }
+
+    // $ in a block selection extends every line to its end
+    fn block_columns(&self) -> Range<usize> {
+        let (anchor_x, _) = self.visual_anchor;
+        let (x, _) = self.buffer.cursor();
+        let end = if self.buffer.preferred_column() == usize::MAX {
+            usize::MAX
+        } else {
+            max(anchor_x, x) + 1
+        };
+        min(anchor_x, x)..end
+    }
+}
+
+mod test {
+    #[test]
+    fn should_operate_on_visual_selections() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("one two three\nfour five\nsix\n");
+
+        editor.type_keys("wvey");
+        assert_eq!("two", editor.registers.get('"').unwrap().text);
+        assert_eq!(crate::editor::Mode::NORMAL, editor.mode);
+        editor.type_keys("vjd");
+        assert_eq!("one five\nsix\n", editor.buffer.as_string());
+        editor.type_keys("u$vbd");
+        assert_eq!("one two \nfour five\nsix\n", editor.buffer.as_string());
+        editor.type_keys("0wvlohd");
+        assert_eq!("oneo \nfour five\nsix\n", editor.buffer.as_string());
+        editor.type_keys("ggVjU");
+        assert_eq!("ONEO \nFOUR FIVE\nsix\n", editor.buffer.as_string());
+        editor.type_keys("gvd");
+        assert_eq!("six\n", editor.buffer.as_string());
+        assert_eq!(
+            crate::register::RegisterKind::Lines,
+            editor.registers.get('"').unwrap().kind
+        );
+        editor.type_keys("uvipy");
+        assert_eq!(
+            "ONEO \nFOUR FIVE\nsix\n",
+            editor.registers.get('"').unwrap().text
+        );
+    }
+
+    #[test]
+    fn should_edit_blocks_on_every_line() {
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("abcd\nef\nghij\n");
+
+        editor.type_keys("l\x16jjld");
+        assert_eq!("ad\ne\ngj\n", editor.buffer.as_string());
+        assert_eq!("bc\nf\nhi", editor.registers.get('"').unwrap().text);
+        editor.type_keys("u\x16jjlIX\x1b");
+        assert_eq!("aXbcd\neXf\ngXhij\n", editor.buffer.as_string());
+        assert_eq!((1, 0), editor.buffer.cursor());
+        editor.type_keys("u");
+        assert_eq!("abcd\nef\nghij\n", editor.buffer.as_string());
+        editor.type_keys("0l\x16jjlAY\x1b");
+        assert_eq!("abcYd\nef Y\nghiYj\n", editor.buffer.as_string());
+        editor.type_keys("u\x16jj$A;\x1b");
+        assert_eq!("abcd;\nef;\nghij;\n", editor.buffer.as_string());
+        editor.type_keys("u0l\x16jcZ\x1b");
+        assert_eq!("aZcd\neZ\nghij\n", editor.buffer.as_string());
+    }
+}
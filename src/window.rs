@@ -0,0 +1,528 @@
+use crate::buffer::Buffer;
+use crate::editor::{DisplayInformation, Editor, Mode};
+use crate::Instant;
+
+// a view of a buffer. the current window's cursor and scroll are the editor's, the ones kept
+// here are from when the window was last left
+#[derive(Debug, Clone)]
+pub struct Window {
+    pub id: usize,
+    pub buffer_number: usize,
+    pub cursor: (usize, usize),
+    pub y_render_offset: usize,
+}
+
+// how the screen is shared between windows. the sizes of a split's children are in lines or
+// columns, and scaled when the screen isn't the size they were given for
+#[derive(Debug, Clone)]
+pub enum Layout {
+    Window(usize),
+    Split {
+        // side by side, with a column between them
+        vertical: bool,
+        children: Vec<(Layout, usize)>,
+    },
+}
+
+// in characters. the last line of a window is its status line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+// what the ui needs to draw a window
+pub struct WindowView<'a> {
+    pub rect: Rect,
+    pub buffer: &'a Buffer,
+    pub path: &'a str,
+    pub cursor: (usize, usize),
+    pub y_render_offset: usize,
+    pub current: bool,
+}
+
+// a line of text and the status line, or a column
+const MIN_HEIGHT: usize = 2;
+const MIN_WIDTH: usize = 1;
+
+impl Layout {
+    fn rects(&self, rect: Rect, rects: &mut Vec<(usize, Rect)>) {
+        match self {
+            Layout::Window(id) => rects.push((*id, rect)),
+            Layout::Split { vertical, children } => {
+                let child_rects = split_rect(rect, *vertical, children);
+                for ((child, _), rect) in children.iter().zip(child_rects) {
+                    child.rects(rect, rects);
+                }
+            }
+        }
+    }
+
+    // the sizes become the lines and columns the children have in rect, so resizing can add
+    // and take away from those
+    fn fit(&mut self, rect: Rect) {
+        if let Layout::Split { vertical, children } = self {
+            let child_rects = split_rect(rect, *vertical, children);
+            for ((child, size), rect) in children.iter_mut().zip(child_rects) {
+                *size = if *vertical { rect.width } else { rect.height };
+                child.fit(rect);
+            }
+        }
+    }
+
+    // the indices of the children to go through to get to the window
+    fn path_to(&self, id: usize) -> Option<Vec<usize>> {
+        match self {
+            Layout::Window(window) => Some(vec![]).filter(|_| *window == id),
+            Layout::Split { children, .. } => {
+                children.iter().enumerate().find_map(|(index, (child, _))| {
+                    let mut path = child.path_to(id)?;
+                    path.insert(0, index);
+                    Some(path)
+                })
+            }
+        }
+    }
+
+    fn node_mut(&mut self, path: &[usize]) -> &mut Layout {
+        match (self, path.split_first()) {
+            (Layout::Split { children, .. }, Some((&index, rest))) => {
+                children[index].0.node_mut(rest)
+            }
+            (node, _) => node,
+        }
+    }
+
+    // a split left with one window is replaced by it
+    pub(crate) fn remove(&mut self, id: usize) {
+        let path = match self.path_to(id) {
+            Some(path) if !path.is_empty() => path,
+            _ => return,
+        };
+        let (&index, parent) = path.split_last().unwrap();
+        let parent = self.node_mut(parent);
+        if let Layout::Split { children, .. } = parent {
+            children.remove(index);
+            if children.len() == 1 {
+                let (only, _) = children.remove(0);
+                *parent = only;
+            }
+        }
+    }
+
+    fn equalize(&mut self) {
+        if let Layout::Split { children, .. } = self {
+            for (child, size) in children {
+                *size = 1;
+                child.equalize();
+            }
+        }
+    }
+}
+
+// the rounding is made up for by the last child
+fn split_rect(rect: Rect, vertical: bool, children: &[(Layout, usize)]) -> Vec<Rect> {
+    let (length, separators) = if vertical {
+        (rect.width, children.len().saturating_sub(1))
+    } else {
+        (rect.height, 0)
+    };
+    let length = length.saturating_sub(separators);
+    let total: usize = children.iter().map(|(_, size)| size).sum();
+    let mut start = 0;
+    let mut given = 0;
+    children
+        .iter()
+        .enumerate()
+        .map(|(index, (_, size))| {
+            let child_length = if index + 1 == children.len() {
+                length - given
+            } else {
+                length * size / total.max(1)
+            };
+            given += child_length;
+            let child = if vertical {
+                Rect {
+                    x: rect.x + start,
+                    width: child_length,
+                    ..rect
+                }
+            } else {
+                Rect {
+                    y: rect.y + start,
+                    height: child_length,
+                    ..rect
+                }
+            };
+            start += child_length + vertical as usize;
+            child
+        })
+        .collect()
+}
+
+impl Editor {
+    // everything between the tab line, when there's more than one tab page, and the command line
+    fn screen_rect(&self, info: &DisplayInformation) -> Rect {
+        let tab_line = !self.other_tabs.is_empty() as usize;
+        Rect {
+            x: 0,
+            y: tab_line,
+            width: info.window_width_in_characters,
+            height: info
+                .window_height_in_characters
+                .saturating_sub(1 + tab_line),
+        }
+    }
+
+    // in the order they're gone through with Ctrl-W w
+    pub fn window_rects(&self, info: &DisplayInformation) -> Vec<(usize, Rect)> {
+        let mut rects = vec![];
+        self.layout.rects(self.screen_rect(info), &mut rects);
+        rects
+    }
+
+    fn current_rect(&self) -> Rect {
+        let rects = self.window_rects(&self.current_display_info);
+        let (_, rect) = rects
+            .into_iter()
+            .find(|(id, _)| *id == self.current_window)
+            .unwrap();
+        rect
+    }
+
+    // lines of text the current window has room for
+    pub(crate) fn window_height(&self) -> usize {
+        self.current_rect().height.saturating_sub(1)
+    }
+
+    pub fn window_views(&self, info: &DisplayInformation) -> Vec<WindowView<'_>> {
+        let mut views = vec![];
+        for (id, rect) in self.window_rects(info) {
+            if id == self.current_window {
+                views.push(WindowView {
+                    rect,
+                    buffer: &self.buffer,
+                    path: &self.editing_file_path,
+                    cursor: self.buffer.cursor(),
+                    y_render_offset: self.y_render_offset,
+                    current: true,
+                });
+                continue;
+            }
+            let window = self.window(id);
+            let (buffer, path) = if window.buffer_number == self.buffer_number {
+                (Some(&self.buffer), self.editing_file_path.as_str())
+            } else {
+                let listed = self
+                    .other_buffers
+                    .iter()
+                    .find(|listed| listed.number == window.buffer_number);
+                match listed {
+                    Some(listed) => (listed.buffer.as_ref(), listed.path.as_str()),
+                    None => (None, ""),
+                }
+            };
+            if let Some(buffer) = buffer {
+                views.push(WindowView {
+                    rect,
+                    buffer,
+                    path,
+                    cursor: window.cursor,
+                    y_render_offset: window.y_render_offset,
+                    current: false,
+                });
+            }
+        }
+        views
+    }
+
+    fn window(&self, id: usize) -> &Window {
+        self.windows.iter().find(|window| window.id == id).unwrap()
+    }
+
+    // the current window keeps what the editor has for it while another one is current
+    pub(crate) fn save_current_window(&mut self) {
+        let (buffer_number, cursor) = (self.buffer_number, self.buffer.cursor());
+        let y_render_offset = self.y_render_offset;
+        let current = self.current_window;
+        let window = self.windows.iter_mut().find(|window| window.id == current);
+        *window.unwrap() = Window {
+            id: current,
+            buffer_number,
+            cursor,
+            y_render_offset,
+        };
+    }
+
+    pub(crate) fn go_to_window(&mut self, id: usize) -> Result<(), String> {
+        if id == self.current_window {
+            return Ok(());
+        }
+        self.save_current_window();
+        self.enter_window(id)
+    }
+
+    // the editor takes the window's buffer, cursor and scroll
+    pub(crate) fn enter_window(&mut self, id: usize) -> Result<(), String> {
+        let window = self.window(id).clone();
+        if window.buffer_number != self.buffer_number {
+            let message = std::mem::take(&mut self.status_message);
+            self.switch_to_buffer(window.buffer_number)?;
+            self.status_message = message;
+        }
+        self.current_window = id;
+        let (x, y) = window.cursor;
+        self.buffer.set_cursor(x, y, Mode::NORMAL);
+        self.y_render_offset = window.y_render_offset.min(self.buffer.line_count() - 1);
+        self.cursor_animation_instant = Instant::now();
+        Ok(())
+    }
+
+    // :split and :vsplit. the new window shows the same buffer and becomes the current one
+    pub fn split_window(&mut self, vertical: bool) -> Result<(), String> {
+        let rect = self.current_rect();
+        let (length, min) = if vertical {
+            (rect.width, MIN_WIDTH * 2 + 1)
+        } else {
+            (rect.height, MIN_HEIGHT * 2)
+        };
+        if length < min {
+            return Err("E36: Not enough room".into());
+        }
+        self.layout
+            .fit(self.screen_rect(&self.current_display_info));
+        self.save_current_window();
+        self.last_window_id += 1;
+        let id = self.last_window_id;
+        let window = Window {
+            id,
+            ..self.window(self.current_window).clone()
+        };
+        self.windows.push(window);
+
+        let path = self.layout.path_to(self.current_window).unwrap();
+        let same_direction = match path.split_last() {
+            Some((_, parent)) => match self.layout.node_mut(parent) {
+                Layout::Split {
+                    vertical: parent, ..
+                } => *parent == vertical,
+                Layout::Window(_) => false,
+            },
+            None => false,
+        };
+        if same_direction {
+            let (&index, parent) = path.split_last().unwrap();
+            if let Layout::Split { children, .. } = self.layout.node_mut(parent) {
+                let size = children[index].1;
+                children[index].1 = size - size / 2;
+                children.insert(index, (Layout::Window(id), size / 2));
+            }
+        } else {
+            let node = self.layout.node_mut(&path);
+            let current = std::mem::replace(node, Layout::Window(id));
+            *node = Layout::Split {
+                vertical,
+                children: vec![(Layout::Window(id), 1), (current, 1)],
+            };
+        }
+        self.current_window = id;
+        Ok(())
+    }
+
+    // :close, the window before it becomes the current one
+    pub fn close_window(&mut self) -> Result<(), String> {
+        if self.windows.len() == 1 {
+            return Err("E444: Cannot close last window".into());
+        }
+        let order = self.window_rects(&self.current_display_info);
+        let index = order
+            .iter()
+            .position(|(id, _)| *id == self.current_window)
+            .unwrap();
+        let (next, _) = order[if index > 0 { index - 1 } else { 1 }];
+        let closed = self.current_window;
+        self.go_to_window(next)?;
+        self.layout.remove(closed);
+        self.windows.retain(|window| window.id != closed);
+        Ok(())
+    }
+
+    // :only
+    pub fn only_window(&mut self) {
+        let current = self.current_window;
+        self.windows.retain(|window| window.id == current);
+        self.layout = Layout::Window(current);
+    }
+
+    // windows showing a buffer that's going away are closed, except the current one
+    pub(crate) fn close_windows_of_buffer(&mut self, number: usize) {
+        self.close_tab_windows_of_buffer(number);
+        let current = self.current_window;
+        let closed: Vec<_> = self
+            .windows
+            .iter()
+            .filter(|window| window.id != current && window.buffer_number == number)
+            .map(|window| window.id)
+            .collect();
+        for id in closed {
+            self.layout.remove(id);
+            self.windows.retain(|window| window.id != id);
+        }
+    }
+
+    // :q closes the window, and the tab page with its last window, quitting once there's only
+    // the one left
+    pub(crate) fn quit_window(&mut self, force: bool) -> Result<(), String> {
+        if self.windows.len() > 1 {
+            return self.close_window();
+        }
+        if !self.other_tabs.is_empty() {
+            return self.close_tab("");
+        }
+        self.check_modified(force)?;
+        self.check_other_buffers(force)?;
+        self.quit = true;
+        Ok(())
+    }
+
+    // Ctrl-W w, the next window from the top left, going around
+    pub fn next_window(&mut self) -> Result<(), String> {
+        let order = self.window_rects(&self.current_display_info);
+        let index = order
+            .iter()
+            .position(|(id, _)| *id == self.current_window)
+            .unwrap();
+        let (next, _) = order[(index + 1) % order.len()];
+        self.go_to_window(next)
+    }
+
+    // Ctrl-W h, j, k and l. of the windows next to this one, the one beside the cursor
+    pub fn go_to_window_beside(&mut self, dx: i64, dy: i64) -> Result<(), String> {
+        let rect = self.current_rect();
+        let (x, y) = self.buffer.cursor();
+        let screen_x = rect.x + x.min(rect.width.saturating_sub(1));
+        let screen_y = rect.y + y.saturating_sub(self.y_render_offset);
+        let beside = |other: &Rect| match (dx, dy) {
+            (1, _) => other.x == rect.x + rect.width + 1,
+            (-1, _) => other.x + other.width + 1 == rect.x,
+            (_, 1) => other.y == rect.y + rect.height,
+            _ => other.y + other.height == rect.y,
+        };
+        let facing = |other: &Rect| {
+            if dx != 0 {
+                (other.y..other.y + other.height).contains(&screen_y)
+            } else {
+                (other.x..=other.x + other.width).contains(&screen_x)
+            }
+        };
+        let rects = self.window_rects(&self.current_display_info);
+        let mut candidates = rects.iter().filter(|(_, other)| beside(other));
+        let first = candidates.clone().next();
+        let target = candidates.find(|(_, other)| facing(other)).or(first);
+        match target {
+            Some(&(id, _)) => self.go_to_window(id),
+            None => Err(String::new()),
+        }
+    }
+
+    // Ctrl-W + - < and >, taking the lines or columns from the window after this one, or
+    // before it for the last one
+    pub fn resize_window(&mut self, delta: i64, vertical: bool) {
+        self.layout
+            .fit(self.screen_rect(&self.current_display_info));
+        let path = self.layout.path_to(self.current_window).unwrap();
+        for depth in (0..path.len()).rev() {
+            let node = self.layout.node_mut(&path[..depth]);
+            let children = match node {
+                Layout::Split {
+                    vertical: direction,
+                    children,
+                } if *direction == vertical => children,
+                _ => continue,
+            };
+            let index = path[depth];
+            let other = if index + 1 < children.len() {
+                index + 1
+            } else {
+                index - 1
+            };
+            let min = if vertical { MIN_WIDTH } else { MIN_HEIGHT } as i64;
+            let (size, other_size) = (children[index].1 as i64, children[other].1 as i64);
+            let delta = delta.max(min - size).min(other_size - min);
+            children[index].1 = (size + delta) as usize;
+            children[other].1 = (other_size - delta) as usize;
+            return;
+        }
+    }
+
+    // Ctrl-W =
+    pub fn equalize_windows(&mut self) {
+        self.layout.equalize();
+    }
+}
+
+mod test {
+    #[test]
+    fn should_split_the_screen_between_windows() {
+        use super::Rect;
+        let rects = |editor: &crate::editor::Editor| {
+            let rects = editor
+                .window_rects(&editor.current_display_info)
+                .into_iter();
+            rects.map(|(_, rect)| rect).collect::<Vec<_>>()
+        };
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let mut editor = crate::editor::Editor::new();
+        editor.buffer = crate::buffer::Buffer::from("one\ntwo\nthree\nfour");
+
+        editor.type_keys(":vsplit<CR>:sp<CR>");
+        assert_eq!(
+            vec![rect(0, 0, 39, 19), rect(0, 19, 39, 20), rect(40, 0, 40, 39)],
+            rects(&editor)
+        );
+        // every window has its own cursor on the buffer
+        editor.type_keys("jj<C-w>jj<C-w>l");
+        assert_eq!((0, 0), editor.buffer.cursor());
+        // of the two windows on the left, the one beside the cursor
+        editor.type_keys("<C-w>h");
+        assert_eq!((0, 2), editor.buffer.cursor());
+        editor.type_keys("<C-w>j");
+        assert_eq!((0, 1), editor.buffer.cursor());
+
+        editor.type_keys("<C-w>k<C-w>+<C-w>+<C-w>><C-w>j<C-w>+");
+        assert_eq!(
+            vec![rect(0, 0, 40, 20), rect(0, 20, 40, 19), rect(41, 0, 39, 39)],
+            rects(&editor)
+        );
+        editor.type_keys("<C-w>=");
+        assert_eq!(
+            vec![rect(0, 0, 39, 19), rect(0, 19, 39, 20), rect(40, 0, 40, 39)],
+            rects(&editor)
+        );
+
+        // windows show buffers of their own
+        editor.run_command("badd other.txt").unwrap();
+        editor.type_keys(":b2<CR>ix<Esc><C-w>w");
+        let views = editor.window_views(&editor.current_display_info);
+        assert_eq!("x", views[1].buffer.as_string());
+        assert_eq!("one\ntwo\nthree\nfour", views[2].buffer.as_string());
+        assert!(views[2].current);
+        editor.type_keys(":close<CR>:only<CR>");
+        assert_eq!(vec![rect(0, 0, 80, 39)], rects(&editor));
+        assert_eq!("x", editor.buffer.as_string());
+        editor.type_keys(":q<CR>");
+        assert!(!editor.quit);
+        editor.type_keys(":sp<CR>:on<CR>");
+        assert_eq!(
+            Err("E444: Cannot close last window".into()),
+            editor.run_command("close")
+        );
+    }
+}